@@ -0,0 +1,525 @@
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::kernel::Kernel;
+use crate::material::{Material, ViscosityModel};
+use crate::method::SimulationMethod;
+use crate::neighbour::{NeighbourSearch, NeighbourSort};
+use crate::solver::PressureSolver;
+use crate::validation::{MAX_FRICTION_ANGLE, MAX_HASH_TABLE_SIZE, MAX_PARTICLES, MIN_HASH_TABLE_SIZE};
+use crate::SimulationParameters;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Group {
+    Simulation,
+    Method,
+    Particles,
+    Fluid,
+    Pressure,
+    Material,
+    Surface,
+    Domain,
+    Kernels
+}
+
+impl Group {
+    pub const ALL: [Group; 9] = [Group::Simulation, Group::Method, Group::Particles, Group::Fluid, Group::Pressure, Group::Material, Group::Surface, Group::Domain, Group::Kernels];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Group::Simulation => "Simulation",
+            Group::Method => "Method",
+            Group::Particles => "Particles",
+            Group::Fluid => "Fluid",
+            Group::Pressure => "Pressure solver",
+            Group::Material => "Material",
+            Group::Surface => "Surface tension",
+            Group::Domain => "Domain",
+            Group::Kernels => "Kernels"
+        }
+    }
+}
+
+//What there is to know about a tunable field, shared by the UI, the command line and the control API
+#[derive(Debug, Clone, Serialize)]
+pub struct ParameterDescriptor {
+    //Path of the field as serialized, the same as timeline tracks use
+    pub name: &'static str,
+    pub label: &'static str,
+    pub unit: &'static str,
+    pub range: RangeInclusive<f32>,
+    //Change per dragged pixel
+    pub step: f64,
+    pub group: Group,
+    pub tooltip: &'static str,
+    //Names of the components of vectors, empty for plain numbers
+    pub components: &'static [&'static str],
+    #[serde(flatten)]
+    pub kind: ParameterKind
+}
+
+//How the field is stored. Values are passed around as floats whatever the kind, integers and ids up to 2²⁴ are exact in them
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind")]
+pub enum ParameterKind {
+    Float {
+        #[serde(skip)]
+        value: fn(&mut SimulationParameters) -> &mut [f32]
+    },
+    //Whole numbers, restart is set for the ones sizing the buffers, which a running simulation can't change
+    Integer {
+        restart: bool,
+        #[serde(skip)]
+        value: fn(&mut SimulationParameters) -> &mut u32
+    },
+    //Whole numbers that have to be powers of two, dragged by their exponent
+    PowerOfTwo {
+        #[serde(skip)]
+        value: fn(&mut SimulationParameters) -> &mut u32
+    },
+    //Id of one of the names, the enums the uniform keeps as u32
+    Choice {
+        names: &'static [&'static str],
+        #[serde(skip)]
+        label: fn(u32) -> &'static str,
+        #[serde(skip)]
+        value: fn(&mut SimulationParameters) -> &mut u32
+    }
+}
+
+impl ParameterDescriptor {
+    pub fn find(name: &str) -> Option<&'static ParameterDescriptor> {
+        PARAMETERS.iter().find(|descriptor| descriptor.name == name)
+    }
+
+    pub fn in_group(group: Group) -> impl Iterator<Item = &'static ParameterDescriptor> {
+        PARAMETERS.iter().filter(move |descriptor| descriptor.group == group)
+    }
+
+    pub fn get(&self, parameters: &SimulationParameters) -> Vec<f32> {
+        let mut parameters = *parameters;
+        match self.kind {
+            ParameterKind::Float { value } => value(&mut parameters).to_vec(),
+            ParameterKind::Integer { value, .. } | ParameterKind::PowerOfTwo { value } | ParameterKind::Choice { value, .. } => {
+                vec![*value(&mut parameters) as f32]
+            }
+        }
+    }
+
+    pub fn default_value(&self) -> Vec<f32> {
+        self.get(&SimulationParameters::default())
+    }
+
+    //Needs a restart to take effect
+    pub fn restart(&self) -> bool {
+        matches!(self.kind, ParameterKind::Integer { restart: true, .. })
+    }
+
+    //Names of choices, the number itself for everything else
+    pub fn format(&self, value: f32) -> String {
+        match self.kind {
+            ParameterKind::Choice { names, .. } => names.get(value as usize).map_or_else(|| value.to_string(), |name| name.to_string()),
+            _ => value.to_string()
+        }
+    }
+
+    pub fn check(&self, values: &[f32]) -> Result<(), String> {
+        for &value in values {
+            match self.kind {
+                ParameterKind::Choice { names, .. } if value.fract() != 0.0 || !self.range.contains(&value) => {
+                    return Err(format!("{} has to be one of {}, got {value}", self.name, names.join(", ")));
+                },
+                ParameterKind::Integer { .. } | ParameterKind::PowerOfTwo { .. } if value.fract() != 0.0 => {
+                    return Err(format!("{} has to be a whole number, got {value}", self.name));
+                },
+                ParameterKind::PowerOfTwo { .. } if !(value as u32).is_power_of_two() => {
+                    return Err(format!("{} has to be a power of two, got {value}", self.name));
+                },
+                _ if !self.range.contains(&value) => {
+                    return Err(format!("{} has to be within {:?}, got {value}", self.name, self.range));
+                },
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set(&self, parameters: &mut SimulationParameters, values: &[f32]) -> Result<(), String> {
+        self.check(values)?;
+        let expected = self.components.len().max(1);
+        if values.len() != expected {
+            return Err(format!("{} takes {expected} values, got {}", self.name, values.len()));
+        }
+        match self.kind {
+            ParameterKind::Float { value } => value(parameters).copy_from_slice(values),
+            ParameterKind::Integer { value, .. } | ParameterKind::PowerOfTwo { value } | ParameterKind::Choice { value, .. } => {
+                *value(parameters) = values[0] as u32;
+            }
+        }
+        Ok(())
+    }
+
+    //Choices are given by their name or their id
+    fn parse(&self, text: &str) -> Result<f32, String> {
+        match self.kind {
+            ParameterKind::Choice { names, .. } => match names.iter().position(|name| name.eq_ignore_ascii_case(text)) {
+                Some(id) => Ok(id as f32),
+                None => text.parse::<f32>().map_err(|_| format!("{} has to be one of {}, got {text}", self.name, names.join(", ")))
+            },
+            _ => text.parse::<f32>().map_err(|err| format!("{text}: {err}"))
+        }
+    }
+}
+
+//A `name=value` assignment, vectors are given as comma separated components and choices by their name
+#[derive(Debug, Clone)]
+pub struct ParameterOverride {
+    pub descriptor: &'static ParameterDescriptor,
+    pub values: Vec<f32>
+}
+
+impl ParameterOverride {
+    pub fn apply(&self, parameters: &mut SimulationParameters) -> Result<(), String> {
+        self.descriptor.set(parameters, &self.values)
+    }
+}
+
+impl std::fmt::Display for ParameterOverride {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let values: Vec<String> = self.values.iter().map(|&value| self.descriptor.format(value)).collect();
+        write!(f, "{}={}", self.descriptor.name, values.join(","))
+    }
+}
+
+impl FromStr for ParameterOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = s.split_once('=').ok_or("expected name=value")?;
+        let descriptor = ParameterDescriptor::find(name.trim()).ok_or_else(|| format!("unknown parameter {}", name.trim()))?;
+        let values = value
+            .split(',')
+            .map(|component| descriptor.parse(component.trim()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let expected = descriptor.components.len().max(1);
+        if values.len() != expected {
+            return Err(format!("{} takes {expected} values, got {}", descriptor.name, values.len()));
+        }
+        descriptor.check(&values)?;
+        Ok(ParameterOverride { descriptor, values })
+    }
+}
+
+//Every tunable field, choices first in their group
+pub static PARAMETERS: &[ParameterDescriptor] = &[
+    ParameterDescriptor {
+        name: "neighbour_search", label: "Neighbour search", unit: "", range: 0.0..=(NeighbourSearch::NAMES.len() - 1) as f32, step: 1.0, group: Group::Simulation,
+        tooltip: "How particles find their neighbours, a hash table of fixed size or one bucket per cell of the bounding box",
+        components: &[], kind: ParameterKind::Choice {
+            names: &NeighbourSearch::NAMES, label: |id| NeighbourSearch::from_id(id).unwrap_or_default().name(), value: |p| &mut p.neighbour_search
+        }
+    },
+    ParameterDescriptor {
+        name: "hash_table_size", label: "Hash table size", unit: "", range: MIN_HASH_TABLE_SIZE as f32..=MAX_HASH_TABLE_SIZE as f32, step: 1.0, group: Group::Simulation,
+        tooltip: "Buckets of the hash table, more make collisions between distant cells rarer",
+        components: &[], kind: ParameterKind::PowerOfTwo { value: |p| &mut p.hash_table_size }
+    },
+    ParameterDescriptor {
+        name: "neighbour_sort", label: "Neighbour sort", unit: "", range: 0.0..=(NeighbourSort::NAMES.len() - 1) as f32, step: 1.0, group: Group::Simulation,
+        tooltip: "How particles are grouped by their bucket",
+        components: &[], kind: ParameterKind::Choice {
+            names: &NeighbourSort::NAMES, label: |id| NeighbourSort::from_id(id).unwrap_or_default().name(), value: |p| &mut p.neighbour_sort
+        }
+    },
+    ParameterDescriptor {
+        name: "pixels_per_metre", label: "Pixels per metre", unit: "px/m", range: 1.0..=10000.0, step: 0.1, group: Group::Simulation,
+        tooltip: "How simulated metres map to the pixels the bounding box and particles are drawn at",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.pixels_per_metre) }
+    },
+    ParameterDescriptor {
+        name: "time_step", label: "Time step", unit: "s", range: 0.0..=1.0, step: 0.0001, group: Group::Simulation,
+        tooltip: "Simulated time per step, smaller steps are more stable",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.time_step) }
+    },
+    ParameterDescriptor {
+        name: "grid_size", label: "Grid size", unit: "m", range: 0.1..=10.0, step: 0.01, group: Group::Simulation,
+        tooltip: "Cell size of the neighbour search, should be at least the largest kernel radius",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.grid_size) }
+    },
+    ParameterDescriptor {
+        name: "velocity_smoothing_scale", label: "Velocity smoothing scale", unit: "", range: 0.0..=1.0, step: 0.001, group: Group::Simulation,
+        tooltip: "How much particles take on the velocity of their neighbours (XSPH)",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.velocity_smoothing_scale) }
+    },
+    ParameterDescriptor {
+        name: "simulation_method", label: "Method", unit: "", range: 0.0..=(SimulationMethod::NAMES.len() - 1) as f32, step: 1.0, group: Group::Method,
+        tooltip: "Particles alone (SPH) or particles carrying the velocity across a grid the pressure is solved on",
+        components: &[], kind: ParameterKind::Choice {
+            names: &SimulationMethod::NAMES, label: |id| SimulationMethod::from_id(id).unwrap_or_default().name(), value: |p| &mut p.simulation_method
+        }
+    },
+    ParameterDescriptor {
+        name: "projection_iterations", label: "Pressure iterations", unit: "", range: 1.0..=1000.0, step: 1.0, group: Group::Method,
+        tooltip: "Jacobi passes per step, more keep the volume better",
+        components: &[], kind: ParameterKind::Integer { restart: false, value: |p| &mut p.projection_iterations }
+    },
+    ParameterDescriptor {
+        name: "flip_ratio", label: "FLIP ratio", unit: "", range: 0.0..=1.0, step: 0.01, group: Group::Method,
+        tooltip: "Share of the velocity change particles take from the grid, the rest is the grid velocity itself. Lively but noisy at 1, smooth but damped at 0 (PIC)",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.flip_ratio) }
+    },
+    ParameterDescriptor {
+        name: "flip_cell_size", label: "Cell size", unit: "m", range: 0.05..=5.0, step: 0.01, group: Group::Method,
+        tooltip: "Size of the grid cells the pressure is solved on, about two particles across",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.flip_cell_size) }
+    },
+    ParameterDescriptor {
+        name: "particles_amount", label: "Particles", unit: "", range: 1.0..=MAX_PARTICLES as f32, step: 1.0, group: Group::Particles,
+        tooltip: "Number of particles, sizes the buffers so it only changes on a restart",
+        components: &[], kind: ParameterKind::Integer { restart: true, value: |p| &mut p.particles_amount }
+    },
+    ParameterDescriptor {
+        name: "particle_mass", label: "Particle's mass", unit: "kg", range: 0.1..=100.0, step: 0.1, group: Group::Particles,
+        tooltip: "Mass of every particle",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.particle_mass) }
+    },
+    ParameterDescriptor {
+        name: "particle_radius", label: "Particles's (draw) radius", unit: "px", range: 0.5..=100.0, step: 0.1, group: Group::Particles,
+        tooltip: "Only changes how particles are drawn",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.particle_radius) }
+    },
+    ParameterDescriptor {
+        name: "collision_damping", label: "Collision damping", unit: "", range: 0.0..=1.0, step: 0.01, group: Group::Particles,
+        tooltip: "Share of the velocity kept when bouncing off the bounding box",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.collision_damping) }
+    },
+    ParameterDescriptor {
+        name: "rest_density", label: "Rest density", unit: "kg/m²", range: 0.0..=1000.0, step: 0.1, group: Group::Fluid,
+        tooltip: "Density the pressure drives the fluid towards, per area since the simulation is 2D",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.rest_density) }
+    },
+    ParameterDescriptor {
+        name: "pressure_solver", label: "Solver", unit: "", range: 0.0..=(PressureSolver::NAMES.len() - 1) as f32, step: 1.0, group: Group::Pressure,
+        tooltip: "How the pressure keeping the fluid at its rest density is found",
+        components: &[], kind: ParameterKind::Choice {
+            names: &PressureSolver::NAMES, label: |id| PressureSolver::from_id(id).unwrap_or_default().name(), value: |p| &mut p.pressure_solver
+        }
+    },
+    ParameterDescriptor {
+        name: "solver_iterations", label: "Iterations", unit: "", range: 1.0..=50.0, step: 1.0, group: Group::Pressure,
+        tooltip: "Passes per step, more converge closer to the rest density",
+        components: &[], kind: ParameterKind::Integer { restart: false, value: |p| &mut p.solver_iterations }
+    },
+    ParameterDescriptor {
+        name: "pressure_multiplier", label: "Pressure multiplier", unit: "", range: 0.0..=10000.0, step: 0.1, group: Group::Pressure,
+        tooltip: "Stiffness of the fluid, higher values compress less but need smaller time steps",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.pressure_multiplier) }
+    },
+    ParameterDescriptor {
+        name: "near_pressure_multiplier", label: "Near pressure multiplier", unit: "", range: 0.0..=10000.0, step: 0.1, group: Group::Pressure,
+        tooltip: "Short range repulsion that keeps particles from clumping",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.near_pressure_multiplier) }
+    },
+    ParameterDescriptor {
+        name: "speed_of_sound", label: "Speed of sound", unit: "m/s", range: 1.0..=1000.0, step: 0.1, group: Group::Pressure,
+        tooltip: "Stiffness of the Tait equation, density varies about with the square of the flow speed over it",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.speed_of_sound) }
+    },
+    ParameterDescriptor {
+        name: "tait_exponent", label: "Tait exponent", unit: "", range: 1.0..=10.0, step: 0.01, group: Group::Pressure,
+        tooltip: "How much faster pressure grows with compression, 7 for water",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.tait_exponent) }
+    },
+    ParameterDescriptor {
+        name: "pbf_relaxation", label: "Constraint relaxation", unit: "1/m²", range: 0.001..=1000.0, step: 0.01, group: Group::Pressure,
+        tooltip: "Softens the density constraints, higher values are more stable but compress more",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.pbf_relaxation) }
+    },
+    ParameterDescriptor {
+        name: "pbf_tensile_coef", label: "Tensile correction", unit: "", range: 0.0..=0.1, step: 0.0001, group: Group::Pressure,
+        tooltip: "Artificial pressure that keeps particles from clumping in sparse regions",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.pbf_tensile_coef) }
+    },
+    ParameterDescriptor {
+        name: "vorticity_inensity", label: "Intensity of vorticity", unit: "", range: 0.0..=1.0, step: 0.01, group: Group::Fluid,
+        tooltip: "Vorticity confinement, brings back swirls lost to numerical damping",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.vorticity_inensity) }
+    },
+    ParameterDescriptor {
+        name: "material", label: "Material", unit: "", range: 0.0..=(Material::NAMES.len() - 1) as f32, step: 1.0, group: Group::Material,
+        tooltip: "A fluid, or grains that pile up like sand",
+        components: &[], kind: ParameterKind::Choice {
+            names: &Material::NAMES, label: |id| Material::from_id(id).unwrap_or_default().name(), value: |p| &mut p.material
+        }
+    },
+    ParameterDescriptor {
+        name: "viscosity_model", label: "Viscosity model", unit: "", range: 0.0..=(ViscosityModel::NAMES.len() - 1) as f32, step: 1.0, group: Group::Material,
+        tooltip: "How the viscosity depends on how fast the fluid is sheared",
+        components: &[], kind: ParameterKind::Choice {
+            names: &ViscosityModel::NAMES, label: |id| ViscosityModel::from_id(id).unwrap_or_default().name(), value: |p| &mut p.viscosity_model
+        }
+    },
+    ParameterDescriptor {
+        name: "viscosity", label: "Viscosity", unit: "", range: 0.0..=1000.0, step: 0.01, group: Group::Material,
+        tooltip: "How strongly neighbouring particles resist moving relative to each other. The consistency of power law fluids, the viscosity at rest of Carreau fluids and the plastic viscosity of Bingham fluids",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.viscosity) }
+    },
+    ParameterDescriptor {
+        name: "flow_index", label: "Flow index", unit: "", range: 0.05..=3.0, step: 0.01, group: Group::Material,
+        tooltip: "Below 1 the fluid thins as it is sheared faster, above 1 it thickens",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.flow_index) }
+    },
+    ParameterDescriptor {
+        name: "infinite_shear_viscosity", label: "Viscosity at high shear", unit: "", range: 0.0..=100.0, step: 0.01, group: Group::Material,
+        tooltip: "Viscosity the Carreau fluid thins down to, the viscosity above is the one at rest",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.infinite_shear_viscosity) }
+    },
+    ParameterDescriptor {
+        name: "relaxation_time", label: "Relaxation time", unit: "s", range: 0.0..=100.0, step: 0.01, group: Group::Material,
+        tooltip: "Inverse of the shear rate the Carreau fluid starts thinning at",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.relaxation_time) }
+    },
+    ParameterDescriptor {
+        name: "yield_stress", label: "Yield stress", unit: "", range: 0.0..=1000.0, step: 0.1, group: Group::Material,
+        tooltip: "Stress below which the Bingham fluid barely flows, in units of viscosity per second",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.yield_stress) }
+    },
+    ParameterDescriptor {
+        name: "max_viscosity", label: "Max viscosity", unit: "", range: 0.0..=1000.0, step: 0.1, group: Group::Material,
+        tooltip: "Cap on the apparent viscosity, which grows without bounds when the fluid is at rest. High values need smaller time steps",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.max_viscosity) }
+    },
+    ParameterDescriptor {
+        name: "spring_stiffness", label: "Spring stiffness", unit: "N/m", range: 0.0..=10000.0, step: 0.1, group: Group::Material,
+        tooltip: "How strongly springs between neighbours pull them back to their rest length",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.spring_stiffness) }
+    },
+    ParameterDescriptor {
+        name: "spring_yield_ratio", label: "Spring yield ratio", unit: "", range: 0.0..=1.0, step: 0.001, group: Group::Material,
+        tooltip: "Share of the rest length a spring stretches or compresses elastically before it deforms for good",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.spring_yield_ratio) }
+    },
+    ParameterDescriptor {
+        name: "plasticity", label: "Plasticity", unit: "1/s", range: 0.0..=100.0, step: 0.01, group: Group::Material,
+        tooltip: "How fast springs stretched beyond their yield take the new length as their rest length",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.plasticity) }
+    },
+    ParameterDescriptor {
+        name: "friction_angle", label: "Friction angle", unit: "°", range: 0.0..=MAX_FRICTION_ANGLE, step: 0.1, group: Group::Material,
+        tooltip: "Steepest slope a pile of cohesionless grains holds",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.friction_angle) }
+    },
+    ParameterDescriptor {
+        name: "granular_cohesion", label: "Cohesion", unit: "N/m", range: 0.0..=10000.0, step: 1.0, group: Group::Material,
+        tooltip: "Shear stress grains withstand without any pressure, and what lets them hold together under tension. Dry sand has none, wet sand or snow some",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.granular_cohesion) }
+    },
+    ParameterDescriptor {
+        name: "shear_modulus", label: "Shear modulus", unit: "N/m", range: 0.0..=100000.0, step: 10.0, group: Group::Material,
+        tooltip: "Stiffness of the grains against shearing before they yield. Stiffer grains need smaller time steps",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.shear_modulus) }
+    },
+    ParameterDescriptor {
+        name: "cohesion_coef", label: "Cohesion coef.", unit: "", range: 0.0..=50000.0, step: 0.1, group: Group::Surface,
+        tooltip: "Attraction between fluid particles",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.cohesion_coef) }
+    },
+    ParameterDescriptor {
+        name: "curvature_cef", label: "Curvature coef.", unit: "", range: 0.0..=50000.0, step: 0.1, group: Group::Surface,
+        tooltip: "Force minimizing the curvature of the surface",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.curvature_cef) }
+    },
+    ParameterDescriptor {
+        name: "adhesion_cef", label: "Adhesion coef.", unit: "", range: 0.0..=50000.0, step: 0.1, group: Group::Surface,
+        tooltip: "Attraction of fluid particles to the walls",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.adhesion_cef) }
+    },
+    ParameterDescriptor {
+        name: "bounding_box.position1", label: "Bounding box min", unit: "px", range: 0.0..=10000.0, step: 1.0, group: Group::Domain,
+        tooltip: "Bottom left corner of the box the particles are kept in",
+        components: &["x1", "y1"], kind: ParameterKind::Float { value: |p| &mut p.bounding_box.position1[..2] }
+    },
+    ParameterDescriptor {
+        name: "bounding_box.position2", label: "Bounding box max", unit: "px", range: 0.0..=10000.0, step: 1.0, group: Group::Domain,
+        tooltip: "Top right corner of the box the particles are kept in",
+        components: &["x2", "y2"], kind: ParameterKind::Float { value: |p| &mut p.bounding_box.position2[..2] }
+    },
+    ParameterDescriptor {
+        name: "gravity", label: "Gravity", unit: "m/s²", range: -1000.0..=1000.0, step: 0.01, group: Group::Domain,
+        tooltip: "Acceleration applied to every particle",
+        components: &["x", "y"], kind: ParameterKind::Float { value: |p| &mut p.gravity[..2] }
+    },
+    ParameterDescriptor {
+        name: "density_kernel", label: "Density kernel", unit: "", range: 0.0..=(Kernel::NAMES.len() - 1) as f32, step: 1.0, group: Group::Kernels,
+        tooltip: "Kernel the density is estimated with",
+        components: &[], kind: ParameterKind::Choice { names: &Kernel::NAMES, label: |id| Kernel::from_id(id).unwrap_or_default().name(), value: |p| &mut p.density_kernel }
+    },
+    ParameterDescriptor {
+        name: "pressure_kernel", label: "Pressure kernel", unit: "", range: 0.0..=(Kernel::NAMES.len() - 1) as f32, step: 1.0, group: Group::Kernels,
+        tooltip: "Kernel whose gradient pushes particles apart",
+        components: &[], kind: ParameterKind::Choice { names: &Kernel::NAMES, label: |id| Kernel::from_id(id).unwrap_or_default().name(), value: |p| &mut p.pressure_kernel }
+    },
+    ParameterDescriptor {
+        name: "viscosity_kernel", label: "Viscosity kernel", unit: "", range: 0.0..=(Kernel::NAMES.len() - 1) as f32, step: 1.0, group: Group::Kernels,
+        tooltip: "Kernel viscosity is smoothed with",
+        components: &[], kind: ParameterKind::Choice { names: &Kernel::NAMES, label: |id| Kernel::from_id(id).unwrap_or_default().name(), value: |p| &mut p.viscosity_kernel }
+    },
+    ParameterDescriptor {
+        name: "surface_normal_kernel", label: "Surface normal kernel", unit: "", range: 0.0..=(Kernel::NAMES.len() - 1) as f32, step: 1.0, group: Group::Kernels,
+        tooltip: "Kernel the surface normals are estimated with",
+        components: &[], kind: ParameterKind::Choice { names: &Kernel::NAMES, label: |id| Kernel::from_id(id).unwrap_or_default().name(), value: |p| &mut p.surface_normal_kernel }
+    },
+    ParameterDescriptor {
+        name: "vorticity_kernel", label: "Vorticity kernel", unit: "", range: 0.0..=(Kernel::NAMES.len() - 1) as f32, step: 1.0, group: Group::Kernels,
+        tooltip: "Kernel the vorticity is estimated with",
+        components: &[], kind: ParameterKind::Choice { names: &Kernel::NAMES, label: |id| Kernel::from_id(id).unwrap_or_default().name(), value: |p| &mut p.vorticity_kernel }
+    },
+    ParameterDescriptor {
+        name: "smoothing_kernel", label: "Velocity smoothing kernel", unit: "", range: 0.0..=(Kernel::NAMES.len() - 1) as f32, step: 1.0, group: Group::Kernels,
+        tooltip: "Kernel velocities are smoothed with (XSPH)",
+        components: &[], kind: ParameterKind::Choice { names: &Kernel::NAMES, label: |id| Kernel::from_id(id).unwrap_or_default().name(), value: |p| &mut p.smoothing_kernel }
+    },
+    ParameterDescriptor {
+        name: "poly_kernel_radius", label: "Density kernel radius", unit: "m", range: 0.1..=5.0, step: 0.01, group: Group::Kernels,
+        tooltip: "Neighbourhood the density is estimated from",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.poly_kernel_radius) }
+    },
+    ParameterDescriptor {
+        name: "pressure_kernel_radius", label: "Pressure kernel radius", unit: "m", range: 0.1..=5.0, step: 0.01, group: Group::Kernels,
+        tooltip: "Neighbourhood pressure acts in",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.pressure_kernel_radius) }
+    },
+    ParameterDescriptor {
+        name: "near_pressure_kernel_radius", label: "Near pressure kernel radius", unit: "m", range: 0.1..=5.0, step: 0.01, group: Group::Kernels,
+        tooltip: "Neighbourhood near pressure acts in",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.near_pressure_kernel_radius) }
+    },
+    ParameterDescriptor {
+        name: "viscosity_kernel_radius", label: "Viscosity kernel radius", unit: "m", range: 0.1..=5.0, step: 0.01, group: Group::Kernels,
+        tooltip: "Neighbourhood viscosity acts in",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.viscosity_kernel_radius) }
+    },
+    ParameterDescriptor {
+        name: "vorticity_kernel_radius", label: "Vorticity kernel radius", unit: "m", range: 0.1..=5.0, step: 0.01, group: Group::Kernels,
+        tooltip: "Neighbourhood the vorticity is estimated from",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.vorticity_kernel_radius) }
+    },
+    ParameterDescriptor {
+        name: "cohesion_kernel_radius", label: "Cohesion kernel radius", unit: "m", range: 0.1..=5.0, step: 0.01, group: Group::Kernels,
+        tooltip: "Neighbourhood cohesion acts in",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.cohesion_kernel_radius) }
+    },
+    ParameterDescriptor {
+        name: "adhesion_kernel_radius", label: "Adhesion kernel radius", unit: "m", range: 0.1..=5.0, step: 0.01, group: Group::Kernels,
+        tooltip: "Distance to the walls adhesion acts in",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.adhesion_kernel_radius) }
+    },
+    ParameterDescriptor {
+        name: "surface_normal_kernel_radius", label: "Surface normal kernel radius", unit: "m", range: 0.1..=5.0, step: 0.01, group: Group::Kernels,
+        tooltip: "Neighbourhood the surface normals are estimated from",
+        components: &[], kind: ParameterKind::Float { value: |p| std::slice::from_mut(&mut p.surface_normal_kernel_radius) }
+    }
+];
@@ -0,0 +1,184 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::SimulationParameters;
+
+//The simulation is 2D, kernels are normalized for it
+pub const DIMENSIONS: u32 = 2;
+
+//Smoothing kernels, all with support radius h and normalized so they integrate to 1.
+//Mirrored by `kernel` and `d1_kernel` in simulation.wgsl, which has to match
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum Kernel {
+    //(1 - q)², used for density and pressure from the start
+    #[default]
+    QuadraticSpiky,
+    CubicSpline,
+    WendlandC2,
+    WendlandC4,
+    Poly6,
+    //(1 - q)³, doesn't flatten out towards the centre, so pressure keeps particles apart
+    Spiky
+}
+
+impl Kernel {
+    pub const ALL: [Kernel; 6] = [Kernel::QuadraticSpiky, Kernel::CubicSpline, Kernel::WendlandC2, Kernel::WendlandC4, Kernel::Poly6, Kernel::Spiky];
+
+    //As serialized, in the order of the ids
+    pub const NAMES: [&str; 6] = ["QuadraticSpiky", "CubicSpline", "WendlandC2", "WendlandC4", "Poly6", "Spiky"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Kernel::QuadraticSpiky => "Quadratic spiky",
+            Kernel::CubicSpline => "Cubic spline",
+            Kernel::WendlandC2 => "Wendland C2",
+            Kernel::WendlandC4 => "Wendland C4",
+            Kernel::Poly6 => "Poly6",
+            Kernel::Spiky => "Spiky"
+        }
+    }
+
+    //Index in the GPU uniform
+    pub fn id(self) -> u32 {
+        self as u32
+    }
+
+    pub fn from_id(id: u32) -> Option<Self> {
+        Kernel::ALL.get(id as usize).copied()
+    }
+
+    //Factor that makes the kernel integrate to 1, times h^dimensions
+    fn normalization(self, dimensions: u32) -> f32 {
+        use std::f32::consts::PI;
+        match (self, dimensions) {
+            (Kernel::QuadraticSpiky, 2) => 6.0 / PI,
+            (Kernel::QuadraticSpiky, _) => 15.0 / (2.0 * PI),
+            (Kernel::CubicSpline, 2) => 40.0 / (7.0 * PI),
+            (Kernel::CubicSpline, _) => 8.0 / PI,
+            (Kernel::WendlandC2, 2) => 7.0 / PI,
+            (Kernel::WendlandC2, _) => 21.0 / (2.0 * PI),
+            (Kernel::WendlandC4, 2) => 9.0 / PI,
+            (Kernel::WendlandC4, _) => 495.0 / (32.0 * PI),
+            (Kernel::Poly6, 2) => 4.0 / PI,
+            (Kernel::Poly6, _) => 315.0 / (64.0 * PI),
+            (Kernel::Spiky, 2) => 10.0 / PI,
+            (Kernel::Spiky, _) => 15.0 / PI
+        }
+    }
+
+    //Unnormalized kernel over q = r / h
+    fn shape(self, q: f32) -> f32 {
+        match self {
+            Kernel::QuadraticSpiky => (1.0 - q).powi(2),
+            Kernel::CubicSpline => {
+                if q <= 0.5 { 6.0 * (q * q * q - q * q) + 1.0 } else { 2.0 * (1.0 - q).powi(3) }
+            },
+            Kernel::WendlandC2 => (1.0 - q).powi(4) * (1.0 + 4.0 * q),
+            Kernel::WendlandC4 => (1.0 - q).powi(6) * (1.0 + 6.0 * q + 35.0 / 3.0 * q * q),
+            Kernel::Poly6 => (1.0 - q * q).powi(3),
+            Kernel::Spiky => (1.0 - q).powi(3)
+        }
+    }
+
+    //Derivative of the shape with respect to q
+    fn shape_derivative(self, q: f32) -> f32 {
+        match self {
+            Kernel::QuadraticSpiky => -2.0 * (1.0 - q),
+            Kernel::CubicSpline => {
+                if q <= 0.5 { 6.0 * (3.0 * q * q - 2.0 * q) } else { -6.0 * (1.0 - q).powi(2) }
+            },
+            Kernel::WendlandC2 => -20.0 * q * (1.0 - q).powi(3),
+            Kernel::WendlandC4 => -56.0 / 3.0 * q * (1.0 + 5.0 * q) * (1.0 - q).powi(5),
+            Kernel::Poly6 => -6.0 * q * (1.0 - q * q).powi(2),
+            Kernel::Spiky => -3.0 * (1.0 - q).powi(2)
+        }
+    }
+
+    //W(r) for distance r and support radius h, in 1/m^dimensions
+    pub fn value(self, r: f32, h: f32, dimensions: u32) -> f32 {
+        if r > h {
+            return 0.0;
+        }
+        self.normalization(dimensions) / h.powi(dimensions as i32) * self.shape(r / h)
+    }
+
+    //How fast the kernel falls off, -dW/dr. The gradient is this times the unit vector towards the neighbour
+    pub fn slope(self, r: f32, h: f32, dimensions: u32) -> f32 {
+        if r > h {
+            return 0.0;
+        }
+        -self.normalization(dimensions) / h.powi(dimensions as i32 + 1) * self.shape_derivative(r / h)
+    }
+}
+
+//Force terms that pick their kernel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KernelTerm {
+    Density,
+    Pressure,
+    Viscosity,
+    SurfaceNormal,
+    Vorticity,
+    Smoothing
+}
+
+impl KernelTerm {
+    pub const ALL: [KernelTerm; 6] = [
+        KernelTerm::Density, KernelTerm::Pressure, KernelTerm::Viscosity, KernelTerm::SurfaceNormal, KernelTerm::Vorticity, KernelTerm::Smoothing
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            KernelTerm::Density => "Density",
+            KernelTerm::Pressure => "Pressure",
+            KernelTerm::Viscosity => "Viscosity",
+            KernelTerm::SurfaceNormal => "Surface normals",
+            KernelTerm::Vorticity => "Vorticity",
+            KernelTerm::Smoothing => "Velocity smoothing"
+        }
+    }
+
+    pub fn id(self, parameters: &SimulationParameters) -> u32 {
+        match self {
+            KernelTerm::Density => parameters.density_kernel,
+            KernelTerm::Pressure => parameters.pressure_kernel,
+            KernelTerm::Viscosity => parameters.viscosity_kernel,
+            KernelTerm::SurfaceNormal => parameters.surface_normal_kernel,
+            KernelTerm::Vorticity => parameters.vorticity_kernel,
+            KernelTerm::Smoothing => parameters.smoothing_kernel
+        }
+    }
+
+    pub fn id_mut(self, parameters: &mut SimulationParameters) -> &mut u32 {
+        match self {
+            KernelTerm::Density => &mut parameters.density_kernel,
+            KernelTerm::Pressure => &mut parameters.pressure_kernel,
+            KernelTerm::Viscosity => &mut parameters.viscosity_kernel,
+            KernelTerm::SurfaceNormal => &mut parameters.surface_normal_kernel,
+            KernelTerm::Vorticity => &mut parameters.vorticity_kernel,
+            KernelTerm::Smoothing => &mut parameters.smoothing_kernel
+        }
+    }
+}
+
+impl SimulationParameters {
+    //Unknown ids fall back to the default kernel
+    pub fn kernel(&self, term: KernelTerm) -> Kernel {
+        Kernel::from_id(term.id(self)).unwrap_or_default()
+    }
+}
+
+//The uniform keeps kernels as their ids, files and messages use their names
+pub(crate) mod as_name {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Kernel;
+
+    pub fn serialize<S: Serializer>(id: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+        Kernel::from_id(*id).unwrap_or_default().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+        Ok(Kernel::deserialize(deserializer)?.id())
+    }
+}
@@ -1,3 +1,17 @@
 pub mod settings;
+pub mod descriptor;
+pub mod protocol;
+pub mod transport;
+pub mod preset;
+pub mod scene;
+pub mod timeline;
+pub mod solver;
+pub mod kernel;
+pub mod material;
+pub mod method;
+pub mod neighbour;
+pub mod validation;
+#[cfg(feature = "ui")]
+pub mod ui;
 
-pub use settings::*;
+pub use settings::*;
\ No newline at end of file
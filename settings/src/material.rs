@@ -0,0 +1,142 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::SimulationParameters;
+
+//What the particles are made of
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum Material {
+    //Pressure keeps it from compressing, viscosity from shearing
+    #[default]
+    Fluid,
+    //Grains that resist shearing elastically up to a Drucker–Prager yield stress, like sand or snow.
+    //Pressure still comes from the pressure solver, without the tension cohesion can't hold
+    Granular
+}
+
+impl Material {
+    pub const ALL: [Material; 2] = [Material::Fluid, Material::Granular];
+
+    //As serialized, in the order of the ids
+    pub const NAMES: [&str; 2] = ["Fluid", "Granular"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Material::Fluid => "Fluid",
+            Material::Granular => "Granular"
+        }
+    }
+
+    //Index in the GPU uniform
+    pub fn id(self) -> u32 {
+        self as u32
+    }
+
+    pub fn from_id(id: u32) -> Option<Self> {
+        Material::ALL.get(id as usize).copied()
+    }
+
+    //Descriptors of the parameters the material reads besides the viscosity model's
+    pub fn parameters(self) -> &'static [&'static str] {
+        match self {
+            Material::Fluid => &[],
+            Material::Granular => &["friction_angle", "granular_cohesion", "shear_modulus"]
+        }
+    }
+}
+
+//How the fluid resists shearing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum ViscosityModel {
+    //Constant viscosity, like water
+    #[default]
+    Newtonian,
+    //Viscosity K·γ̇^(n-1), shear thinning below a flow index of 1 and thickening above
+    PowerLaw,
+    //Shear thinning between a viscosity at rest and one at high shear rates, like blood or honey
+    Carreau,
+    //Barely flows until the stress exceeds the yield stress, like ketchup
+    Bingham,
+    //Newtonian viscosity plus springs between neighbours that stretch and settle, like slime
+    Viscoelastic
+}
+
+impl ViscosityModel {
+    pub const ALL: [ViscosityModel; 5] = [
+        ViscosityModel::Newtonian, ViscosityModel::PowerLaw, ViscosityModel::Carreau, ViscosityModel::Bingham, ViscosityModel::Viscoelastic
+    ];
+
+    //As serialized, in the order of the ids
+    pub const NAMES: [&str; 5] = ["Newtonian", "PowerLaw", "Carreau", "Bingham", "Viscoelastic"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ViscosityModel::Newtonian => "Newtonian",
+            ViscosityModel::PowerLaw => "Power law",
+            ViscosityModel::Carreau => "Carreau",
+            ViscosityModel::Bingham => "Bingham plastic",
+            ViscosityModel::Viscoelastic => "Viscoelastic"
+        }
+    }
+
+    //Index in the GPU uniform
+    pub fn id(self) -> u32 {
+        self as u32
+    }
+
+    pub fn from_id(id: u32) -> Option<Self> {
+        ViscosityModel::ALL.get(id as usize).copied()
+    }
+
+    //Descriptors of the parameters the model reads
+    pub fn parameters(self) -> &'static [&'static str] {
+        match self {
+            ViscosityModel::Newtonian => &["viscosity"],
+            ViscosityModel::PowerLaw => &["viscosity", "flow_index", "max_viscosity"],
+            ViscosityModel::Carreau => &["viscosity", "infinite_shear_viscosity", "relaxation_time", "flow_index", "max_viscosity"],
+            ViscosityModel::Bingham => &["viscosity", "yield_stress", "max_viscosity"],
+            ViscosityModel::Viscoelastic => &["viscosity", "spring_stiffness", "spring_yield_ratio", "plasticity"]
+        }
+    }
+}
+
+impl SimulationParameters {
+    //Unknown ids fall back to the default material
+    pub fn material(&self) -> Material {
+        Material::from_id(self.material).unwrap_or_default()
+    }
+
+    //Unknown ids fall back to the default model
+    pub fn viscosity_model(&self) -> ViscosityModel {
+        ViscosityModel::from_id(self.viscosity_model).unwrap_or_default()
+    }
+}
+
+//The uniform keeps the model as its id, files and messages use its name
+pub(crate) mod as_name {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::ViscosityModel;
+
+    pub fn serialize<S: Serializer>(id: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+        ViscosityModel::from_id(*id).unwrap_or_default().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+        Ok(ViscosityModel::deserialize(deserializer)?.id())
+    }
+}
+
+pub(crate) mod material_as_name {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Material;
+
+    pub fn serialize<S: Serializer>(id: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+        Material::from_id(*id).unwrap_or_default().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+        Ok(Material::deserialize(deserializer)?.id())
+    }
+}
@@ -0,0 +1,90 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::SimulationParameters;
+
+//How the particles are moved
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum SimulationMethod {
+    //Smoothed particle hydrodynamics, forces between neighbouring particles
+    #[default]
+    Sph,
+    //Velocities are transferred to a grid, made divergence-free there and the change is transferred back
+    Flip,
+    //Like FLIP, but the particles carry an affine velocity field instead of taking the change
+    Apic
+}
+
+impl SimulationMethod {
+    pub const ALL: [SimulationMethod; 3] = [SimulationMethod::Sph, SimulationMethod::Flip, SimulationMethod::Apic];
+
+    //As serialized, in the order of the ids
+    pub const NAMES: [&str; 3] = ["Sph", "Flip", "Apic"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SimulationMethod::Sph => "SPH",
+            SimulationMethod::Flip => "FLIP/PIC",
+            SimulationMethod::Apic => "APIC"
+        }
+    }
+
+    //Index in the GPU uniform
+    pub fn id(self) -> u32 {
+        self as u32
+    }
+
+    pub fn from_id(id: u32) -> Option<Self> {
+        SimulationMethod::ALL.get(id as usize).copied()
+    }
+
+    //Methods projecting the velocities on a grid instead of searching neighbours
+    pub fn is_grid_based(self) -> bool {
+        matches!(self, SimulationMethod::Flip | SimulationMethod::Apic)
+    }
+
+    //Descriptors of the parameters the method reads
+    pub fn parameters(self) -> &'static [&'static str] {
+        match self {
+            SimulationMethod::Sph => &[],
+            SimulationMethod::Flip => &["flip_ratio", "flip_cell_size", "projection_iterations"],
+            SimulationMethod::Apic => &["flip_cell_size", "projection_iterations"]
+        }
+    }
+}
+
+impl SimulationParameters {
+    //Unknown ids fall back to the default method
+    pub fn simulation_method(&self) -> SimulationMethod {
+        SimulationMethod::from_id(self.simulation_method).unwrap_or_default()
+    }
+
+    //Cells of the FLIP and APIC grid along x and y, covering the bounding box with cells of flip_cell_size
+    pub fn flip_grid_size(&self) -> [u32; 2] {
+        let cell_size = self.flip_cell_size * self.pixels_per_metre;
+        let (position1, position2) = (self.bounding_box.position1, self.bounding_box.position2);
+        let cells = |axis: usize| (((position2[axis] - position1[axis]) / cell_size).ceil() as u32).max(1);
+        [cells(0), cells(1)]
+    }
+
+    //Velocity faces of the grid, u faces and v faces. Saturates instead of overflowing for absurd boxes
+    pub fn flip_grid_faces(&self) -> u64 {
+        let [x, y] = self.flip_grid_size().map(u64::from);
+        (x + 1).saturating_mul(y).saturating_add(x.saturating_mul(y + 1))
+    }
+}
+
+//The uniform keeps the method as its id, files and messages use its name
+pub(crate) mod as_name {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::SimulationMethod;
+
+    pub fn serialize<S: Serializer>(id: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+        SimulationMethod::from_id(*id).unwrap_or_default().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+        Ok(SimulationMethod::deserialize(deserializer)?.id())
+    }
+}
@@ -0,0 +1,121 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::SimulationParameters;
+
+//How particles find the cell list of their neighbours
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum NeighbourSearch {
+    //Cells are hashed into a table of hash_table_size buckets, distant cells may share one
+    #[default]
+    Hashed,
+    //One bucket per cell of the bounding box, no collisions but the table grows with the domain
+    Dense
+}
+
+impl NeighbourSearch {
+    pub const ALL: [NeighbourSearch; 2] = [NeighbourSearch::Hashed, NeighbourSearch::Dense];
+
+    //As serialized, in the order of the ids
+    pub const NAMES: [&str; 2] = ["Hashed", "Dense"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NeighbourSearch::Hashed => "Hashed",
+            NeighbourSearch::Dense => "Dense grid"
+        }
+    }
+
+    //Index in the GPU uniform
+    pub fn id(self) -> u32 {
+        self as u32
+    }
+
+    pub fn from_id(id: u32) -> Option<Self> {
+        NeighbourSearch::ALL.get(id as usize).copied()
+    }
+}
+
+//How particles are grouped by their bucket
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum NeighbourSort {
+    //Radix sort of the bucket keys, then a pass to find where each bucket starts and ends
+    #[default]
+    Radix,
+    //Particles are counted per bucket and scattered to their place, which gives the bucket ranges directly
+    Counting
+}
+
+impl NeighbourSort {
+    pub const ALL: [NeighbourSort; 2] = [NeighbourSort::Radix, NeighbourSort::Counting];
+
+    //As serialized, in the order of the ids
+    pub const NAMES: [&str; 2] = ["Radix", "Counting"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NeighbourSort::Radix => "Radix sort",
+            NeighbourSort::Counting => "Counting sort"
+        }
+    }
+
+    pub fn id(self) -> u32 {
+        self as u32
+    }
+
+    pub fn from_id(id: u32) -> Option<Self> {
+        NeighbourSort::ALL.get(id as usize).copied()
+    }
+}
+
+impl SimulationParameters {
+    //Unknown ids fall back to the default search
+    pub fn neighbour_search(&self) -> NeighbourSearch {
+        NeighbourSearch::from_id(self.neighbour_search).unwrap_or_default()
+    }
+
+    pub fn neighbour_sort(&self) -> NeighbourSort {
+        NeighbourSort::from_id(self.neighbour_sort).unwrap_or_default()
+    }
+
+    //Cells of the dense grid along x and y, the bounding box is in pixels and the grid size in metres
+    pub fn dense_grid_size(&self) -> [u32; 2] {
+        let cell_size = self.grid_size * self.pixels_per_metre;
+        let (position1, position2) = (self.bounding_box.position1, self.bounding_box.position2);
+        let cells = |axis: usize| {
+            let first = (position1[axis] / cell_size).floor();
+            let last = (position2[axis] / cell_size).floor();
+            (last - first).max(0.0) as u32 + 1
+        };
+        [cells(0), cells(1)]
+    }
+}
+
+//The uniform keeps the search and the sort as their ids, files and messages use their names
+pub(crate) mod as_name {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::NeighbourSearch;
+
+    pub fn serialize<S: Serializer>(id: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+        NeighbourSearch::from_id(*id).unwrap_or_default().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+        Ok(NeighbourSearch::deserialize(deserializer)?.id())
+    }
+}
+
+pub(crate) mod sort_as_name {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::NeighbourSort;
+
+    pub fn serialize<S: Serializer>(id: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+        NeighbourSort::from_id(*id).unwrap_or_default().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+        Ok(NeighbourSort::deserialize(deserializer)?.id())
+    }
+}
@@ -0,0 +1,313 @@
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::timeline::Timeline;
+use crate::SimulationParameters;
+
+//Named set of parameters, stored as TOML or JSON depending on the file extension
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    pub parameters: SimulationParameters,
+    #[serde(default, skip_serializing_if = "Timeline::is_empty")]
+    pub timeline: Timeline
+}
+
+#[derive(Debug)]
+pub enum PresetError {
+    Io(std::io::Error),
+    Toml(String),
+    Json(serde_json::Error),
+    UnknownFormat,
+    //A scene sets the viscosity without saying which scale it is on
+    AmbiguousViscosity
+}
+
+impl std::fmt::Display for PresetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PresetError::Io(err) => write!(f, "{err}"),
+            PresetError::Toml(err) => write!(f, "{err}"),
+            PresetError::Json(err) => write!(f, "{err}"),
+            PresetError::UnknownFormat => write!(f, "presets have to be .toml or .json files"),
+            PresetError::AmbiguousViscosity => write!(f, "viscosity is set without viscosity_kernel, set both since the viscosity used to be 10/h³ times smaller (h the viscosity kernel radius)")
+        }
+    }
+}
+
+impl std::error::Error for PresetError {}
+
+impl From<std::io::Error> for PresetError {
+    fn from(err: std::io::Error) -> Self {
+        PresetError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for PresetError {
+    fn from(err: serde_json::Error) -> Self {
+        PresetError::Json(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresetFormat {
+    Toml,
+    Json
+}
+
+impl PresetFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "toml" => Some(PresetFormat::Toml),
+            "json" => Some(PresetFormat::Json),
+            _ => None
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            PresetFormat::Toml => "toml",
+            PresetFormat::Json => "json"
+        }
+    }
+}
+
+impl Preset {
+    pub fn new(name: &str, parameters: SimulationParameters) -> Self {
+        Preset {
+            name: name.to_string(),
+            parameters,
+            timeline: Timeline::default()
+        }
+    }
+
+    pub fn with_timeline(mut self, timeline: Timeline) -> Self {
+        self.timeline = timeline;
+        self
+    }
+
+    //Presets shipped with the simulation
+    pub fn builtin() -> Vec<Preset> {
+        let defaults = SimulationParameters::default();
+
+        let mut calm_water = defaults;
+        calm_water.viscosity = 4.6;
+        calm_water.vorticity_inensity = 0.1;
+        calm_water.collision_damping = 0.5;
+        calm_water.velocity_smoothing_scale = 0.05;
+
+        let mut honey = defaults;
+        honey.viscosity = 185.0;
+        honey.velocity_smoothing_scale = 0.3;
+        honey.vorticity_inensity = 0.0;
+        honey.cohesion_coef = 5.0;
+        honey.collision_damping = 0.2;
+        honey.rest_density = 40.0;
+
+        let mut splashy = defaults;
+        splashy.viscosity = 0.46;
+        splashy.vorticity_inensity = 1.0;
+        splashy.collision_damping = 1.0;
+        splashy.velocity_smoothing_scale = 0.01;
+        splashy.pressure_multiplier = 1800.0;
+        splashy.near_pressure_multiplier = 160.0;
+
+        vec![
+            Preset::new("Default", defaults),
+            Preset::new("Calm water", calm_water),
+            Preset::new("Honey", honey),
+            Preset::new("Splashy", splashy)
+        ]
+    }
+
+    pub fn to_text(&self, format: PresetFormat) -> Result<String, PresetError> {
+        match format {
+            PresetFormat::Toml => toml::to_string_pretty(self).map_err(|err| PresetError::Toml(err.to_string())),
+            PresetFormat::Json => Ok(serde_json::to_string_pretty(self)?)
+        }
+    }
+
+    pub fn from_text(text: &str, format: PresetFormat) -> Result<Self, PresetError> {
+        parse(text, format)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), PresetError> {
+        let format = PresetFormat::from_path(path).ok_or(PresetError::UnknownFormat)?;
+        std::fs::write(path, self.to_text(format)?)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, PresetError> {
+        let format = PresetFormat::from_path(path).ok_or(PresetError::UnknownFormat)?;
+        Preset::from_text(&std::fs::read_to_string(path)?, format)
+    }
+}
+
+//Names of the parameters that differ between the two sets
+pub fn differences(parameters: &SimulationParameters, base: &SimulationParameters) -> Vec<String> {
+    let (Ok(serde_json::Value::Object(a)), Ok(serde_json::Value::Object(b))) = (serde_json::to_value(parameters), serde_json::to_value(base)) else {
+        return vec![];
+    };
+    a.into_iter().filter(|(name, value)| b.get(name) != Some(value)).map(|(name, _)| name).collect()
+}
+
+//Presets and scenes go through JSON values, so files from older versions can be brought up to date first
+pub(crate) fn parse_value(text: &str, format: PresetFormat) -> Result<Value, PresetError> {
+    let mut value: Value = match format {
+        PresetFormat::Toml => toml::from_str(text).map_err(|err| PresetError::Toml(err.to_string()))?,
+        PresetFormat::Json => serde_json::from_str(text)?
+    };
+    upgrade(&mut value);
+    Ok(value)
+}
+
+fn parse<T: DeserializeOwned>(text: &str, format: PresetFormat) -> Result<T, PresetError> {
+    let mut value = parse_value(text, format)?;
+    upgrade_viscosity(&mut value);
+    Ok(serde_json::from_value(value)?)
+}
+
+//Before parameters were in metres, lengths were scaled by `scene_scale_factor`, the inverse of pixels per metre
+fn upgrade(value: &mut Value) {
+    let Some(parameters) = value.get_mut("parameters").and_then(Value::as_object_mut) else { return; };
+    if let Some(scale) = parameters.remove("scene_scale_factor").and_then(|scale| scale.as_f64()) {
+        if scale > 0.0 {
+            parameters.entry("pixels_per_metre").or_insert((1.0 / scale).into());
+        }
+    }
+}
+
+//Before kernels were selectable, viscosity used an unnormalized kernel that weighed 10/h³ times as much as the
+//normalized ones. Presets are saved complete, so one without a viscosity kernel is from before. Scenes are
+//written by hand with only some fields, there it can't be told apart and check_viscosity refuses the scene instead
+fn upgrade_viscosity(value: &mut Value) {
+    let Some(parameters) = value.get_mut("parameters").and_then(Value::as_object_mut) else { return; };
+    if parameters.contains_key("viscosity_kernel") {
+        return;
+    }
+    let radius = parameters.get("viscosity_kernel_radius").and_then(Value::as_f64)
+        .unwrap_or(SimulationParameters::default().viscosity_kernel_radius as f64);
+    if let Some(viscosity) = parameters.get("viscosity").and_then(Value::as_f64) {
+        if radius > 0.0 {
+            parameters.insert("viscosity".to_string(), (viscosity * 10.0 / radius.powi(3)).into());
+        }
+    }
+}
+
+pub(crate) fn check_viscosity(value: &Value) -> Result<(), PresetError> {
+    let Some(parameters) = value.get("parameters").and_then(Value::as_object) else { return Ok(()); };
+    if parameters.contains_key("viscosity") && !parameters.contains_key("viscosity_kernel") {
+        return Err(PresetError::AmbiguousViscosity);
+    }
+    Ok(())
+}
+
+const SESSION_FILE: &str = "session.toml";
+
+//Per user configuration directory, following XDG on unix and APPDATA on windows
+pub fn config_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME").map(PathBuf::from)
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("water-simulation"))
+}
+
+pub fn load_session() -> Option<Preset> {
+    let path = config_dir()?.join(SESSION_FILE);
+    if !path.exists() {
+        return None;
+    }
+
+    match Preset::load(&path) {
+        Ok(preset) => Some(preset),
+        Err(err) => {
+            log::error!("Failed to restore the last session from {}: {err}", path.display());
+            None
+        }
+    }
+}
+
+pub fn save_session(parameters: SimulationParameters, timeline: &Timeline) {
+    let Some(dir) = config_dir() else { return; };
+    let path = dir.join(SESSION_FILE);
+    let res = std::fs::create_dir_all(&dir).map_err(Into::into)
+        .and_then(|_| Preset::new("Last session", parameters).with_timeline(timeline.clone()).save(&path));
+    if let Err(err) = res {
+        log::error!("Failed to save the session to {}: {err}", path.display());
+    }
+}
+
+//Built-in presets followed by the ones saved in the presets directory
+pub struct PresetStore {
+    pub presets: Vec<Preset>,
+    //File of every preset, None for built-ins
+    pub paths: Vec<Option<PathBuf>>
+}
+
+impl Default for PresetStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PresetStore {
+    pub fn new() -> Self {
+        let mut store = PresetStore {
+            presets: vec![],
+            paths: vec![]
+        };
+        store.reload();
+        store
+    }
+
+    fn dir() -> Option<PathBuf> {
+        Some(config_dir()?.join("presets"))
+    }
+
+    pub fn reload(&mut self) {
+        self.presets = Preset::builtin();
+        self.paths = vec![None; self.presets.len()];
+
+        let Some(entries) = Self::dir().and_then(|dir| std::fs::read_dir(dir).ok()) else { return; };
+        let mut paths: Vec<PathBuf> = entries.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| PresetFormat::from_path(path).is_some())
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            match Preset::load(&path) {
+                Ok(preset) => {
+                    self.presets.push(preset);
+                    self.paths.push(Some(path));
+                },
+                Err(err) => log::error!("Failed to load preset {}: {err}", path.display())
+            }
+        }
+    }
+
+    pub fn save(&mut self, preset: &Preset, format: PresetFormat) -> Result<(), String> {
+        let dir = Self::dir().ok_or("no configuration directory")?;
+        std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+
+        //Keep the file name portable whatever the preset is called
+        let file_name: String = preset.name.chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        let path = dir.join(format!("{file_name}.{}", format.extension()));
+        preset.save(&path).map_err(|err| err.to_string())?;
+
+        self.reload();
+        Ok(())
+    }
+
+    pub fn delete(&mut self, index: usize) -> Result<(), String> {
+        if let Some(Some(path)) = self.paths.get(index) {
+            std::fs::remove_file(path).map_err(|err| err.to_string())?;
+        }
+        self.reload();
+        Ok(())
+    }
+}
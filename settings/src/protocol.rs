@@ -0,0 +1,149 @@
+use std::io::{Read, Write};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::timeline::Timeline;
+use crate::SimulationParameters;
+
+//Bump whenever a message changes, peers with a different version are refused during the handshake
+pub const PROTOCOL_VERSION: u32 = 13;
+
+
+//Anything bigger is a corrupted or foreign stream
+const MAX_MESSAGE_SIZE: u32 = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum Command {
+    Pause,
+    Resume,
+    //Advance a paused simulation by one step
+    Step,
+    //Put the particles back on the initial grid
+    Reset,
+    Snapshot,
+    Screenshot
+}
+
+//Statistics the simulation publishes periodically
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Telemetry {
+    pub frame: u64,
+    //Simulated time in seconds
+    pub time: f64,
+    //Steps are held until resumed or stepped
+    pub paused: bool,
+    //Rendered frames per second since the last sample
+    pub fps: f32,
+    //Wall time from submitting a step until the GPU finished it, in milliseconds. The last step's while paused
+    pub step_time: f32,
+    pub particles: u32,
+    //In kilograms per square metre
+    pub average_density: f32,
+    pub max_density: f32,
+    //In metres per second
+    pub max_speed: f32,
+    //Neighbour search buckets holding particles. The three neighbour statistics are zero for FLIP and APIC, which search none
+    pub occupied_cells: u32,
+    //Particles sharing their bucket with particles of another cell
+    pub hash_collisions: u32,
+    //Particles in the fullest bucket
+    pub max_cell_particles: u32
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Message {
+    //First message in both directions
+    Hello { version: u32 },
+    //Boxed, the parameters dwarf every other message
+    Parameters(Box<SimulationParameters>),
+    Command(Command),
+    //Reply to every parameter update and command that was applied
+    Ack,
+    Error(String),
+    Telemetry(Telemetry),
+    //Replaces the timeline the simulation plays back, an empty one stops it
+    Timeline(Timeline)
+}
+
+#[derive(Debug)]
+pub enum ProtocolError {
+    Io(std::io::Error),
+    Encoding(bincode::Error),
+    TooLarge(u32),
+    VersionMismatch(u32),
+    UnexpectedMessage
+}
+
+impl std::fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtocolError::Io(err) => write!(f, "{err}"),
+            ProtocolError::Encoding(err) => write!(f, "{err}"),
+            ProtocolError::TooLarge(size) => write!(f, "message of {size} bytes is too large"),
+            ProtocolError::VersionMismatch(version) => write!(f, "peer speaks protocol version {version}, expected {PROTOCOL_VERSION}"),
+            ProtocolError::UnexpectedMessage => write!(f, "unexpected message")
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl From<std::io::Error> for ProtocolError {
+    fn from(err: std::io::Error) -> Self {
+        ProtocolError::Io(err)
+    }
+}
+
+impl From<bincode::Error> for ProtocolError {
+    fn from(err: bincode::Error) -> Self {
+        ProtocolError::Encoding(err)
+    }
+}
+
+//Every message is prefixed with its length as a little endian u32
+pub fn write_message(writer: &mut impl Write, message: &Message) -> Result<(), ProtocolError> {
+    let payload = bincode::serialize(message)?;
+    writer.write_all(&(payload.len() as u32).to_le_bytes())?;
+    writer.write_all(&payload)?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn read_message(reader: &mut impl Read) -> Result<Message, ProtocolError> {
+    let mut length = [0u8; 4];
+    reader.read_exact(&mut length)?;
+    let length = u32::from_le_bytes(length);
+    if length > MAX_MESSAGE_SIZE {
+        return Err(ProtocolError::TooLarge(length));
+    }
+
+    let mut payload = vec![0u8; length as usize];
+    reader.read_exact(&mut payload)?;
+    Ok(bincode::deserialize(&payload)?)
+}
+
+//Exchange hellos, the side that accepted the connection answers after checking the version
+pub fn handshake<S: Read + Write>(stream: &mut S, accepting: bool) -> Result<(), ProtocolError> {
+    let hello = Message::Hello { version: PROTOCOL_VERSION };
+    if !accepting {
+        write_message(stream, &hello)?;
+    }
+
+    match read_message(stream)? {
+        Message::Hello { version } if version == PROTOCOL_VERSION => {},
+        Message::Hello { version } => {
+            if accepting {
+                let _ = write_message(stream, &Message::Error(ProtocolError::VersionMismatch(version).to_string()));
+            }
+            return Err(ProtocolError::VersionMismatch(version));
+        },
+        Message::Error(err) => return Err(ProtocolError::Io(std::io::Error::other(err))),
+        _ => return Err(ProtocolError::UnexpectedMessage)
+    }
+
+    if accepting {
+        write_message(stream, &hello)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::preset::{self, PresetError, PresetFormat};
+use crate::timeline::Timeline;
+use crate::SimulationParameters;
+
+//Unit of the lengths a scene gives in render space, the bounding box and the particle radius.
+//Everything else is always in metres, kilograms and seconds. Parameters of a loaded scene are in pixels
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    #[default]
+    Pixels,
+    Metres
+}
+
+//What an experiment starts from. Every preset file is a valid scene
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scene {
+    pub name: String,
+    pub units: Units,
+    pub parameters: SimulationParameters,
+    pub timeline: Timeline,
+    //Rhai script run before every step
+    pub script: Option<String>,
+    //Script in a separate file, relative to the scene file
+    pub script_file: Option<PathBuf>
+}
+
+impl Scene {
+    pub fn load(path: &Path) -> Result<Self, PresetError> {
+        let format = PresetFormat::from_path(path).ok_or(PresetError::UnknownFormat)?;
+        let mut value = preset::parse_value(&std::fs::read_to_string(path)?, format)?;
+        preset::check_viscosity(&value)?;
+        if value.get("units").and_then(Value::as_str) == Some("metres") {
+            metres_to_pixels(&mut value);
+        }
+        let mut scene: Scene = serde_json::from_value(value)?;
+
+        if let Some(script_file) = scene.script_file.take() {
+            let script_file = path.parent().unwrap_or(Path::new("")).join(script_file);
+            scene.script = Some(std::fs::read_to_string(script_file)?);
+        }
+        Ok(scene)
+    }
+}
+
+//Only lengths given in the file are converted, missing ones are filled in from the defaults in pixels
+fn metres_to_pixels(scene: &mut Value) {
+    let Some(parameters) = scene.get_mut("parameters").and_then(Value::as_object_mut) else { return; };
+    let scale = parameters.get("pixels_per_metre").and_then(Value::as_f64).unwrap_or(SimulationParameters::default().pixels_per_metre as f64);
+    let convert = |value: Option<&mut Value>| {
+        if let Some(value) = value {
+            if let Some(length) = value.as_f64() {
+                *value = (length * scale).into();
+            }
+        }
+    };
+
+    convert(parameters.get_mut("particle_radius"));
+    if let Some(bounding_box) = parameters.get_mut("bounding_box") {
+        //Depth isn't simulated, only x and y are lengths
+        for corner in ["position1", "position2"] {
+            for axis in 0..2 {
+                convert(bounding_box.get_mut(corner).and_then(|corner| corner.get_mut(axis)));
+            }
+        }
+    }
+}
@@ -1,8 +1,17 @@
 use cgmath::Vector3;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::kernel::Kernel;
+use crate::material::{Material, ViscosityModel};
+use crate::method::SimulationMethod;
+use crate::neighbour::{NeighbourSearch, NeighbourSort};
+use crate::solver::PressureSolver;
+
+//Missing fields are taken from the defaults, so older preset files keep loading
 #[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
 pub struct SimulationParameters {
     pub bounding_box: BoundingBoxUniform,
     pub gravity: [f32; 3],
@@ -27,7 +36,8 @@ pub struct SimulationParameters {
     pub near_pressure_multiplier: f32,
     pub grid_size: f32,
     //16
-    pub scene_scale_factor: f32,
+    //Physics runs in metres, kilograms and seconds, while positions are kept in the pixels they are drawn at
+    pub pixels_per_metre: f32,
     pub vorticity_kernel_radius: f32,
     pub vorticity_inensity: f32,
     pub cohesion_kernel_radius: f32,
@@ -36,7 +46,81 @@ pub struct SimulationParameters {
     pub surface_normal_kernel_radius: f32,
     pub time_step: f32,
     pub velocity_smoothing_scale: f32,
-    _padding: [f32; 1]
+    //24
+    #[serde(with = "crate::solver::as_name")]
+    #[schemars(with = "crate::solver::PressureSolver")]
+    pub pressure_solver: u32,
+    //Passes of the iterative solvers per step
+    pub solver_iterations: u32,
+    //Tait equation of state
+    pub speed_of_sound: f32,
+    pub tait_exponent: f32,
+    //28, position based fluids
+    pub pbf_relaxation: f32,
+    pub pbf_tensile_coef: f32,
+    //30, kernel of each force term
+    #[serde(with = "crate::kernel::as_name")]
+    #[schemars(with = "crate::kernel::Kernel")]
+    pub density_kernel: u32,
+    #[serde(with = "crate::kernel::as_name")]
+    #[schemars(with = "crate::kernel::Kernel")]
+    pub pressure_kernel: u32,
+    //32
+    #[serde(with = "crate::kernel::as_name")]
+    #[schemars(with = "crate::kernel::Kernel")]
+    pub viscosity_kernel: u32,
+    #[serde(with = "crate::kernel::as_name")]
+    #[schemars(with = "crate::kernel::Kernel")]
+    pub surface_normal_kernel: u32,
+    #[serde(with = "crate::kernel::as_name")]
+    #[schemars(with = "crate::kernel::Kernel")]
+    pub vorticity_kernel: u32,
+    #[serde(with = "crate::kernel::as_name")]
+    #[schemars(with = "crate::kernel::Kernel")]
+    pub smoothing_kernel: u32,
+    //36, non-Newtonian viscosity
+    #[serde(with = "crate::material::as_name")]
+    #[schemars(with = "crate::material::ViscosityModel")]
+    pub viscosity_model: u32,
+    pub flow_index: f32,
+    pub infinite_shear_viscosity: f32,
+    pub relaxation_time: f32,
+    //40
+    pub yield_stress: f32,
+    //Cap on the apparent viscosity, which grows without bounds at rest for shear thinning and Bingham fluids
+    pub max_viscosity: f32,
+    //Viscoelastic springs
+    pub spring_stiffness: f32,
+    pub spring_yield_ratio: f32,
+    //44
+    pub plasticity: f32,
+    #[serde(with = "crate::material::material_as_name")]
+    #[schemars(with = "crate::material::Material")]
+    pub material: u32,
+    //Drucker–Prager grains, the friction angle in degrees
+    pub friction_angle: f32,
+    pub granular_cohesion: f32,
+    //48
+    pub shear_modulus: f32,
+    #[serde(with = "crate::method::as_name")]
+    #[schemars(with = "crate::method::SimulationMethod")]
+    pub simulation_method: u32,
+    //FLIP and APIC grid
+    pub flip_ratio: f32,
+    pub flip_cell_size: f32,
+    //52
+    pub projection_iterations: u32,
+    #[serde(with = "crate::neighbour::as_name")]
+    #[schemars(with = "crate::neighbour::NeighbourSearch")]
+    pub neighbour_search: u32,
+    //Buckets of the hashed neighbour search, a power of two
+    pub hash_table_size: u32,
+    #[serde(with = "crate::neighbour::sort_as_name")]
+    #[schemars(with = "crate::neighbour::NeighbourSort")]
+    pub neighbour_sort: u32,
+    #[serde(skip)]
+    #[schemars(skip)]
+    _padding: u32
 }
 
 impl Default for SimulationParameters {
@@ -44,13 +128,13 @@ impl Default for SimulationParameters {
         let width = 1600.0f32;
         let height = 900.0f32;
         let diagonal = (width * width + height * height).sqrt();
-        let scene_scale_factor = 50.0 / diagonal;
+        let pixels_per_metre = diagonal / 50.0;
 
         let particle_mass = 1.0;
         let particle_radius = 1.5;
         let particles_amount = 16384;
         let collision_damping = 0.9;
-        let viscosity = 0.05;
+        let viscosity = 2.3;
         let cohesion_coef = 1.0;
         let curvature_cef = 1.0; 
         let adhesion_cef = 1.0;
@@ -64,6 +148,46 @@ impl Default for SimulationParameters {
         let time_step = 1.0 / 120.0;
         let velocity_smoothing_scale = 0.035;
 
+        //Linearized, the Tait equation matches the linear pressure for this speed of sound
+        let pressure_solver = PressureSolver::Linear.id();
+        let solver_iterations = 3;
+        let speed_of_sound = f32::sqrt(pressure_multiplier);
+        let tait_exponent = 7.0;
+        let pbf_relaxation = 1.0;
+        let pbf_tensile_coef = 0.001;
+
+        let density_kernel = Kernel::QuadraticSpiky.id();
+        let pressure_kernel = Kernel::QuadraticSpiky.id();
+        let viscosity_kernel = Kernel::Spiky.id();
+        let surface_normal_kernel = Kernel::Poly6.id();
+        let vorticity_kernel = Kernel::QuadraticSpiky.id();
+        let smoothing_kernel = Kernel::Poly6.id();
+
+        let viscosity_model = ViscosityModel::Newtonian.id();
+        let flow_index = 0.5;
+        let infinite_shear_viscosity = 0.0;
+        let relaxation_time = 1.0;
+        let yield_stress = 100.0;
+        let max_viscosity = 50.0;
+        let spring_stiffness = 30.0;
+        let spring_yield_ratio = 0.1;
+        let plasticity = 0.5;
+
+        let material = Material::Fluid.id();
+        let friction_angle = 30.0;
+        let granular_cohesion = 0.0;
+        let shear_modulus = 10000.0;
+
+        let simulation_method = SimulationMethod::Sph.id();
+        let flip_ratio = 0.95;
+        //Cells about two particles across at the rest density
+        let flip_cell_size = 0.35;
+        let projection_iterations = 60;
+
+        let neighbour_search = NeighbourSearch::Hashed.id();
+        let hash_table_size = 16384;
+        let neighbour_sort = NeighbourSort::Radix.id();
+
         let poly_kernel_radius = grid_size;
         let pressure_kernel_radius = grid_size;
         let near_pressure_kernel_radius = grid_size;
@@ -91,7 +215,7 @@ impl Default for SimulationParameters {
             near_pressure_multiplier,
             bounding_box,
             grid_size,
-            scene_scale_factor,
+            pixels_per_metre,
             gravity,
             vorticity_kernel_radius,
             vorticity_inensity,
@@ -100,17 +224,51 @@ impl Default for SimulationParameters {
             surface_normal_kernel_radius,
             time_step,
             velocity_smoothing_scale,
-            _padding: Default::default(),
+            pressure_solver,
+            solver_iterations,
+            speed_of_sound,
+            tait_exponent,
+            pbf_relaxation,
+            pbf_tensile_coef,
+            density_kernel,
+            pressure_kernel,
+            viscosity_kernel,
+            surface_normal_kernel,
+            vorticity_kernel,
+            smoothing_kernel,
+            viscosity_model,
+            flow_index,
+            infinite_shear_viscosity,
+            relaxation_time,
+            yield_stress,
+            max_viscosity,
+            spring_stiffness,
+            spring_yield_ratio,
+            plasticity,
+            material,
+            friction_angle,
+            granular_cohesion,
+            shear_modulus,
+            simulation_method,
+            flip_ratio,
+            flip_cell_size,
+            projection_iterations,
+            neighbour_search,
+            hash_table_size,
+            neighbour_sort,
+            _padding: Default::default()
         }
     }
 }
 
 #[repr(C)]
-#[derive(Debug, Copy, Clone, Default, bytemuck::Pod, bytemuck::Zeroable, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable, Serialize, Deserialize, JsonSchema)]
 pub struct BoundingBoxUniform{
     pub position1: [f32; 3],
+    #[serde(skip)]
     _padding: u32,
     pub position2: [f32; 3],
+    #[serde(skip)]
     _padding1: u32,
 }
 
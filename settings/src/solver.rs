@@ -0,0 +1,80 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::SimulationParameters;
+
+//How the fluid is kept from compressing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum PressureSolver {
+    //Pressure linear in the density error, with a near pressure against clumping
+    #[default]
+    Linear,
+    //Weakly compressible SPH with the Tait equation of state
+    Tait,
+    //Position based fluids, density constraints projected on the positions
+    Pbf,
+    //Divergence-free SPH, pressure solved for by correcting the velocities
+    Dfsph
+}
+
+impl PressureSolver {
+    pub const ALL: [PressureSolver; 4] = [PressureSolver::Linear, PressureSolver::Tait, PressureSolver::Pbf, PressureSolver::Dfsph];
+
+    //As serialized, in the order of the ids
+    pub const NAMES: [&str; 4] = ["Linear", "Tait", "Pbf", "Dfsph"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PressureSolver::Linear => "Linear",
+            PressureSolver::Tait => "WCSPH (Tait)",
+            PressureSolver::Pbf => "Position based (PBF)",
+            PressureSolver::Dfsph => "Divergence-free (DFSPH)"
+        }
+    }
+
+    //Index in the GPU uniform
+    pub fn id(self) -> u32 {
+        self as u32
+    }
+
+    pub fn from_id(id: u32) -> Option<Self> {
+        PressureSolver::ALL.get(id as usize).copied()
+    }
+
+    //Solvers running their own passes for a number of iterations, instead of adding a pressure force
+    pub fn is_iterative(self) -> bool {
+        matches!(self, PressureSolver::Pbf | PressureSolver::Dfsph)
+    }
+
+    //Descriptors of the parameters the solver reads
+    pub fn parameters(self) -> &'static [&'static str] {
+        match self {
+            PressureSolver::Linear => &["pressure_multiplier", "near_pressure_multiplier"],
+            PressureSolver::Tait => &["speed_of_sound", "tait_exponent", "near_pressure_multiplier"],
+            PressureSolver::Pbf => &["solver_iterations", "pbf_relaxation", "pbf_tensile_coef"],
+            PressureSolver::Dfsph => &["solver_iterations"]
+        }
+    }
+}
+
+impl SimulationParameters {
+    //Unknown ids, e.g. from a newer peer, fall back to the default solver
+    pub fn pressure_solver(&self) -> PressureSolver {
+        PressureSolver::from_id(self.pressure_solver).unwrap_or_default()
+    }
+}
+
+//The uniform keeps the solver as its id, files and messages use its name
+pub(crate) mod as_name {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::PressureSolver;
+
+    pub fn serialize<S: Serializer>(id: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+        PressureSolver::from_id(*id).unwrap_or_default().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+        Ok(PressureSolver::deserialize(deserializer)?.id())
+    }
+}
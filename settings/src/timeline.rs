@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::SimulationParameters;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut
+}
+
+impl Easing {
+    pub const ALL: [Easing; 4] = [Easing::Linear, Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Easing::Linear => "Linear",
+            Easing::EaseIn => "Ease in",
+            Easing::EaseOut => "Ease out",
+            Easing::EaseInOut => "Ease in-out"
+        }
+    }
+
+    //Maps the progress between two keyframes, both in 0..=1
+    pub fn apply(self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe {
+    //Simulated time in seconds
+    pub time: f32,
+    pub value: f32,
+    //How the value moves on to the next keyframe
+    #[serde(default)]
+    pub easing: Easing
+}
+
+//Keyframes of one numeric parameter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Track {
+    //Path of the field, with indices for arrays, e.g. `viscosity`, `gravity.1` or `bounding_box.position2.0`
+    pub field: String,
+    pub keyframes: Vec<Keyframe>
+}
+
+impl Track {
+    pub fn new(field: &str) -> Self {
+        Track {
+            field: field.to_string(),
+            keyframes: vec![]
+        }
+    }
+
+    //Keyframes have to be sorted by time
+    pub fn sort(&mut self) {
+        self.keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+    }
+
+    //Before the first and after the last keyframe their value is held
+    pub fn value(&self, time: f32) -> Option<f32> {
+        let first = self.keyframes.first()?;
+        let last = self.keyframes.last()?;
+        if time <= first.time {
+            return Some(first.value);
+        }
+        if time >= last.time {
+            return Some(last.value);
+        }
+
+        let next = self.keyframes.iter().position(|key| key.time > time)?;
+        let (a, b) = (self.keyframes[next - 1], self.keyframes[next]);
+        let t = a.easing.apply((time - a.time) / (b.time - a.time));
+        Some(a.value + (b.value - a.value) * t)
+    }
+}
+
+//Parameters animated over the simulated time, played back by the simulation every step
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Timeline {
+    pub tracks: Vec<Track>
+}
+
+impl Timeline {
+    pub fn is_empty(&self) -> bool {
+        self.tracks.iter().all(|track| track.keyframes.is_empty())
+    }
+
+    pub fn sort(&mut self) {
+        self.tracks.iter_mut().for_each(Track::sort);
+    }
+
+    //Returns whether anything changed
+    pub fn apply(&self, parameters: &mut SimulationParameters, time: f64) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+
+        let Ok(mut value) = serde_json::to_value(*parameters) else { return false; };
+        for track in &self.tracks {
+            if let (Some(field), Some(new)) = (field_mut(&mut value, &track.field), track.value(time as f32)) {
+                *field = Value::from(new);
+            }
+        }
+
+        match serde_json::from_value::<SimulationParameters>(value) {
+            //Buffers of a running simulation can't be resized
+            Ok(mut animated) if animated != *parameters => {
+                animated.particles_amount = parameters.particles_amount;
+                *parameters = animated;
+                true
+            },
+            _ => false
+        }
+    }
+}
+
+fn field_mut<'a>(value: &'a mut Value, path: &str) -> Option<&'a mut Value> {
+    path.split('.').try_fold(value, |value, key| match value {
+        Value::Object(map) => map.get_mut(key),
+        Value::Array(array) => array.get_mut(key.parse::<usize>().ok()?),
+        _ => None
+    })
+}
+
+//Current value of a field, if it's a number
+pub fn field_value(parameters: &SimulationParameters, path: &str) -> Option<f32> {
+    let mut value = serde_json::to_value(*parameters).ok()?;
+    field_mut(&mut value, path)?.as_f64().map(|v| v as f32)
+}
+
+//Paths of all fields a track can animate. The particle count is left out since it can't change while running
+pub fn numeric_fields() -> Vec<String> {
+    fn collect(value: &Value, path: String, fields: &mut Vec<String>) {
+        let join = |key: &str| if path.is_empty() { key.to_string() } else { format!("{path}.{key}") };
+        match value {
+            Value::Object(map) => map.iter().for_each(|(key, value)| collect(value, join(key), fields)),
+            Value::Array(array) => array.iter().enumerate().for_each(|(i, value)| collect(value, join(&i.to_string()), fields)),
+            Value::Number(_) => fields.push(path),
+            _ => {}
+        }
+    }
+
+    let mut fields = vec![];
+    if let Ok(value) = serde_json::to_value(SimulationParameters::default()) {
+        collect(&value, String::new(), &mut fields);
+    }
+    //Counts, fractional values would fail to apply
+    fields.retain(|field| !matches!(field.as_str(), "particles_amount" | "solver_iterations" | "projection_iterations" | "hash_table_size"));
+    fields
+}
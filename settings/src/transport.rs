@@ -0,0 +1,162 @@
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::PathBuf;
+
+pub const DEFAULT_HOST: &str = "127.0.0.1";
+pub const DEFAULT_PORT: u16 = 12345;
+pub const DEFAULT_ENDPOINT: &str = "127.0.0.1:12345";
+
+//Where the simulation listens, written as `host:port` or `unix:path`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Endpoint {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(PathBuf)
+}
+
+impl std::str::FromStr for Endpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("unix:") {
+            #[cfg(unix)]
+            return Ok(Endpoint::Unix(PathBuf::from(path)));
+            #[cfg(not(unix))]
+            return Err(format!("unix sockets aren't supported on this platform ({path})"));
+        }
+
+        match s.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => Ok(Endpoint::Tcp(s.to_string())),
+            _ => Err(format!("expected host:port or unix:path, got {s}"))
+        }
+    }
+}
+
+impl std::fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Endpoint::Tcp(address) => write!(f, "{address}"),
+            #[cfg(unix)]
+            Endpoint::Unix(path) => write!(f, "unix:{}", path.display())
+        }
+    }
+}
+
+impl Default for Endpoint {
+    fn default() -> Self {
+        Endpoint::Tcp(DEFAULT_ENDPOINT.to_string())
+    }
+}
+
+impl Endpoint {
+    pub fn tcp(host: &str, port: u16) -> Self {
+        Endpoint::Tcp(format!("{host}:{port}"))
+    }
+}
+
+//Ask the OS for a port nobody listens on. It's released again, so it can in theory be taken before it's used
+pub fn free_port(host: &str) -> std::io::Result<u16> {
+    Ok(TcpListener::bind((host, 0))?.local_addr()?.port())
+}
+
+pub enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream)
+}
+
+impl Stream {
+    pub fn connect(endpoint: &Endpoint) -> std::io::Result<Self> {
+        match endpoint {
+            Endpoint::Tcp(address) => Ok(Stream::Tcp(TcpStream::connect(address)?)),
+            #[cfg(unix)]
+            Endpoint::Unix(path) => Ok(Stream::Unix(UnixStream::connect(path)?))
+        }
+    }
+
+    pub fn try_clone(&self) -> std::io::Result<Self> {
+        match self {
+            Stream::Tcp(stream) => Ok(Stream::Tcp(stream.try_clone()?)),
+            #[cfg(unix)]
+            Stream::Unix(stream) => Ok(Stream::Unix(stream.try_clone()?))
+        }
+    }
+
+    pub fn shutdown(&self) -> std::io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.shutdown(Shutdown::Both),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.shutdown(Shutdown::Both)
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.read(buf)
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.write(buf)
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.flush()
+        }
+    }
+}
+
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener)
+}
+
+impl Listener {
+    pub fn bind(endpoint: &Endpoint) -> std::io::Result<Self> {
+        match endpoint {
+            Endpoint::Tcp(address) => Ok(Listener::Tcp(TcpListener::bind(address)?)),
+            #[cfg(unix)]
+            Endpoint::Unix(path) => {
+                //A socket file left behind by a simulation that didn't exit cleanly is replaced, a live one
+                //or anything that isn't a socket is left alone
+                if path.exists() {
+                    if UnixStream::connect(path).is_ok() {
+                        return Err(std::io::Error::new(std::io::ErrorKind::AddrInUse, format!("{} is in use", path.display())));
+                    }
+                    if !std::fs::metadata(path)?.file_type().is_socket() {
+                        return Err(std::io::Error::new(std::io::ErrorKind::AlreadyExists, format!("{} exists and isn't a socket", path.display())));
+                    }
+                    std::fs::remove_file(path)?;
+                }
+                Ok(Listener::Unix(UnixListener::bind(path)?))
+            }
+        }
+    }
+
+    pub fn accept(&self) -> std::io::Result<Stream> {
+        match self {
+            Listener::Tcp(listener) => Ok(Stream::Tcp(listener.accept()?.0)),
+            #[cfg(unix)]
+            Listener::Unix(listener) => Ok(Stream::Unix(listener.accept()?.0))
+        }
+    }
+}
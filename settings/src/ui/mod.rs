@@ -0,0 +1,321 @@
+use crate::descriptor::{Group, ParameterDescriptor, ParameterKind};
+use crate::preset::{self, Preset, PresetFormat, PresetStore};
+use crate::neighbour::NeighbourSearch;
+use crate::protocol::Command;
+use crate::timeline::Timeline;
+use crate::{BoundingBoxUniform, SimulationParameters};
+
+mod telemetry;
+mod timeline;
+
+pub use self::telemetry::TelemetryHistory;
+pub use self::timeline::TimelinePanel;
+
+//Controls shared by the settings UI and the in-window overlay of the simulation
+
+//Pause state comes from the simulation, which scripts and other clients can pause too
+pub fn command_bar(ui: &mut egui::Ui, paused: bool) -> Option<Command> {
+    let mut command = None;
+    ui.horizontal(|ui| {
+        if ui.button(if paused { "Resume" } else { "Pause" }).clicked() {
+            command = Some(if paused { Command::Resume } else { Command::Pause });
+        }
+        if ui.add_enabled(paused, egui::Button::new("Step")).clicked() {
+            command = Some(Command::Step);
+        }
+        if ui.button("Reset").clicked() {
+            command = Some(Command::Reset);
+        }
+        if ui.button("Snapshot").clicked() {
+            command = Some(Command::Snapshot);
+        }
+        if ui.button("Screenshot").clicked() {
+            command = Some(Command::Screenshot);
+        }
+    });
+    command
+}
+
+pub struct ParametersPanel {
+    start_bound: BoundingBoxUniform
+}
+
+impl ParametersPanel {
+    //Takes the box from the parameters the simulation was started with
+    pub fn new(initial: &SimulationParameters) -> Self {
+        ParametersPanel {
+            start_bound: initial.bounding_box
+        }
+    }
+
+    //Generated from the descriptor table, one section per group
+    pub fn ui(&mut self, ui: &mut egui::Ui, parameters: &mut SimulationParameters) {
+        validation(ui, parameters);
+
+        egui::Grid::new("Parameters")
+        .num_columns(2)
+        .spacing([50.0, 4.0])
+        .striped(true)
+        .show(ui, |ui| {
+            for group in Group::ALL {
+                ui.label(egui::RichText::new(group.name()).strong());
+                ui.end_row();
+                for descriptor in ParameterDescriptor::in_group(group) {
+                    if is_used(descriptor, parameters) {
+                        param(ui, descriptor, parameters);
+                    }
+                }
+                if group == Group::Domain {
+                    domain_size(ui, parameters);
+                }
+            }
+        });
+
+        //Buffers and the window are sized from the starting box, it can only shrink
+        let bounding_box = &mut parameters.bounding_box;
+        for axis in 0..2 {
+            bounding_box.position1[axis] = bounding_box.position1[axis].max(self.start_bound.position1[axis]);
+            bounding_box.position2[axis] = bounding_box.position2[axis].min(self.start_bound.position2[axis]);
+        }
+    }
+}
+
+pub struct PresetPanel {
+    store: PresetStore,
+    selected: usize,
+    name: String,
+    format: PresetFormat,
+    status: Option<String>,
+    //Parameters and timeline of the last session, only restored on request
+    session: Option<Preset>
+}
+
+impl Default for PresetPanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PresetPanel {
+    pub fn new() -> Self {
+        PresetPanel {
+            store: PresetStore::new(),
+            selected: 0,
+            name: String::new(),
+            format: PresetFormat::Toml,
+            status: None,
+            session: None
+        }
+    }
+
+    pub fn with_session(mut self, session: Option<Preset>) -> Self {
+        self.session = session;
+        self
+    }
+
+    fn load(&mut self, index: usize, parameters: &mut SimulationParameters, timeline: &mut Timeline) {
+        let Some(preset) = self.store.presets.get(index) else { return; };
+
+        apply(preset, parameters, timeline);
+        self.name = preset.name.clone();
+        self.status = Some(format!("Loaded {}", preset.name));
+    }
+
+    //Presets carry the timeline along with the parameters
+    pub fn ui(&mut self, ui: &mut egui::Ui, parameters: &mut SimulationParameters, timeline: &mut Timeline) {
+        ui.horizontal(|ui| {
+            let selected = self.store.presets.get(self.selected).map(|p| p.name.clone()).unwrap_or_default();
+            egui::ComboBox::from_id_source("Preset")
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                for (i, preset) in self.store.presets.iter().enumerate() {
+                    let label = if self.store.paths[i].is_none() { format!("{} (built-in)", preset.name) } else { preset.name.clone() };
+                    ui.selectable_value(&mut self.selected, i, label);
+                }
+            });
+
+            if ui.button("Load").clicked() {
+                self.load(self.selected, parameters, timeline);
+            }
+
+            let user_preset = self.store.paths.get(self.selected).is_some_and(|p| p.is_some());
+            if ui.add_enabled(user_preset, egui::Button::new("Delete")).clicked() {
+                self.status = Some(match self.store.delete(self.selected) {
+                    Ok(_) => "Deleted".to_string(),
+                    Err(err) => err
+                });
+                self.selected = 0;
+            }
+
+            if ui.button("Reload").on_hover_text("Look for new files in the presets directory").clicked() {
+                self.store.reload();
+                self.selected = self.selected.min(self.store.presets.len() - 1);
+            }
+
+            if let Some(session) = &self.session {
+                if ui.button("Restore session").on_hover_text("Parameters and timeline the settings were closed with").clicked() {
+                    apply(session, parameters, timeline);
+                    self.status = Some("Restored the last session".to_string());
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.name).hint_text("Name").desired_width(140.0));
+            egui::ComboBox::from_id_source("Preset format")
+            .width(60.0)
+            .selected_text(self.format.extension())
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.format, PresetFormat::Toml, "toml");
+                ui.selectable_value(&mut self.format, PresetFormat::Json, "json");
+            });
+
+            let name = self.name.trim().to_string();
+            if ui.add_enabled(!name.is_empty(), egui::Button::new("Save")).clicked() {
+                let preset = Preset::new(&name, *parameters).with_timeline(timeline.clone());
+                self.status = Some(match self.store.save(&preset, self.format) {
+                    Ok(_) => format!("Saved {name}"),
+                    Err(err) => format!("Failed to save {name}: {err}")
+                });
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let defaults = SimulationParameters::default();
+            let changed = preset::differences(parameters, &defaults);
+            if changed.is_empty() {
+                ui.label("All parameters are at their defaults");
+            } else {
+                ui.label(format!("{} parameters differ from the defaults", changed.len())).on_hover_text(changed.join("\n"));
+                if ui.button("Reset all").clicked() {
+                    let particles_amount = parameters.particles_amount;
+                    *parameters = defaults;
+                    parameters.particles_amount = particles_amount;
+                }
+            }
+        });
+
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+    }
+}
+
+fn apply(preset: &Preset, parameters: &mut SimulationParameters, timeline: &mut Timeline) {
+    //Buffers of a running simulation can't be resized
+    let particles_amount = parameters.particles_amount;
+    *parameters = preset.parameters;
+    parameters.particles_amount = particles_amount;
+    *timeline = preset.timeline.clone();
+}
+
+//Size of the bounding box in the units the physics uses
+fn domain_size(ui: &mut egui::Ui, parameters: &SimulationParameters) {
+    let bounding_box = &parameters.bounding_box;
+    let width = (bounding_box.position2[0] - bounding_box.position1[0]) / parameters.pixels_per_metre;
+    let height = (bounding_box.position2[1] - bounding_box.position1[1]) / parameters.pixels_per_metre;
+    ui.label("Domain size:");
+    ui.label(format!("{width:.3} m × {height:.3} m"));
+    ui.end_row();
+}
+
+//Only what the chosen search, method, solver and material model read. Fields sizing the buffers are left to the command line and scenes
+fn is_used(descriptor: &ParameterDescriptor, parameters: &SimulationParameters) -> bool {
+    if descriptor.restart() {
+        return false;
+    }
+    if let ParameterKind::Choice { .. } = descriptor.kind {
+        return true;
+    }
+    match descriptor.group {
+        Group::Simulation => descriptor.name != "hash_table_size" || parameters.neighbour_search() == NeighbourSearch::Hashed,
+        Group::Method => parameters.simulation_method().parameters().contains(&descriptor.name),
+        Group::Pressure => parameters.pressure_solver().parameters().contains(&descriptor.name),
+        Group::Material => {
+            parameters.material().parameters().contains(&descriptor.name) || parameters.viscosity_model().parameters().contains(&descriptor.name)
+        },
+        _ => true
+    }
+}
+
+//Problems of the current parameters, errors are refused by the simulation
+fn validation(ui: &mut egui::Ui, parameters: &SimulationParameters) {
+    let validation = parameters.validate();
+    for error in &validation.errors {
+        ui.colored_label(ui.visuals().error_fg_color, format!("⛔ {error}"));
+    }
+    for warning in &validation.warnings {
+        ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {warning}"));
+    }
+    if !validation.errors.is_empty() || !validation.warnings.is_empty() {
+        ui.separator();
+    }
+}
+
+//Label of a parameter with its unit, highlighted when it differs from the default
+fn param_label(ui: &mut egui::Ui, descriptor: &ParameterDescriptor, changed: bool, default: &[f32]) {
+    let label = if descriptor.unit.is_empty() {
+        format!("{}:", descriptor.label)
+    } else {
+        format!("{} ({}):", descriptor.label, descriptor.unit)
+    };
+    let default = match descriptor.kind {
+        ParameterKind::Choice { label, .. } => label(default[0] as u32).to_string(),
+        _ if descriptor.components.is_empty() => default[0].to_string(),
+        _ => format!("{default:?}")
+    };
+    let hover = format!("{}\nDefault: {default}", descriptor.tooltip);
+
+    if changed {
+        ui.label(egui::RichText::new(label).color(ui.visuals().warn_fg_color)).on_hover_text(hover);
+    } else {
+        ui.label(label).on_hover_text(hover);
+    }
+}
+
+fn reset_button(ui: &mut egui::Ui, changed: bool) -> bool {
+    changed && ui.small_button("↺").on_hover_text("Reset to default").clicked()
+}
+
+//Widget of the kind of the field, limited to the range of its descriptor
+fn param(ui: &mut egui::Ui, descriptor: &ParameterDescriptor, parameters: &mut SimulationParameters) {
+    let default = descriptor.default_value();
+    let changed = descriptor.get(parameters) != default;
+    param_label(ui, descriptor, changed, &default);
+    let (min, max) = (*descriptor.range.start(), *descriptor.range.end());
+    ui.horizontal(|ui| {
+        match descriptor.kind {
+            ParameterKind::Float { value } => {
+                for (i, value) in value(parameters).iter_mut().enumerate() {
+                    if let Some(component) = descriptor.components.get(i) {
+                        ui.label(format!("{component}:"));
+                    }
+                    ui.add(egui::DragValue::new(value).speed(descriptor.step).clamp_range(descriptor.range.clone()));
+                }
+            },
+            ParameterKind::Integer { value, .. } => {
+                ui.add(egui::DragValue::new(value(parameters)).speed(descriptor.step).clamp_range(min as u32..=max as u32));
+            },
+            ParameterKind::PowerOfTwo { value } => {
+                let value = value(parameters);
+                let mut exponent = (*value).max(1).ilog2();
+                ui.add(egui::Slider::new(&mut exponent, (min as u32).ilog2()..=(max as u32).ilog2()).custom_formatter(|exponent, _| (1u32 << exponent as u32).to_string()));
+                *value = 1 << exponent;
+            },
+            ParameterKind::Choice { names, label, value } => {
+                let value = value(parameters);
+                egui::ComboBox::from_id_source(descriptor.name)
+                .selected_text(label(*value))
+                .show_ui(ui, |ui| {
+                    for id in 0..names.len() as u32 {
+                        ui.selectable_value(value, id, label(id));
+                    }
+                });
+            }
+        }
+        if reset_button(ui, changed) {
+            let _ = descriptor.set(parameters, &default);
+        }
+    });
+    ui.end_row();
+}
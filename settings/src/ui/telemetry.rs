@@ -0,0 +1,99 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::protocol::Telemetry;
+
+use egui_plot::{Legend, Line, Plot, PlotPoints};
+
+//How far back the plots reach
+const HISTORY: Duration = Duration::from_secs(60);
+
+//Name of a plotted line and the value it shows
+type Series<'a> = (&'a str, fn(&Telemetry) -> f32);
+
+//Statistics received from the simulation, shown as a table and plots
+pub struct TelemetryHistory {
+    start: Instant,
+    //Seconds since start with the sample received at that time
+    samples: VecDeque<(f64, Telemetry)>
+}
+
+impl Default for TelemetryHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TelemetryHistory {
+    pub fn new() -> Self {
+        TelemetryHistory {
+            start: Instant::now(),
+            samples: VecDeque::new()
+        }
+    }
+
+    pub fn push(&mut self, telemetry: Telemetry) {
+        let now = self.start.elapsed().as_secs_f64();
+        self.samples.push_back((now, telemetry));
+        while self.samples.front().is_some_and(|(t, _)| now - t > HISTORY.as_secs_f64()) {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn latest(&self) -> Option<&Telemetry> {
+        self.samples.back().map(|(_, telemetry)| telemetry)
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    pub fn ui(&self, ui: &mut egui::Ui) {
+        let Some((_, latest)) = self.samples.back() else {
+            ui.label("No statistics received yet");
+            return;
+        };
+
+        egui::Grid::new("Telemetry")
+        .num_columns(2)
+        .spacing([50.0, 4.0])
+        .striped(true)
+        .show(ui, |ui| {
+            let mut row = |name: &str, value: String| {
+                ui.label(name);
+                ui.label(value);
+                ui.end_row();
+            };
+            row("Frame:", latest.frame.to_string());
+            row("FPS:", format!("{:.1}", latest.fps));
+            row("Step time:", format!("{:.2} ms", latest.step_time));
+            row("Particles:", latest.particles.to_string());
+            row("Average density (kg/m²):", format!("{:.3}", latest.average_density));
+            row("Max density (kg/m²):", format!("{:.3}", latest.max_density));
+            row("Max speed (m/s):", format!("{:.3}", latest.max_speed));
+            row("Occupied cells:", latest.occupied_cells.to_string());
+            row("Hash collisions:", latest.hash_collisions.to_string());
+            row("Fullest cell:", latest.max_cell_particles.to_string());
+        });
+
+        self.plot(ui, "Performance", &[("FPS", |t| t.fps), ("Step time (ms)", |t| t.step_time)]);
+        self.plot(ui, "Density (kg/m²)", &[("Average", |t| t.average_density), ("Max", |t| t.max_density)]);
+        self.plot(ui, "Speed (m/s)", &[("Max speed", |t| t.max_speed)]);
+        self.plot(ui, "Neighbour search", &[("Hash collisions", |t| t.hash_collisions as f32), ("Fullest cell", |t| t.max_cell_particles as f32)]);
+    }
+
+    fn plot(&self, ui: &mut egui::Ui, id: &str, series: &[Series]) {
+        Plot::new(id)
+        .height(100.0)
+        .legend(Legend::default())
+        .allow_drag(false)
+        .allow_zoom(false)
+        .allow_scroll(false)
+        .show(ui, |plot_ui| {
+            for &(name, value) in series {
+                let points: PlotPoints = self.samples.iter().map(|(t, telemetry)| [*t, value(telemetry) as f64]).collect();
+                plot_ui.line(Line::new(points).name(name));
+            }
+        });
+    }
+}
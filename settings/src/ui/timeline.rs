@@ -0,0 +1,148 @@
+use egui_plot::{Line, Plot, PlotPoints, VLine};
+
+use crate::timeline::{self, Easing, Keyframe, Timeline, Track};
+use crate::SimulationParameters;
+
+//Editing of keyframed parameters, played back by the simulation on its own clock
+pub struct TimelinePanel {
+    //Whether the simulation plays the timeline, otherwise an empty one is sent
+    pub playing: bool,
+    fields: Vec<String>,
+    new_field: String
+}
+
+impl Default for TimelinePanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimelinePanel {
+    pub fn new() -> Self {
+        let fields = timeline::numeric_fields();
+        TimelinePanel {
+            playing: false,
+            new_field: fields.first().cloned().unwrap_or_default(),
+            fields
+        }
+    }
+
+    //What the simulation should play back
+    pub fn playback(&self, timeline: &Timeline) -> Timeline {
+        if !self.playing {
+            return Timeline::default();
+        }
+        let mut timeline = timeline.clone();
+        timeline.sort();
+        timeline
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, timeline: &mut Timeline, parameters: &SimulationParameters, time: f64) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.playing, "Play in the simulation");
+            ui.label(format!("t = {time:.2} s"));
+        });
+
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("Timeline field")
+            .width(200.0)
+            .selected_text(&self.new_field)
+            .show_ui(ui, |ui| {
+                for field in &self.fields {
+                    ui.selectable_value(&mut self.new_field, field.clone(), field);
+                }
+            });
+            let exists = timeline.tracks.iter().any(|track| track.field == self.new_field);
+            if ui.add_enabled(!exists, egui::Button::new("Add track")).clicked() {
+                timeline.tracks.push(Track::new(&self.new_field));
+            }
+        });
+
+        let mut removed = None;
+        for (i, track) in timeline.tracks.iter_mut().enumerate() {
+            egui::CollapsingHeader::new(&track.field)
+            .id_source(("Track", i))
+            .default_open(true)
+            .show(ui, |ui| {
+                track_plot(ui, i, track, time);
+                keyframes(ui, i, track);
+
+                ui.horizontal(|ui| {
+                    if ui.button("Add keyframe").on_hover_text("At the current time with the current value").clicked() {
+                        let value = timeline::field_value(parameters, &track.field).unwrap_or_default();
+                        track.keyframes.push(Keyframe { time: time as f32, value, easing: Easing::default() });
+                        track.sort();
+                    }
+                    if ui.button("Remove track").clicked() {
+                        removed = Some(i);
+                    }
+                });
+            });
+        }
+        if let Some(i) = removed {
+            timeline.tracks.remove(i);
+        }
+    }
+}
+
+fn keyframes(ui: &mut egui::Ui, index: usize, track: &mut Track) {
+    let mut removed = None;
+    let mut moved = false;
+    egui::Grid::new(("Keyframes", index))
+    .num_columns(4)
+    .striped(true)
+    .show(ui, |ui| {
+        ui.label("Time (s)");
+        ui.label("Value");
+        ui.label("Easing");
+        ui.end_row();
+
+        for (i, key) in track.keyframes.iter_mut().enumerate() {
+            let response = ui.add(egui::DragValue::new(&mut key.time).speed(0.01).clamp_range(0.0..=f32::MAX));
+            //Sorting while dragging would move the keyframe away from the cursor
+            moved |= response.drag_stopped() || response.lost_focus();
+            ui.add(egui::DragValue::new(&mut key.value).speed(0.01));
+            egui::ComboBox::from_id_source(("Easing", index, i))
+            .selected_text(key.easing.name())
+            .show_ui(ui, |ui| {
+                for easing in Easing::ALL {
+                    ui.selectable_value(&mut key.easing, easing, easing.name());
+                }
+            });
+            if ui.small_button("🗑").clicked() {
+                removed = Some(i);
+            }
+            ui.end_row();
+        }
+    });
+
+    if let Some(i) = removed {
+        track.keyframes.remove(i);
+    }
+    if moved {
+        track.sort();
+    }
+}
+
+//Curve of the track with the current time marked
+fn track_plot(ui: &mut egui::Ui, index: usize, track: &Track, time: f64) {
+    let mut sorted = track.clone();
+    sorted.sort();
+    let end = sorted.keyframes.last().map_or(0.0, |key| key.time as f64).max(time) + 1.0;
+    let points: PlotPoints = (0..=200)
+        .filter_map(|i| {
+            let t = end * i as f64 / 200.0;
+            sorted.value(t as f32).map(|value| [t, value as f64])
+        })
+        .collect();
+
+    Plot::new(("Track plot", index))
+    .height(80.0)
+    .allow_drag(false)
+    .allow_zoom(false)
+    .allow_scroll(false)
+    .show(ui, |plot_ui| {
+        plot_ui.line(Line::new(points));
+        plot_ui.vline(VLine::new(time));
+    });
+}
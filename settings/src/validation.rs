@@ -0,0 +1,353 @@
+use serde_json::Value;
+
+use crate::descriptor::{Group, ParameterDescriptor, ParameterKind};
+use crate::kernel::{Kernel, KernelTerm};
+use crate::material::{Material, ViscosityModel};
+use crate::method::SimulationMethod;
+use crate::neighbour::{NeighbourSearch, NeighbourSort};
+use crate::solver::PressureSolver;
+use crate::SimulationParameters;
+
+//Smallest values the simulation runs with
+const MIN_TIME_STEP: f32 = 1e-5;
+const MIN_REST_DENSITY: f32 = 1e-3;
+const MIN_PARTICLE_MASS: f32 = 1e-3;
+const MIN_GRID_SIZE: f32 = 0.01;
+const MIN_PIXELS_PER_METRE: f32 = 1e-3;
+const MIN_BOX_SIZE: f32 = 1.0;
+const MIN_SPEED_OF_SOUND: f32 = 1e-3;
+const MIN_TAIT_EXPONENT: f32 = 1e-3;
+const MIN_FLOW_INDEX: f32 = 1e-3;
+//Grains at 90° would hold any shear, and close to it the yield stress grows too steep to be stable
+pub const MAX_FRICTION_ANGLE: f32 = 80.0;
+//Springs take 128 bytes per particle and a storage buffer binding can hold 128 MiB
+pub const MAX_PARTICLES: u32 = 1 << 20;
+//Smaller cells make grids too large to allocate
+const MIN_FLIP_CELL_SIZE: f32 = 0.05;
+//A pass over every face of the FLIP grid is one dispatch of 64 wide workgroups, at most 65535 of them.
+//The largest buffer, two u32 per face, stays far below the 128 MiB a storage buffer binding can hold
+const MAX_FLIP_FACES: u64 = 65535 * 64;
+//Bounds of the neighbour search tables, a pass over every bucket has to fit in one dispatch
+pub const MIN_HASH_TABLE_SIZE: u32 = 64;
+pub const MAX_HASH_TABLE_SIZE: u32 = 1 << 21;
+const MAX_DENSE_CELLS: u64 = 1 << 21;
+
+//Values the simulation can't run with
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    NotFinite(String),
+    NoParticles,
+    TooManyParticles(u32),
+    NonPositiveTimeStep(f32),
+    NonPositiveRestDensity(f32),
+    NonPositiveParticleMass(f32),
+    NonPositiveGridSize(f32),
+    NonPositivePixelsPerMetre(f32),
+    UnknownPressureSolver(u32),
+    UnknownKernel { term: &'static str, id: u32 },
+    UnknownViscosityModel(u32),
+    NonPositiveFlowIndex(f32),
+    UnknownMaterial(u32),
+    FrictionAngleOutOfRange(f32),
+    UnknownSimulationMethod(u32),
+    FlipCellTooSmall(f32),
+    FlipGridTooLarge(u64),
+    NoProjectionIterations,
+    UnknownNeighbourSearch(u32),
+    InvalidHashTableSize(u32),
+    DenseGridTooLarge(u64),
+    UnknownNeighbourSort(u32),
+    //Iterative solvers do nothing without iterations
+    NoSolverIterations,
+    NonPositiveSpeedOfSound(f32),
+    NonPositiveTaitExponent(f32),
+    //Bounding box that has no area, or its corners swapped
+    DegenerateBoundingBox { position1: [f32; 3], position2: [f32; 3] }
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::NotFinite(field) => write!(f, "{field} is not a finite number"),
+            ValidationError::NoParticles => write!(f, "there has to be at least one particle"),
+            ValidationError::TooManyParticles(amount) => write!(f, "at most {MAX_PARTICLES} particles fit in the buffers, got {amount}"),
+            ValidationError::NonPositiveTimeStep(value) => write!(f, "time step has to be positive, got {value}"),
+            ValidationError::NonPositiveRestDensity(value) => write!(f, "rest density has to be positive, got {value}"),
+            ValidationError::NonPositiveParticleMass(value) => write!(f, "particle mass has to be positive, got {value}"),
+            ValidationError::NonPositiveGridSize(value) => write!(f, "grid size has to be positive, got {value}"),
+            ValidationError::NonPositivePixelsPerMetre(value) => write!(f, "pixels per metre have to be positive, got {value}"),
+            ValidationError::UnknownPressureSolver(id) => write!(f, "unknown pressure solver {id}"),
+            ValidationError::UnknownKernel { term, id } => write!(f, "unknown kernel {id} for {}", term.to_lowercase()),
+            ValidationError::UnknownViscosityModel(id) => write!(f, "unknown viscosity model {id}"),
+            ValidationError::NonPositiveFlowIndex(value) => write!(f, "flow index has to be positive, got {value}"),
+            ValidationError::UnknownMaterial(id) => write!(f, "unknown material {id}"),
+            ValidationError::FrictionAngleOutOfRange(value) => write!(f, "friction angle has to be within 0..={MAX_FRICTION_ANGLE}°, got {value}"),
+            ValidationError::UnknownSimulationMethod(id) => write!(f, "unknown simulation method {id}"),
+            ValidationError::FlipCellTooSmall(value) => write!(f, "grid cells have to be at least {MIN_FLIP_CELL_SIZE} m, got {value}"),
+            ValidationError::FlipGridTooLarge(faces) => write!(f, "the FLIP grid would have {faces} faces, at most {MAX_FLIP_FACES} fit, use larger cells"),
+            ValidationError::NoProjectionIterations => write!(f, "the pressure projection needs at least one iteration"),
+            ValidationError::UnknownNeighbourSearch(id) => write!(f, "unknown neighbour search {id}"),
+            ValidationError::InvalidHashTableSize(size) => write!(f, "hash table size has to be a power of two within {MIN_HASH_TABLE_SIZE}..={MAX_HASH_TABLE_SIZE}, got {size}"),
+            ValidationError::DenseGridTooLarge(cells) => write!(f, "the dense grid would have {cells} cells, at most {MAX_DENSE_CELLS} fit"),
+            ValidationError::UnknownNeighbourSort(id) => write!(f, "unknown neighbour sort {id}"),
+            ValidationError::NoSolverIterations => write!(f, "the pressure solver needs at least one iteration"),
+            ValidationError::NonPositiveSpeedOfSound(value) => write!(f, "speed of sound has to be positive, got {value}"),
+            ValidationError::NonPositiveTaitExponent(value) => write!(f, "Tait exponent has to be positive, got {value}"),
+            ValidationError::DegenerateBoundingBox { position1, position2 } => {
+                write!(f, "bounding box from {position1:?} to {position2:?} has no area")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+//Values the simulation runs with, but likely not as intended
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationWarning {
+    //Neighbours are only searched in adjacent cells, so a bigger kernel misses some and shows the grid
+    KernelLargerThanGrid { kernel: &'static str, radius: f32, grid_size: f32 }
+}
+
+impl std::fmt::Display for ValidationWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationWarning::KernelLargerThanGrid { kernel, radius, grid_size } => {
+                write!(f, "{kernel} ({radius}) is larger than the grid size ({grid_size}), expect grid artifacts")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Validation {
+    pub errors: Vec<ValidationError>,
+    pub warnings: Vec<ValidationWarning>
+}
+
+impl Validation {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    //All errors in one line, for replies and logs
+    pub fn error_message(&self) -> String {
+        self.errors.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")
+    }
+}
+
+impl SimulationParameters {
+    pub fn validate(&self) -> Validation {
+        let mut validation = Validation::default();
+        let errors = &mut validation.errors;
+
+        errors.extend(non_finite_fields(self).into_iter().map(ValidationError::NotFinite));
+        if self.particles_amount == 0 {
+            errors.push(ValidationError::NoParticles);
+        }
+        if self.particles_amount > MAX_PARTICLES {
+            errors.push(ValidationError::TooManyParticles(self.particles_amount));
+        }
+        if self.time_step <= 0.0 {
+            errors.push(ValidationError::NonPositiveTimeStep(self.time_step));
+        }
+        if self.rest_density <= 0.0 {
+            errors.push(ValidationError::NonPositiveRestDensity(self.rest_density));
+        }
+        if self.particle_mass <= 0.0 {
+            errors.push(ValidationError::NonPositiveParticleMass(self.particle_mass));
+        }
+        if self.grid_size <= 0.0 {
+            errors.push(ValidationError::NonPositiveGridSize(self.grid_size));
+        }
+        if self.pixels_per_metre <= 0.0 {
+            errors.push(ValidationError::NonPositivePixelsPerMetre(self.pixels_per_metre));
+        }
+
+        match PressureSolver::from_id(self.pressure_solver) {
+            None => errors.push(ValidationError::UnknownPressureSolver(self.pressure_solver)),
+            Some(solver) if solver.is_iterative() && self.solver_iterations == 0 => errors.push(ValidationError::NoSolverIterations),
+            Some(PressureSolver::Tait) => {
+                if self.speed_of_sound <= 0.0 {
+                    errors.push(ValidationError::NonPositiveSpeedOfSound(self.speed_of_sound));
+                }
+                if self.tait_exponent <= 0.0 {
+                    errors.push(ValidationError::NonPositiveTaitExponent(self.tait_exponent));
+                }
+            },
+            Some(_) => {}
+        }
+
+        for term in KernelTerm::ALL {
+            let id = term.id(self);
+            if Kernel::from_id(id).is_none() {
+                errors.push(ValidationError::UnknownKernel { term: term.name(), id });
+            }
+        }
+
+        match ViscosityModel::from_id(self.viscosity_model) {
+            None => errors.push(ValidationError::UnknownViscosityModel(self.viscosity_model)),
+            Some(ViscosityModel::PowerLaw | ViscosityModel::Carreau) if self.flow_index <= 0.0 => {
+                errors.push(ValidationError::NonPositiveFlowIndex(self.flow_index));
+            },
+            Some(_) => {}
+        }
+
+        match Material::from_id(self.material) {
+            None => errors.push(ValidationError::UnknownMaterial(self.material)),
+            Some(Material::Granular) if !(0.0..=MAX_FRICTION_ANGLE).contains(&self.friction_angle) => {
+                errors.push(ValidationError::FrictionAngleOutOfRange(self.friction_angle));
+            },
+            Some(_) => {}
+        }
+
+        match SimulationMethod::from_id(self.simulation_method) {
+            None => errors.push(ValidationError::UnknownSimulationMethod(self.simulation_method)),
+            Some(method) if method.is_grid_based() => {
+                if self.flip_cell_size < MIN_FLIP_CELL_SIZE {
+                    errors.push(ValidationError::FlipCellTooSmall(self.flip_cell_size));
+                }
+                let faces = self.flip_grid_faces();
+                if faces > MAX_FLIP_FACES {
+                    errors.push(ValidationError::FlipGridTooLarge(faces));
+                }
+                if self.projection_iterations == 0 {
+                    errors.push(ValidationError::NoProjectionIterations);
+                }
+            },
+            Some(_) => {}
+        }
+
+        match NeighbourSearch::from_id(self.neighbour_search) {
+            None => errors.push(ValidationError::UnknownNeighbourSearch(self.neighbour_search)),
+            Some(NeighbourSearch::Hashed) => {
+                if !self.hash_table_size.is_power_of_two() || !(MIN_HASH_TABLE_SIZE..=MAX_HASH_TABLE_SIZE).contains(&self.hash_table_size) {
+                    errors.push(ValidationError::InvalidHashTableSize(self.hash_table_size));
+                }
+            },
+            Some(NeighbourSearch::Dense) => {
+                let [x, y] = self.dense_grid_size();
+                let cells = x as u64 * y as u64;
+                if cells > MAX_DENSE_CELLS {
+                    errors.push(ValidationError::DenseGridTooLarge(cells));
+                }
+            }
+        }
+
+        if NeighbourSort::from_id(self.neighbour_sort).is_none() {
+            errors.push(ValidationError::UnknownNeighbourSort(self.neighbour_sort));
+        }
+
+        //The simulation is 2D, depth doesn't matter
+        let (position1, position2) = (self.bounding_box.position1, self.bounding_box.position2);
+        if position2[0] <= position1[0] || position2[1] <= position1[1] {
+            errors.push(ValidationError::DegenerateBoundingBox { position1, position2 });
+        }
+
+        if self.grid_size > 0.0 {
+            //The radii, not the choices of kernel
+            let radii = ParameterDescriptor::in_group(Group::Kernels).filter(|kernel| matches!(kernel.kind, ParameterKind::Float { .. }));
+            for kernel in radii {
+                let radius = kernel.get(self)[0];
+                if radius > self.grid_size {
+                    validation.warnings.push(ValidationWarning::KernelLargerThanGrid { kernel: kernel.name, radius, grid_size: self.grid_size });
+                }
+            }
+        }
+
+        validation
+    }
+
+    //Closest parameters without errors. Values that aren't numbers fall back to their defaults
+    pub fn clamped(&self) -> Self {
+        let defaults = SimulationParameters::default();
+        let mut parameters = *self;
+        if let (Ok(mut value), Ok(default)) = (serde_json::to_value(parameters), serde_json::to_value(defaults)) {
+            replace_non_finite(&mut value, &default);
+            parameters = serde_json::from_value(value).unwrap_or(defaults);
+        }
+
+        parameters.particles_amount = parameters.particles_amount.clamp(1, MAX_PARTICLES);
+        parameters.time_step = parameters.time_step.max(MIN_TIME_STEP);
+        parameters.rest_density = parameters.rest_density.max(MIN_REST_DENSITY);
+        parameters.particle_mass = parameters.particle_mass.max(MIN_PARTICLE_MASS);
+        parameters.grid_size = parameters.grid_size.max(MIN_GRID_SIZE);
+        parameters.pixels_per_metre = parameters.pixels_per_metre.max(MIN_PIXELS_PER_METRE);
+        //Before the grids sized from it are checked
+        let bounding_box = &mut parameters.bounding_box;
+        for axis in 0..2 {
+            bounding_box.position2[axis] = bounding_box.position2[axis].max(bounding_box.position1[axis] + MIN_BOX_SIZE);
+        }
+        parameters.pressure_solver = parameters.pressure_solver().id();
+        parameters.solver_iterations = parameters.solver_iterations.max(1);
+        parameters.viscosity_model = parameters.viscosity_model().id();
+        parameters.flow_index = parameters.flow_index.max(MIN_FLOW_INDEX);
+        parameters.material = parameters.material().id();
+        parameters.friction_angle = parameters.friction_angle.clamp(0.0, MAX_FRICTION_ANGLE);
+        parameters.simulation_method = parameters.simulation_method().id();
+        parameters.flip_cell_size = parameters.flip_cell_size.max(MIN_FLIP_CELL_SIZE);
+        //Cells grow until the grid fits, first by about the right factor
+        let faces = parameters.flip_grid_faces();
+        if parameters.simulation_method().is_grid_based() && faces > MAX_FLIP_FACES {
+            parameters.flip_cell_size *= (faces as f32 / MAX_FLIP_FACES as f32).sqrt();
+            while parameters.flip_grid_faces() > MAX_FLIP_FACES {
+                parameters.flip_cell_size *= 1.01;
+            }
+        }
+        parameters.projection_iterations = parameters.projection_iterations.max(1);
+        parameters.neighbour_search = parameters.neighbour_search().id();
+        parameters.hash_table_size = parameters.hash_table_size.clamp(MIN_HASH_TABLE_SIZE, MAX_HASH_TABLE_SIZE).next_power_of_two();
+        if parameters.neighbour_search() == NeighbourSearch::Dense {
+            let [x, y] = parameters.dense_grid_size();
+            if x as u64 * y as u64 > MAX_DENSE_CELLS {
+                parameters.neighbour_search = NeighbourSearch::Hashed.id();
+            }
+        }
+        parameters.neighbour_sort = parameters.neighbour_sort().id();
+        for term in KernelTerm::ALL {
+            *term.id_mut(&mut parameters) = parameters.kernel(term).id();
+        }
+        parameters.speed_of_sound = parameters.speed_of_sound.max(MIN_SPEED_OF_SOUND);
+        parameters.tait_exponent = parameters.tait_exponent.max(MIN_TAIT_EXPONENT);
+
+        parameters
+    }
+}
+
+//NaN and infinities don't survive JSON, so they show up as nulls
+fn non_finite_fields(parameters: &SimulationParameters) -> Vec<String> {
+    fn collect(value: &Value, path: String, fields: &mut Vec<String>) {
+        let join = |key: &str| if path.is_empty() { key.to_string() } else { format!("{path}.{key}") };
+        match value {
+            Value::Object(map) => map.iter().for_each(|(key, value)| collect(value, join(key), fields)),
+            Value::Array(array) => array.iter().enumerate().for_each(|(i, value)| collect(value, join(&i.to_string()), fields)),
+            Value::Null => fields.push(path),
+            _ => {}
+        }
+    }
+
+    let mut fields = vec![];
+    if let Ok(value) = serde_json::to_value(parameters) {
+        collect(&value, String::new(), &mut fields);
+    }
+    fields
+}
+
+fn replace_non_finite(value: &mut Value, default: &Value) {
+    match (value, default) {
+        (Value::Object(map), Value::Object(defaults)) => {
+            for (key, value) in map.iter_mut() {
+                if let Some(default) = defaults.get(key) {
+                    replace_non_finite(value, default);
+                }
+            }
+        },
+        (Value::Array(array), Value::Array(defaults)) => {
+            for (value, default) in array.iter_mut().zip(defaults) {
+                replace_non_finite(value, default);
+            }
+        },
+        (value @ Value::Null, default) => *value = default.clone(),
+        _ => {}
+    }
+}
@@ -0,0 +1,54 @@
+use settings::descriptor::{ParameterDescriptor, ParameterKind, ParameterOverride, PARAMETERS};
+use settings::SimulationParameters;
+
+#[test]
+fn defaults_are_within_the_ranges() {
+    for descriptor in PARAMETERS {
+        assert!(descriptor.check(&descriptor.default_value()).is_ok(), "{} defaults out of range", descriptor.name);
+    }
+}
+
+#[test]
+fn choice_names_are_the_serialized_ones() {
+    for descriptor in PARAMETERS {
+        let ParameterKind::Choice { names, .. } = descriptor.kind else { continue; };
+        for (id, name) in names.iter().enumerate() {
+            let mut parameters = SimulationParameters::default();
+            descriptor.set(&mut parameters, &[id as f32]).unwrap();
+
+            let serialized = serde_json::to_value(parameters).unwrap();
+            assert_eq!(serialized[descriptor.name], *name, "{} {id}", descriptor.name);
+        }
+    }
+}
+
+#[test]
+fn overrides_take_names_and_numbers() {
+    let mut parameters = SimulationParameters::default();
+    for text in ["neighbour_sort=Counting", "density_kernel=wendlandc2", "pressure_solver=2", "solver_iterations=7", "hash_table_size=1024", "gravity=0,-3.5"] {
+        text.parse::<ParameterOverride>().unwrap().apply(&mut parameters).unwrap();
+    }
+
+    assert_eq!(parameters.neighbour_sort, 1);
+    assert_eq!(parameters.density_kernel, 2);
+    assert_eq!(parameters.pressure_solver, 2);
+    assert_eq!(parameters.solver_iterations, 7);
+    assert_eq!(parameters.hash_table_size, 1024);
+    assert_eq!(parameters.gravity[..2], [0.0, -3.5]);
+    //Choices are written back by their name, so the launcher can pass them on
+    assert_eq!("neighbour_sort=1".parse::<ParameterOverride>().unwrap().to_string(), "neighbour_sort=Counting");
+}
+
+#[test]
+fn invalid_overrides_are_refused() {
+    for text in ["neighbour_sort=Bubble", "neighbour_sort=2", "solver_iterations=2.5", "solver_iterations=0", "hash_table_size=1000", "hash_table_size=32", "gravity=1", "no_such_field=1"] {
+        assert!(text.parse::<ParameterOverride>().is_err(), "{text} was accepted");
+    }
+}
+
+#[test]
+fn only_the_particle_count_needs_a_restart() {
+    let restart: Vec<&str> = PARAMETERS.iter().filter(|descriptor| descriptor.restart()).map(|descriptor| descriptor.name).collect();
+    assert_eq!(restart, ["particles_amount"]);
+    assert!(ParameterDescriptor::find("particles_amount").is_some());
+}
@@ -0,0 +1,61 @@
+use settings::kernel::Kernel;
+
+const SAMPLES: usize = 20000;
+
+//Integral of the kernel over the disk or ball of radius h, with the midpoint rule over the radius
+fn integrate(kernel: Kernel, h: f32, dimensions: u32) -> f64 {
+    let dr = h as f64 / SAMPLES as f64;
+    (0..SAMPLES).map(|i| {
+        let r = (i as f64 + 0.5) * dr;
+        let shell = match dimensions {
+            2 => 2.0 * std::f64::consts::PI * r,
+            _ => 4.0 * std::f64::consts::PI * r * r
+        };
+        kernel.value(r as f32, h, dimensions) as f64 * shell * dr
+    }).sum()
+}
+
+#[test]
+fn kernels_integrate_to_one() {
+    for kernel in Kernel::ALL {
+        for dimensions in [2, 3] {
+            for h in [0.3, 0.6, 1.7] {
+                let integral = integrate(kernel, h, dimensions);
+                assert!((integral - 1.0).abs() < 1e-3, "{} in {dimensions}D with h = {h} integrates to {integral}", kernel.name());
+            }
+        }
+    }
+}
+
+#[test]
+fn slopes_match_finite_differences() {
+    for kernel in Kernel::ALL {
+        for dimensions in [2, 3] {
+            let h = 0.6;
+            let step = 1e-3 * h;
+            let steepest = (0..=100).map(|i| kernel.slope(i as f32 / 100.0 * h, h, dimensions).abs()).fold(0.0, f32::max);
+            for i in 1..100 {
+                let r = i as f32 / 100.0 * h;
+                let difference = -(kernel.value(r + step, h, dimensions) - kernel.value(r - step, h, dimensions)) / (2.0 * step);
+                let slope = kernel.slope(r, h, dimensions);
+                assert!(
+                    (difference - slope).abs() < 1e-2 * steepest,
+                    "{} in {dimensions}D at r = {r}: slope {slope}, finite difference {difference}", kernel.name()
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn kernels_vanish_at_the_support_radius() {
+    for kernel in Kernel::ALL {
+        for dimensions in [2, 3] {
+            let h = 0.6;
+            assert!(kernel.value(h, h, dimensions).abs() < 1e-6, "{} doesn't vanish at h", kernel.name());
+            assert!(kernel.slope(h, h, dimensions).abs() < 1e-6, "{} isn't flat at h", kernel.name());
+            assert_eq!(kernel.value(1.01 * h, h, dimensions), 0.0);
+            assert_eq!(kernel.slope(1.01 * h, h, dimensions), 0.0);
+        }
+    }
+}
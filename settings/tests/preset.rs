@@ -0,0 +1,40 @@
+use settings::descriptor::PARAMETERS;
+use settings::preset::{Preset, PresetError, PresetFormat};
+use settings::scene::Scene;
+
+#[test]
+fn builtin_presets_are_valid_and_within_the_ranges() {
+    for preset in Preset::builtin() {
+        let validation = preset.parameters.validate();
+        assert!(validation.is_valid(), "{}: {}", preset.name, validation.error_message());
+        for descriptor in PARAMETERS {
+            assert!(descriptor.check(&descriptor.get(&preset.parameters)).is_ok(), "{}: {} out of range", preset.name, descriptor.name);
+        }
+    }
+}
+
+#[test]
+fn presets_from_before_selectable_kernels_are_scaled() {
+    let old = "name = \"old\"\n[parameters]\nviscosity = 0.1\nviscosity_kernel_radius = 0.5\n";
+    let preset = Preset::from_text(old, PresetFormat::Toml).unwrap();
+    assert!((preset.parameters.viscosity - 0.1 * 10.0 / 0.125).abs() < 1e-4);
+
+    let current = "name = \"current\"\n[parameters]\nviscosity = 0.1\nviscosity_kernel = \"Spiky\"\n";
+    assert_eq!(Preset::from_text(current, PresetFormat::Toml).unwrap().parameters.viscosity, 0.1);
+}
+
+#[test]
+fn scenes_have_to_say_which_viscosity_scale_they_use() {
+    let dir = std::env::temp_dir().join(format!("water-simulation-{}-scenes", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (ambiguous, explicit) = (dir.join("ambiguous.toml"), dir.join("explicit.toml"));
+    std::fs::write(&ambiguous, "[parameters]\nviscosity = 0.1\n").unwrap();
+    std::fs::write(&explicit, "[parameters]\nviscosity = 0.1\nviscosity_kernel = \"Spiky\"\n").unwrap();
+
+    let refused = Scene::load(&ambiguous);
+    let loaded = Scene::load(&explicit);
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert!(matches!(refused, Err(PresetError::AmbiguousViscosity)));
+    assert_eq!(loaded.unwrap().parameters.viscosity, 0.1);
+}
@@ -0,0 +1,63 @@
+use std::io::Cursor;
+use std::net::{TcpListener, TcpStream};
+
+use settings::protocol::{handshake, read_message, write_message, Command, Message, ProtocolError, Telemetry, PROTOCOL_VERSION};
+use settings::SimulationParameters;
+
+//Connected loopback streams, accepting side first, so the handshakes run on every platform
+fn stream_pair() -> (TcpStream, TcpStream) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let connecting = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (accepting, _) = listener.accept().unwrap();
+    (accepting, connecting)
+}
+
+#[test]
+fn messages_survive_framing() {
+    let telemetry = Telemetry { frame: 42, time: 1.5, paused: true, particles: 16384, ..Default::default() };
+    let parameters = SimulationParameters::default();
+
+    let mut stream = Vec::new();
+    write_message(&mut stream, &Message::Command(Command::Step)).unwrap();
+    write_message(&mut stream, &Message::Telemetry(telemetry)).unwrap();
+    write_message(&mut stream, &Message::Parameters(Box::new(parameters))).unwrap();
+
+    let mut reader = Cursor::new(stream);
+    assert!(matches!(read_message(&mut reader).unwrap(), Message::Command(Command::Step)));
+    assert!(matches!(read_message(&mut reader).unwrap(), Message::Telemetry(read) if read == telemetry));
+    assert!(matches!(read_message(&mut reader).unwrap(), Message::Parameters(read) if *read == parameters));
+    //Nothing but whole messages was written
+    assert!(matches!(read_message(&mut reader), Err(ProtocolError::Io(_))));
+}
+
+#[test]
+fn frames_over_a_mebibyte_are_refused() {
+    let mut stream = ((1u32 << 20) + 1).to_le_bytes().to_vec();
+    stream.resize(stream.len() + 16, 0);
+
+    assert!(matches!(read_message(&mut Cursor::new(stream)), Err(ProtocolError::TooLarge(size)) if size == (1 << 20) + 1));
+}
+
+#[test]
+fn handshake_succeeds_with_the_same_version() {
+    let (mut accepting, mut connecting) = stream_pair();
+
+    let peer = std::thread::spawn(move || handshake(&mut connecting, false));
+    handshake(&mut accepting, true).unwrap();
+    peer.join().unwrap().unwrap();
+}
+
+#[test]
+fn handshake_refuses_another_version() {
+    let (mut accepting, mut connecting) = stream_pair();
+    let version = PROTOCOL_VERSION + 1;
+
+    let peer = std::thread::spawn(move || {
+        write_message(&mut connecting, &Message::Hello { version }).unwrap();
+        read_message(&mut connecting).unwrap()
+    });
+    assert!(matches!(handshake(&mut accepting, true), Err(ProtocolError::VersionMismatch(v)) if v == version));
+
+    //The peer is told why before the connection is dropped
+    assert!(matches!(peer.join().unwrap(), Message::Error(_)));
+}
@@ -0,0 +1,81 @@
+use settings::timeline::{Easing, Keyframe, Timeline, Track};
+use settings::SimulationParameters;
+
+fn track(field: &str, keys: &[(f32, f32, Easing)]) -> Track {
+    Track {
+        field: field.to_string(),
+        keyframes: keys.iter().map(|&(time, value, easing)| Keyframe { time, value, easing }).collect()
+    }
+}
+
+fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-5
+}
+
+#[test]
+fn easings_start_at_zero_and_end_at_one() {
+    for easing in Easing::ALL {
+        assert!(close(easing.apply(0.0), 0.0), "{} starts at {}", easing.name(), easing.apply(0.0));
+        assert!(close(easing.apply(1.0), 1.0), "{} ends at {}", easing.name(), easing.apply(1.0));
+    }
+    assert!(close(Easing::Linear.apply(0.25), 0.25));
+    assert!(close(Easing::EaseIn.apply(0.5), 0.25));
+    assert!(close(Easing::EaseOut.apply(0.5), 0.75));
+    assert!(close(Easing::EaseInOut.apply(0.5), 0.5));
+}
+
+#[test]
+fn values_are_held_outside_the_keyframes() {
+    let track = track("viscosity", &[(1.0, 2.0, Easing::Linear), (3.0, 6.0, Easing::Linear)]);
+
+    assert_eq!(track.value(0.0), Some(2.0));
+    assert_eq!(track.value(1.0), Some(2.0));
+    assert_eq!(track.value(3.0), Some(6.0));
+    assert_eq!(track.value(10.0), Some(6.0));
+    assert_eq!(Track::new("viscosity").value(1.0), None);
+}
+
+#[test]
+fn values_are_interpolated_between_keyframes() {
+    let track = track("viscosity", &[
+        (0.0, 0.0, Easing::Linear),
+        (2.0, 4.0, Easing::EaseIn),
+        (4.0, 8.0, Easing::Linear)
+    ]);
+
+    assert!(close(track.value(1.0).unwrap(), 2.0));
+    //The easing of a keyframe shapes the way to the next one
+    assert!(close(track.value(3.0).unwrap(), 4.0 + 4.0 * 0.25));
+    assert!(close(track.value(3.5).unwrap(), 4.0 + 4.0 * 0.5625));
+}
+
+#[test]
+fn nested_fields_are_animated() {
+    let timeline = Timeline {
+        tracks: vec![
+            track("gravity.1", &[(0.0, -5.0, Easing::Linear), (2.0, -15.0, Easing::Linear)]),
+            track("bounding_box.position2.0", &[(0.0, 300.0, Easing::Linear)]),
+            track("no.such.field", &[(0.0, 1.0, Easing::Linear)])
+        ]
+    };
+    let mut parameters = SimulationParameters::default();
+    let before = parameters;
+
+    assert!(timeline.apply(&mut parameters, 1.0));
+    assert_eq!(parameters.gravity, [before.gravity[0], -10.0, before.gravity[2]]);
+    assert_eq!(parameters.bounding_box.position2[0], 300.0);
+    assert_eq!(parameters.bounding_box.position2[1], before.bounding_box.position2[1]);
+
+    //Nothing changes the second time
+    assert!(!timeline.apply(&mut parameters, 1.0));
+}
+
+#[test]
+fn the_particle_count_is_never_animated() {
+    let timeline = Timeline { tracks: vec![track("particles_amount", &[(0.0, 10.0, Easing::Linear)])] };
+    let mut parameters = SimulationParameters::default();
+    let before = parameters.particles_amount;
+
+    timeline.apply(&mut parameters, 0.0);
+    assert_eq!(parameters.particles_amount, before);
+}
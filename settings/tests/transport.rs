@@ -0,0 +1,48 @@
+#![cfg(unix)]
+
+use std::io::ErrorKind;
+use std::os::unix::net::UnixListener;
+use std::path::PathBuf;
+
+use settings::transport::{Endpoint, Listener};
+
+fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("water-simulation-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+#[test]
+fn stale_socket_is_replaced() {
+    let path = temp_path("stale.sock");
+    drop(UnixListener::bind(&path).unwrap());
+
+    let listener = Listener::bind(&Endpoint::Unix(path.clone()));
+    let _ = std::fs::remove_file(&path);
+
+    assert!(listener.is_ok());
+}
+
+#[test]
+fn live_socket_is_refused() {
+    let path = temp_path("live.sock");
+    let _live = UnixListener::bind(&path).unwrap();
+
+    let listener = Listener::bind(&Endpoint::Unix(path.clone()));
+    let _ = std::fs::remove_file(&path);
+
+    assert!(matches!(listener, Err(err) if err.kind() == ErrorKind::AddrInUse));
+}
+
+#[test]
+fn other_files_are_left_alone() {
+    let path = temp_path("file.sock");
+    std::fs::write(&path, "not a socket").unwrap();
+
+    let listener = Listener::bind(&Endpoint::Unix(path.clone()));
+    let contents = std::fs::read_to_string(&path);
+    let _ = std::fs::remove_file(&path);
+
+    assert!(matches!(listener, Err(err) if err.kind() == ErrorKind::AlreadyExists));
+    assert_eq!(contents.unwrap(), "not a socket");
+}
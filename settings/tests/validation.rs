@@ -0,0 +1,116 @@
+use settings::kernel::KernelTerm;
+use settings::material::{Material, ViscosityModel};
+use settings::method::SimulationMethod;
+use settings::neighbour::NeighbourSearch;
+use settings::solver::PressureSolver;
+use settings::validation::{ValidationError, ValidationWarning};
+use settings::SimulationParameters;
+
+type Case = (&'static str, fn(&mut SimulationParameters), fn(&ValidationError) -> bool);
+
+//One broken change per error, with how the error has to look
+const CASES: &[Case] = &[
+    ("NaN", |p| p.viscosity = f32::NAN, |e| matches!(e, ValidationError::NotFinite(field) if field == "viscosity")),
+    ("infinity", |p| p.gravity[1] = f32::NEG_INFINITY, |e| matches!(e, ValidationError::NotFinite(field) if field == "gravity.1")),
+    ("no particles", |p| p.particles_amount = 0, |e| matches!(e, ValidationError::NoParticles)),
+    ("too many particles", |p| p.particles_amount = u32::MAX, |e| matches!(e, ValidationError::TooManyParticles(u32::MAX))),
+    ("time step", |p| p.time_step = 0.0, |e| matches!(e, ValidationError::NonPositiveTimeStep(_))),
+    ("rest density", |p| p.rest_density = -1.0, |e| matches!(e, ValidationError::NonPositiveRestDensity(_))),
+    ("particle mass", |p| p.particle_mass = 0.0, |e| matches!(e, ValidationError::NonPositiveParticleMass(_))),
+    ("grid size", |p| p.grid_size = 0.0, |e| matches!(e, ValidationError::NonPositiveGridSize(_))),
+    ("pixels per metre", |p| p.pixels_per_metre = 0.0, |e| matches!(e, ValidationError::NonPositivePixelsPerMetre(_))),
+    ("pressure solver", |p| p.pressure_solver = 99, |e| matches!(e, ValidationError::UnknownPressureSolver(99))),
+    ("solver iterations", |p| {
+        p.pressure_solver = PressureSolver::Pbf.id();
+        p.solver_iterations = 0;
+    }, |e| matches!(e, ValidationError::NoSolverIterations)),
+    ("speed of sound", |p| {
+        p.pressure_solver = PressureSolver::Tait.id();
+        p.speed_of_sound = 0.0;
+    }, |e| matches!(e, ValidationError::NonPositiveSpeedOfSound(_))),
+    ("Tait exponent", |p| {
+        p.pressure_solver = PressureSolver::Tait.id();
+        p.tait_exponent = -1.0;
+    }, |e| matches!(e, ValidationError::NonPositiveTaitExponent(_))),
+    ("kernel", |p| *KernelTerm::Viscosity.id_mut(p) = 99, |e| matches!(e, ValidationError::UnknownKernel { id: 99, .. })),
+    ("viscosity model", |p| p.viscosity_model = 99, |e| matches!(e, ValidationError::UnknownViscosityModel(99))),
+    ("flow index", |p| {
+        p.viscosity_model = ViscosityModel::PowerLaw.id();
+        p.flow_index = 0.0;
+    }, |e| matches!(e, ValidationError::NonPositiveFlowIndex(_))),
+    ("material", |p| p.material = 99, |e| matches!(e, ValidationError::UnknownMaterial(99))),
+    ("friction angle", |p| {
+        p.material = Material::Granular.id();
+        p.friction_angle = 85.0;
+    }, |e| matches!(e, ValidationError::FrictionAngleOutOfRange(_))),
+    ("simulation method", |p| p.simulation_method = 99, |e| matches!(e, ValidationError::UnknownSimulationMethod(99))),
+    ("FLIP cell size", |p| {
+        p.simulation_method = SimulationMethod::Flip.id();
+        p.flip_cell_size = 0.001;
+    }, |e| matches!(e, ValidationError::FlipCellTooSmall(_))),
+    ("FLIP grid", |p| {
+        p.simulation_method = SimulationMethod::Flip.id();
+        p.flip_cell_size = 0.05;
+        p.pixels_per_metre = 1.0;
+    }, |e| matches!(e, ValidationError::FlipGridTooLarge(_))),
+    ("projection iterations", |p| {
+        p.simulation_method = SimulationMethod::Apic.id();
+        p.projection_iterations = 0;
+    }, |e| matches!(e, ValidationError::NoProjectionIterations)),
+    ("neighbour search", |p| p.neighbour_search = 99, |e| matches!(e, ValidationError::UnknownNeighbourSearch(99))),
+    ("hash table size", |p| p.hash_table_size = 1000, |e| matches!(e, ValidationError::InvalidHashTableSize(1000))),
+    ("dense grid", |p| {
+        p.neighbour_search = NeighbourSearch::Dense.id();
+        p.grid_size = 0.01;
+        p.pixels_per_metre = 1.0;
+    }, |e| matches!(e, ValidationError::DenseGridTooLarge(_))),
+    ("neighbour sort", |p| p.neighbour_sort = 99, |e| matches!(e, ValidationError::UnknownNeighbourSort(99))),
+    ("bounding box", |p| p.bounding_box.position2[0] = p.bounding_box.position1[0], |e| matches!(e, ValidationError::DegenerateBoundingBox { .. }))
+];
+
+#[test]
+fn defaults_are_valid() {
+    let validation = SimulationParameters::default().validate();
+    assert!(validation.is_valid(), "{}", validation.error_message());
+    assert!(validation.warnings.is_empty(), "{:?}", validation.warnings);
+}
+
+#[test]
+fn every_error_is_reported() {
+    for (name, break_parameters, expected) in CASES {
+        let mut parameters = SimulationParameters::default();
+        break_parameters(&mut parameters);
+
+        let validation = parameters.validate();
+        assert!(validation.errors.iter().any(expected), "{name}: got {:?}", validation.errors);
+    }
+}
+
+#[test]
+fn clamped_parameters_are_valid() {
+    for (name, break_parameters, _) in CASES {
+        let mut parameters = SimulationParameters::default();
+        break_parameters(&mut parameters);
+
+        let validation = parameters.clamped().validate();
+        assert!(validation.is_valid(), "{name}: {}", validation.error_message());
+    }
+
+    //Everything broken at once
+    let mut parameters = SimulationParameters::default();
+    for (_, break_parameters, _) in CASES {
+        break_parameters(&mut parameters);
+    }
+    let validation = parameters.clamped().validate();
+    assert!(validation.is_valid(), "{}", validation.error_message());
+}
+
+#[test]
+fn kernels_larger_than_the_grid_are_warned_about() {
+    let mut parameters = SimulationParameters::default();
+    parameters.viscosity_kernel_radius = parameters.grid_size * 2.0;
+
+    let validation = parameters.validate();
+    assert!(validation.is_valid());
+    assert!(validation.warnings.iter().any(|warning| matches!(warning, ValidationWarning::KernelLargerThanGrid { radius, .. } if *radius == parameters.viscosity_kernel_radius)));
+}
@@ -0,0 +1,120 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+
+use eframe::egui;
+use settings::protocol::{self, Message};
+use settings::transport::{Endpoint, Stream};
+
+const RETRY_INTERVAL: Duration = Duration::from_millis(500);
+
+pub enum Event {
+    Connected,
+    Disconnected,
+    //Boxed, the parameters make messages large
+    Message(Box<Message>)
+}
+
+//Connection to the simulation, kept alive in a background thread that reconnects whenever it's lost
+pub struct Connection {
+    outgoing: Sender<Message>,
+    events: Receiver<Event>
+}
+
+impl Connection {
+    pub fn new(address: Endpoint, ctx: egui::Context) -> Self {
+        let (outgoing, outgoing_receiver) = channel();
+        let (event_sender, events) = channel();
+
+        std::thread::spawn(move || run(&address, &outgoing_receiver, &event_sender, &ctx));
+
+        Connection {
+            outgoing,
+            events
+        }
+    }
+
+    //Messages sent while disconnected are dropped
+    pub fn send(&self, message: Message) {
+        let _ = self.outgoing.send(message);
+    }
+
+    pub fn events(&self) -> impl Iterator<Item = Event> + '_ {
+        self.events.try_iter()
+    }
+}
+
+fn run(address: &Endpoint, outgoing: &Receiver<Message>, events: &Sender<Event>, ctx: &egui::Context) {
+    let notify = |event: Event| {
+        let _ = events.send(event);
+        ctx.request_repaint();
+    };
+
+    loop {
+        let mut stream = match Stream::connect(address) {
+            Ok(stream) => stream,
+            Err(_) => {
+                std::thread::sleep(RETRY_INTERVAL);
+                continue;
+            }
+        };
+
+        if let Err(err) = protocol::handshake(&mut stream, false) {
+            log::error!("Handshake with the simulation failed: {err}");
+            std::thread::sleep(RETRY_INTERVAL);
+            continue;
+        }
+
+        while outgoing.try_recv().is_ok() {}
+        notify(Event::Connected);
+
+        let closed = Arc::new(AtomicBool::new(false));
+        let reader = {
+            let mut stream = stream.try_clone().unwrap();
+            let closed = closed.clone();
+            let events = events.clone();
+            let ctx = ctx.clone();
+            std::thread::spawn(move || {
+                loop {
+                    match protocol::read_message(&mut stream) {
+                        Ok(message) => {
+                            let _ = events.send(Event::Message(Box::new(message)));
+                            ctx.request_repaint();
+                        },
+                        Err(err) => {
+                            log::error!("Lost connection to the simulation: {err}");
+                            break;
+                        }
+                    }
+                }
+                closed.store(true, Ordering::Release);
+            })
+        };
+
+        let stop = loop {
+            match outgoing.recv_timeout(Duration::from_millis(100)) {
+                Ok(message) => {
+                    if let Err(err) = protocol::write_message(&mut stream, &message) {
+                        log::error!("Failed to send to the simulation: {err}");
+                        break false;
+                    }
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    if closed.load(Ordering::Acquire) {
+                        break false;
+                    }
+                },
+                //The UI is gone
+                Err(RecvTimeoutError::Disconnected) => break true
+            }
+        };
+
+        let _ = stream.shutdown();
+        let _ = reader.join();
+        if stop {
+            return;
+        }
+        notify(Event::Disconnected);
+    }
+}
@@ -1,230 +1,214 @@
-use std::{io::Write, net::TcpStream};
+use std::collections::VecDeque;
 
+use clap::Parser;
 use eframe::egui;
 use log::info;
+use ::settings::preset;
+use ::settings::protocol::{self, Message};
+use ::settings::timeline::Timeline;
+use ::settings::transport::Endpoint;
+use ::settings::ui::{command_bar, ParametersPanel, PresetPanel, TelemetryHistory, TimelinePanel};
 use settings::settings;
 
+use crate::connection::{Connection, Event};
+
+mod connection;
+
+#[derive(Parser, Debug)]
+#[command(about = "Settings of the water simulation")]
+struct Args {
+    /// Where the simulation listens, as host:port or unix:path
+    #[arg(long, value_name = "ENDPOINT", env = "WSIM_ADDRESS", default_value_t = Endpoint::default())]
+    address: Endpoint,
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
+    let args = Args::parse();
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_resizable(false)
-            .with_inner_size([360.0, 600.0]),
+            .with_inner_size([360.0, 680.0]),
         ..Default::default()
     };
 
-    eframe::run_native("Settings", native_options, Box::new(|cc| Box::new(SettingsUI::new(cc))))?;
+    eframe::run_native("Settings", native_options, Box::new(move |cc| Box::new(SettingsUI::new(cc, args.address))))?;
 
     Ok(())
 }
 
 struct SettingsUI {
     settings: settings::SimulationParameters,
-    start_bound: settings::BoundingBoxUniform,
-    stream: TcpStream,
-    last_instant: std::time::Instant
+    address: Endpoint,
+    connection: Connection,
+    connected: bool,
+    //Parameters the simulation is known to run with
+    sent: Option<settings::SimulationParameters>,
+    //Sent messages still waiting for their reply, true for parameters. Replies come in order
+    awaiting: VecDeque<bool>,
+    timeline: Timeline,
+    //Timeline the simulation is known to play
+    timeline_sent: Option<Timeline>,
+    last_error: Option<String>,
+    telemetry: TelemetryHistory,
+    parameters: ParametersPanel,
+    presets: PresetPanel,
+    timeline_panel: TimelinePanel
 }
 
 impl SettingsUI {
-    fn new(cc: &eframe::CreationContext<'_>) -> Self {
+    fn new(cc: &eframe::CreationContext<'_>, address: Endpoint) -> Self {
         cc.egui_ctx.set_visuals(egui::Visuals::dark());
-        let stream = TcpStream::connect("127.0.0.1:12345").unwrap();
-        let settings: settings::SimulationParameters =  Default::default();
+        let connection = Connection::new(address.clone(), cc.egui_ctx.clone());
 
         SettingsUI { 
-            settings,
-            stream,
-            last_instant: std::time::Instant::now(),
-            start_bound: settings.bounding_box
+            settings: settings::SimulationParameters::default(),
+            address,
+            connection,
+            connected: false,
+            sent: None,
+            awaiting: VecDeque::new(),
+            timeline: Timeline::default(),
+            timeline_sent: None,
+            last_error: None,
+            telemetry: TelemetryHistory::new(),
+            parameters: ParametersPanel::new(&settings::SimulationParameters::default()),
+            //The last session is offered in the presets instead of overriding the simulation's parameters
+            presets: PresetPanel::new().with_session(preset::load_session()),
+            timeline_panel: TimelinePanel::new()
         }
     }
-}
 
-impl SettingsUI {
-    fn ui(&mut self, ui: &mut egui::Ui) {
-        egui::Grid::new("General parameters")
-        .num_columns(2)
-        .spacing([50.0, 4.0])
-        .striped(true)
-        .show(ui, |ui| {
-            self.general_params(ui);
-
-            ui.label(egui::RichText::new("Kernels").strong());
-            ui.end_row();
-
-            self.kernels(ui);
-        });
+    fn handle_events(&mut self) {
+        let events: Vec<Event> = self.connection.events().collect();
+        for event in events {
+            match event {
+                Event::Connected => {
+                    self.connected = true;
+                    self.last_error = None;
+                    self.telemetry.clear();
+                    self.awaiting.clear();
+                    //Leaves the timeline of a scene playing unless one is played from here
+                    self.timeline_sent = Some(Timeline::default());
+                },
+                Event::Disconnected => {
+                    self.connected = false;
+                    self.sent = None;
+                    self.awaiting.clear();
+                    self.timeline_sent = None;
+                },
+                Event::Message(message) => match *message {
+                    //Sent by the simulation after the handshake, it runs with what its scene and arguments set.
+                    //Later ones come from the timeline, scripts, the overlay or the HTTP API. They are taken over unless
+                    //an edit is still unsent, or they predate parameters sent from here
+                    Message::Parameters(parameters) => {
+                        if self.sent.is_none() {
+                            self.settings = *parameters;
+                            self.parameters = ParametersPanel::new(&parameters);
+                            self.sent = Some(*parameters);
+                        } else if self.sent == Some(self.settings) && !self.awaiting.contains(&true) {
+                            self.settings = *parameters;
+                            self.sent = Some(*parameters);
+                        }
+                    },
+                    Message::Ack => {
+                        self.awaiting.pop_front();
+                    },
+                    Message::Error(err) => {
+                        self.awaiting.pop_front();
+                        log::error!("Simulation refused a message: {err}");
+                        self.last_error = Some(err);
+                    },
+                    Message::Telemetry(telemetry) => self.telemetry.push(telemetry),
+                    _ => {}
+                }
+            }
+        }
     }
 
-    fn general_params(&mut self,  ui: &mut egui::Ui) {
-        ui.label("Scene scale:");
-        ui.add(egui::DragValue::new(&mut self.settings.scene_scale_factor).speed(0.001).clamp_range(0.01..=1.0));
-        ui.end_row();
-
-        ui.label("Time step:");
-        ui.add(egui::DragValue::new(&mut self.settings.time_step).speed(0.0001).clamp_range(0.0..=1.0));
-        ui.end_row();
-
-        ui.label("Particle's mass:");
-        ui.add(egui::DragValue::new(&mut self.settings.particle_mass).speed(0.1).clamp_range(0.1..=100.0));
-        ui.end_row();
-
-        ui.label("Particles's (draw) radius:");
-        ui.add(egui::DragValue::new(&mut self.settings.particle_radius).speed(0.1).clamp_range(0.5..=100.0));
-        ui.end_row();
-
-        ui.label("Collision damping:");
-        ui.add(egui::DragValue::new(&mut self.settings.collision_damping).speed(0.01).clamp_range(0.0..=1.0));
-        ui.end_row();
-
-        ui.label("Viscosity:");
-        ui.add(egui::DragValue::new(&mut self.settings.viscosity).speed(0.01).clamp_range(0.0..=100.0));
-        ui.end_row();
-
-        ui.label("Cohesion coef.:");
-        ui.add(egui::DragValue::new(&mut self.settings.cohesion_coef).speed(0.1).clamp_range(0.0..=50000.0));
-        ui.end_row();
-
-        ui.label("Curvature coef.:");
-        ui.add(egui::DragValue::new(&mut self.settings.curvature_cef).speed(0.1).clamp_range(0.0..=50000.0));
-        ui.end_row();
-
-        ui.label("Adhesion coef.:");
-        ui.add(egui::DragValue::new(&mut self.settings.adhesion_cef).speed(0.1).clamp_range(0.0..=50000.0));
-        ui.end_row();
-
-        ui.label("Rest density:");
-        ui.add(egui::DragValue::new(&mut self.settings.rest_density).speed(0.1).clamp_range(0.0..=1000.0));
-        ui.end_row();
-
-        ui.label("Intensity of vorticity:");
-        ui.add(egui::DragValue::new(&mut self.settings.vorticity_inensity).speed(0.01).clamp_range(0.0..= 1.0));
-        ui.end_row();
-
-        ui.label("Pressure multiplier:");
-        ui.add(egui::DragValue::new(&mut self.settings.pressure_multiplier).speed(0.1).clamp_range(0.0..=10000.0));
-        ui.end_row();
-
-        ui.label("Near pressure multiplier:");
-        ui.add(egui::DragValue::new(&mut self.settings.near_pressure_multiplier).speed(0.1).clamp_range(0.0..=10000.0));
-        ui.end_row();
-
-        ui.label("Grid size:");
-        ui.add(egui::DragValue::new(&mut self.settings.grid_size).speed(0.01).clamp_range(0.1..=10.0));
-        ui.end_row();
-
-        ui.label("Velocity smoothing scale:");
-        ui.add(egui::DragValue::new(&mut self.settings.velocity_smoothing_scale).speed(0.001).clamp_range(0.0..=1.0));
-        ui.end_row();
-
-        self.bounding_box(ui);
-        self.gravity(ui);
+    fn send(&mut self, message: Message) {
+        self.awaiting.push_back(matches!(message, Message::Parameters(_)));
+        self.connection.send(message);
     }
 
-    fn bounding_box(&mut self,  ui: &mut egui::Ui) {
-        let start_left = self.start_bound.position1[0];
-        let now_left = self.settings.bounding_box.position1[0];
-        let start_right = self.start_bound.position2[0];
-        let now_right = self.settings.bounding_box.position2[0];
-
-        let start_bottom = self.start_bound.position1[1];
-        let now_bottom = self.settings.bounding_box.position1[1];
-        let start_top = self.start_bound.position2[1];
-        let now_top = self.settings.bounding_box.position2[1];
-
-        ui.label("Bounding box:");
-        ui.vertical(|ui| {
-            ui.horizontal(|ui| {
-                ui.label("x1:");
-                ui.add(egui::DragValue::new(&mut self.settings.bounding_box.position1[0]).speed(1).clamp_range(start_left..=now_right-1.0));
-                ui.label("y1:");
-                ui.add(egui::DragValue::new(&mut self.settings.bounding_box.position1[1]).speed(1).clamp_range(start_bottom..=now_top-1.0));
-            });
-            ui.horizontal(|ui| {
-                ui.label("x2:");
-                ui.add(egui::DragValue::new(&mut self.settings.bounding_box.position2[0]).speed(1).clamp_range(now_left+1.0..=start_right));
-                ui.label("y2:");
-                ui.add(egui::DragValue::new(&mut self.settings.bounding_box.position2[1]).speed(1).clamp_range(now_bottom+1.0..=start_top));
-            });
-        });
-        ui.end_row();
+    fn send_command(&mut self, command: protocol::Command) {
+        self.send(Message::Command(command));
     }
+}
 
-    fn gravity(&mut self,  ui: &mut egui::Ui) {
-        ui.label("Gravity:");
+impl SettingsUI {
+    fn status(&mut self, ui: &mut egui::Ui) {
         ui.horizontal(|ui| {
-            ui.label("x:");
-            ui.add(egui::DragValue::new(&mut self.settings.gravity[0]).speed(0.01));
-            ui.label("y:");
-            ui.add(egui::DragValue::new(&mut self.settings.gravity[1]).speed(0.01));
+            if self.connected {
+                ui.label("Connected");
+            } else {
+                ui.label(format!("Connecting to {}...", self.address));
+            }
         });
-        ui.end_row();
-    }
-
-    fn kernels(&mut self,  ui: &mut egui::Ui) {
-        ui.label("Density kernel radius:");
-        ui.add(egui::DragValue::new(&mut self.settings.poly_kernel_radius).speed(0.01).clamp_range(0.1..=5.0));
-        ui.end_row();
-
-        ui.label("Pressure kernel radius:");
-        ui.add(egui::DragValue::new(&mut self.settings.pressure_kernel_radius).speed(0.01).clamp_range(0.1..=5.0));
-        ui.end_row();
-
-        ui.label("Near pressure kernel radius:");
-        ui.add(egui::DragValue::new(&mut self.settings.near_pressure_kernel_radius).speed(0.01).clamp_range(0.1..=5.0));
-        ui.end_row();
-
-        ui.label("Viscosity kernel radius:");
-        ui.add(egui::DragValue::new(&mut self.settings.viscosity_kernel_radius).speed(0.01).clamp_range(0.1..=5.0));
-        ui.end_row();
-
-        ui.label("Vorticity kernel radius:");
-        ui.add(egui::DragValue::new(&mut self.settings.vorticity_kernel_radius).speed(0.01).clamp_range(0.1..=5.0));
-        ui.end_row();
-
-        ui.label("Cohesion kernel radius:");
-        ui.add(egui::DragValue::new(&mut self.settings.cohesion_kernel_radius).speed(0.01).clamp_range(0.1..=5.0));
-        ui.end_row();
-
-        ui.label("Adhesion kernel radius:");
-        ui.add(egui::DragValue::new(&mut self.settings.adhesion_kernel_radius).speed(0.01).clamp_range(0.1..=5.0));
-        ui.end_row();
+        if let Some(err) = &self.last_error {
+            ui.colored_label(egui::Color32::LIGHT_RED, err);
+        }
 
-        ui.label("Surface normal kernel radius:");
-        ui.add(egui::DragValue::new(&mut self.settings.surface_normal_kernel_radius).speed(0.01).clamp_range(0.1..=5.0));
-        ui.end_row();
+        let paused = self.telemetry.latest().is_some_and(|telemetry| telemetry.paused);
+        let command = ui.add_enabled_ui(self.connected, |ui| command_bar(ui, paused)).inner;
+        if let Some(command) = command {
+            self.send_command(command);
+        }
     }
 }
 
 impl eframe::App for SettingsUI {
    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.handle_events();
+
+        egui::TopBottomPanel::top("Status")
+        .show(ctx, |ui| {
+            self.status(ui);
+        });
+
         egui::CentralPanel::default()
         .show(ctx, |ui| {
-            self.ui(ui);
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                egui::CollapsingHeader::new(egui::RichText::new("Presets").strong())
+                .default_open(true)
+                .show(ui, |ui| {
+                    self.presets.ui(ui, &mut self.settings, &mut self.timeline);
+                });
+                ui.separator();
+
+                self.parameters.ui(ui, &mut self.settings);
+
+                ui.separator();
+                egui::CollapsingHeader::new(egui::RichText::new("Timeline").strong())
+                .show(ui, |ui| {
+                    let time = self.telemetry.latest().map_or(0.0, |telemetry| telemetry.time);
+                    self.timeline_panel.ui(ui, &mut self.timeline, &self.settings, time);
+                });
+
+                ui.separator();
+                egui::CollapsingHeader::new(egui::RichText::new("Statistics").strong())
+                .default_open(true)
+                .show(ui, |ui| {
+                    self.telemetry.ui(ui);
+                });
+            });
         });
 
-        let new_instant = std::time::Instant::now();
-
-        if (new_instant - self.last_instant).as_secs_f32() > 0.01 {
-            self.last_instant = new_instant;
-            match bincode::serialize(&self.settings) {
-                Ok(value) => {
-                    match self.stream.write_all(&value) {
-                        Ok(_) => {},
-                        Err(err) => {
-                            self.stream = TcpStream::connect("127.0.0.1:12345").unwrap();
-                            log::error!("Stream write error: {}", err);
-                        }
-                    }
-                },
-                Err(err) => {
-                    log::error!("Serde error: {}", err);
-                }
-            }
+        //Only valid changes are sent, once the simulation's own parameters are known
+        if self.connected && self.sent.is_some_and(|sent| sent != self.settings) && self.settings.validate().is_valid() {
+            self.send(Message::Parameters(Box::new(self.settings)));
+            self.sent = Some(self.settings);
+        }
+
+        let playback = self.timeline_panel.playback(&self.timeline);
+        if self.connected && self.timeline_sent.as_ref().is_some_and(|sent| *sent != playback) {
+            self.send(Message::Timeline(playback.clone()));
+            self.timeline_sent = Some(playback);
         }
    }
 
    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
         info!("{:?}", self.settings);
+        preset::save_session(self.settings, &self.timeline);
    }
 }
\ No newline at end of file
@@ -0,0 +1,226 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+use settings::descriptor::PARAMETERS;
+use settings::protocol::{Command, Telemetry};
+use settings::SimulationParameters;
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+use tungstenite::protocol::Role;
+use tungstenite::WebSocket;
+
+use crate::remote::COMMANDS;
+use crate::uniforms::parameters::SIMULATION_PARAMETERS;
+
+//HTTP and WebSocket control of the simulation for scripts, only reachable from the local machine.
+//  GET  /params            current parameters
+//  PUT  /params            update parameters, fields left out keep their value
+//  GET  /params/meta       label, unit, range, step, group and tooltip of every parameter
+//  POST /commands/<name>   pause, resume, step, reset, snapshot or screenshot
+//  GET  /telemetry         latest statistics
+//  GET  /telemetry/stream  WebSocket sending every new sample as a JSON text message
+//  GET  /schema            JSON schema of all of the above
+
+type JsonResponse = Response<std::io::Cursor<Vec<u8>>>;
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+static LATEST: Lazy<Mutex<Option<Telemetry>>> = Lazy::new(|| Mutex::new(None));
+static SUBSCRIBERS: Lazy<Mutex<Vec<Sender<String>>>> = Lazy::new(|| Mutex::new(vec![]));
+
+pub fn is_running() -> bool {
+    RUNNING.load(Ordering::Relaxed)
+}
+
+//Keep the sample for GET requests and pass it on to the open streams
+pub fn publish(telemetry: &Telemetry) {
+    *LATEST.lock().unwrap() = Some(*telemetry);
+    let Ok(text) = serde_json::to_string(telemetry) else { return; };
+    SUBSCRIBERS.lock().unwrap().retain(|subscriber| subscriber.send(text.clone()).is_ok());
+}
+
+pub fn listener(port: u16) {
+    let server = match Server::http(("127.0.0.1", port)) {
+        Ok(server) => server,
+        Err(err) => {
+            log::error!("Failed to start the control API on port {port}: {err}");
+            return;
+        }
+    };
+    log::info!("Control API listening on http://127.0.0.1:{port}");
+    RUNNING.store(true, Ordering::Relaxed);
+
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            handle(request, port);
+        }
+    });
+}
+
+//Web pages the browser runs can reach localhost too, requests naming another host (DNS rebinding)
+//or sent from another origin are refused
+fn is_local(request: &Request, port: u16) -> bool {
+    let hosts = [format!("127.0.0.1:{port}"), format!("localhost:{port}")];
+    let header = |name: &'static str| request.headers().iter().find(|header| header.field.equiv(name)).map(|header| header.value.as_str());
+
+    let host_allowed = header("Host").is_some_and(|host| hosts.iter().any(|allowed| allowed == host));
+    let origin_allowed = header("Origin").is_none_or(|origin| hosts.iter().any(|allowed| origin == format!("http://{allowed}")));
+    host_allowed && origin_allowed
+}
+
+fn handle(mut request: Request, port: u16) {
+    if !is_local(&request, port) {
+        if let Err(err) = request.respond(error(StatusCode(403), "only requests from localhost are served")) {
+            log::error!("Failed to answer an API request: {err}");
+        }
+        return;
+    }
+
+    let url = request.url().trim_end_matches('/').to_string();
+    let response = match (request.method(), url.as_str()) {
+        (Method::Get, "/params") => ok(&*SIMULATION_PARAMETERS.lock().unwrap()),
+        (Method::Get, "/params/meta") => ok(&PARAMETERS),
+        (Method::Put, "/params") => {
+            let mut body = String::new();
+            match request.as_reader().read_to_string(&mut body) {
+                Ok(_) => update_parameters(&body),
+                Err(err) => error(StatusCode(400), &err.to_string())
+            }
+        },
+        (Method::Post, command) if command.starts_with("/commands/") => {
+            match parse_command(&command["/commands/".len()..]) {
+                Some(command) => {
+                    COMMANDS.lock().unwrap().push_back(command);
+                    json_response(StatusCode(202), &json!({ "queued": command }))
+                },
+                None => error(StatusCode(404), "unknown command")
+            }
+        },
+        (Method::Get, "/telemetry") => match *LATEST.lock().unwrap() {
+            Some(telemetry) => ok(&telemetry),
+            None => error(StatusCode(503), "no statistics sampled yet")
+        },
+        (Method::Get, "/telemetry/stream") => {
+            stream_telemetry(request);
+            return;
+        },
+        (Method::Get, "/schema") => ok(&schema()),
+        _ => error(StatusCode(404), "not found")
+    };
+
+    if let Err(err) = request.respond(response) {
+        log::error!("Failed to answer an API request: {err}");
+    }
+}
+
+//The body is merged into the current parameters, so a script can change a single value
+fn update_parameters(body: &str) -> JsonResponse {
+    let update: Value = match serde_json::from_str(body) {
+        Ok(Value::Object(update)) => Value::Object(update),
+        Ok(_) => return error(StatusCode(400), "expected a JSON object"),
+        Err(err) => return error(StatusCode(400), &err.to_string())
+    };
+
+    let mut sim = SIMULATION_PARAMETERS.lock().unwrap();
+    let mut merged = serde_json::to_value(*sim).unwrap();
+    merge(&mut merged, update);
+    let parameters: SimulationParameters = match serde_json::from_value(merged) {
+        Ok(parameters) => parameters,
+        Err(err) => return error(StatusCode(400), &err.to_string())
+    };
+
+    //Values already out of range, e.g. from a script, don't block changing other fields
+    for descriptor in PARAMETERS {
+        let value = descriptor.get(&parameters);
+        if value != descriptor.get(&sim) {
+            if descriptor.restart() {
+                return error(StatusCode(409), &format!("{} can't be changed while running", descriptor.name));
+            }
+            if let Err(message) = descriptor.check(&value) {
+                return error(StatusCode(422), &message);
+            }
+        }
+    }
+    let validation = parameters.validate();
+    if !validation.is_valid() {
+        let errors: Vec<String> = validation.errors.iter().map(ToString::to_string).collect();
+        return json_response(StatusCode(422), &json!({ "error": validation.error_message(), "errors": errors }));
+    }
+    *sim = parameters;
+    ok(&parameters)
+}
+
+//Objects are merged key by key, anything else is replaced
+fn merge(base: &mut Value, update: Value) {
+    match (base, update) {
+        (Value::Object(base), Value::Object(update)) => {
+            for (key, value) in update {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => { base.insert(key, value); }
+                }
+            }
+        },
+        (base, update) => *base = update
+    }
+}
+
+fn parse_command(name: &str) -> Option<Command> {
+    match name {
+        "pause" => Some(Command::Pause),
+        "resume" => Some(Command::Resume),
+        "step" => Some(Command::Step),
+        "reset" => Some(Command::Reset),
+        "snapshot" => Some(Command::Snapshot),
+        "screenshot" => Some(Command::Screenshot),
+        _ => None
+    }
+}
+
+fn stream_telemetry(request: Request) {
+    let key = request.headers().iter()
+        .find(|header| header.field.equiv("Sec-WebSocket-Key"))
+        .map(|header| header.value.to_string());
+    let Some(key) = key else {
+        let _ = request.respond(error(StatusCode(400), "expected a WebSocket upgrade"));
+        return;
+    };
+
+    let response = Response::empty(StatusCode(101))
+        .with_header(Header::from_bytes("Sec-WebSocket-Accept", tungstenite::handshake::derive_accept_key(key.as_bytes())).unwrap());
+    let stream = request.upgrade("websocket", response);
+
+    let (sender, receiver) = channel();
+    SUBSCRIBERS.lock().unwrap().push(sender);
+    std::thread::spawn(move || {
+        let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+        for text in receiver {
+            if socket.send(tungstenite::Message::Text(text)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+fn schema() -> Value {
+    json!({
+        "parameters": schemars::schema_for!(SimulationParameters),
+        "telemetry": schemars::schema_for!(Telemetry),
+        "commands": ["pause", "resume", "step", "reset", "snapshot", "screenshot"]
+    })
+}
+
+fn ok(value: &impl serde::Serialize) -> JsonResponse {
+    json_response(StatusCode(200), value)
+}
+
+fn error(status: StatusCode, message: &str) -> JsonResponse {
+    json_response(status, &json!({ "error": message }))
+}
+
+fn json_response(status: StatusCode, value: &impl serde::Serialize) -> JsonResponse {
+    Response::from_string(serde_json::to_string_pretty(value).unwrap())
+        .with_status_code(status)
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap())
+}
@@ -0,0 +1,327 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use crate::cli::CaptureArgs;
+
+//Frame copied into a staging buffer, waiting for the buffer to be mapped
+struct PendingFrame {
+    buffer: wgpu::Buffer,
+    ready: Arc<AtomicBool>,
+    mapping: bool,
+    job: FrameJob
+}
+
+struct FrameJob {
+    frame: u64,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    bgra: bool,
+    sequence: bool,
+    screenshot: bool,
+    data: Vec<u8>
+}
+
+impl FrameJob {
+    //Tightly packed RGBA rows
+    fn rgba(&self) -> Vec<u8> {
+        let row = (self.width * 4) as usize;
+        let mut rgba = Vec::with_capacity(row * self.height as usize);
+        for y in 0..self.height as usize {
+            let start = y * self.padded_bytes_per_row as usize;
+            rgba.extend_from_slice(&self.data[start..start + row]);
+        }
+        if self.bgra {
+            rgba.chunks_exact_mut(4).for_each(|pixel| pixel.swap(0, 2));
+        }
+        rgba
+    }
+}
+
+pub struct CaptureState {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    every: u32,
+    sequence: bool,
+    screenshot_requested: bool,
+    //Frames are only added to the sequence once while the simulation is paused
+    last_sequence_frame: Option<u64>,
+    pending: VecDeque<PendingFrame>,
+    writer: Option<(Sender<FrameJob>, JoinHandle<()>)>
+}
+
+impl CaptureState {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, width: u32, height: u32, args: &CaptureArgs) -> Self {
+        let supported = matches!(format,
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb |
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb);
+        if !supported {
+            log::error!("Frame capture is not supported for {format:?} render targets");
+        }
+
+        if let Some(dir) = &args.capture {
+            if let Err(err) = std::fs::create_dir_all(dir) {
+                log::error!("Failed to create capture directory {}: {err}", dir.display());
+            }
+        }
+
+        let sequence = supported && (args.capture.is_some() || args.ffmpeg.is_some());
+        let ffmpeg = args.ffmpeg.as_ref().filter(|_| sequence).and_then(|output| spawn_ffmpeg(output, width, height, args.fps));
+
+        let writer = supported.then(|| {
+            let (sender, receiver) = channel::<FrameJob>();
+            let capture_dir = args.capture.clone();
+            let screenshot_dir = args.screenshot_dir.clone();
+
+            let handle = std::thread::spawn(move || {
+                let mut ffmpeg = ffmpeg;
+                for job in receiver {
+                    write_frame(&job, capture_dir.as_deref(), &screenshot_dir, &mut ffmpeg);
+                }
+                if let Some(ffmpeg) = ffmpeg {
+                    ffmpeg.finish();
+                }
+            });
+
+            (sender, handle)
+        });
+
+        let (texture, view) = create_target(device, format, width, height);
+
+        CaptureState {
+            texture,
+            view,
+            format,
+            width,
+            height,
+            every: args.capture_every.max(1),
+            sequence,
+            screenshot_requested: false,
+            last_sequence_frame: None,
+            pending: VecDeque::new(),
+            writer
+        }
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn request_screenshot(&mut self) {
+        self.screenshot_requested = true;
+    }
+
+    pub fn wants_frame(&self, frame: u64) -> bool {
+        self.writer.is_some() && (self.screenshot_requested || self.sequence_due(frame))
+    }
+
+    fn sequence_due(&self, frame: u64) -> bool {
+        self.sequence && frame.is_multiple_of(self.every as u64) && self.last_sequence_frame != Some(frame)
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        (self.texture, self.view) = create_target(device, self.format, width, height);
+        self.width = width;
+        self.height = height;
+    }
+
+    //Record a copy of the rendered target. The buffer is mapped once the copy is submitted
+    pub fn capture(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, frame: u64) {
+        let padded_bytes_per_row = (self.width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let sequence = self.sequence_due(frame);
+        if sequence {
+            self.last_sequence_frame = Some(frame);
+        }
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture buffer"),
+            size: (padded_bytes_per_row * self.height) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+
+        encoder.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: None
+                }
+            },
+            wgpu::Extent3d { width: self.width, height: self.height, depth_or_array_layers: 1 }
+        );
+
+        self.pending.push_back(PendingFrame {
+            buffer,
+            ready: Arc::new(AtomicBool::new(false)),
+            mapping: false,
+            job: FrameJob {
+                frame,
+                width: self.width,
+                height: self.height,
+                padded_bytes_per_row,
+                bgra: matches!(self.format, wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb),
+                sequence,
+                screenshot: self.screenshot_requested,
+                data: vec![]
+            }
+        });
+        self.screenshot_requested = false;
+    }
+
+    //Start mapping the submitted frames and pass the mapped ones over to the writer thread in order
+    pub fn poll(&mut self, device: &wgpu::Device, wait: bool) {
+        for pending in self.pending.iter_mut().filter(|p| !p.mapping) {
+            let ready = pending.ready.clone();
+            pending.buffer.slice(..).map_async(wgpu::MapMode::Read, move |res| {
+                if let Err(err) = res {
+                    log::error!("Failed to map capture buffer: {err}");
+                }
+                ready.store(true, Ordering::Release);
+            });
+            pending.mapping = true;
+        }
+
+        device.poll(if wait { wgpu::Maintain::Wait } else { wgpu::Maintain::Poll });
+
+        while self.pending.front().is_some_and(|p| p.ready.load(Ordering::Acquire)) {
+            let mut pending = self.pending.pop_front().unwrap();
+            pending.job.data = pending.buffer.slice(..).get_mapped_range().to_vec();
+            pending.buffer.unmap();
+
+            if let Some((sender, _)) = &self.writer {
+                if sender.send(pending.job).is_err() {
+                    log::error!("Capture writer thread has stopped");
+                }
+            }
+        }
+    }
+
+    //Wait for every captured frame to be written
+    pub fn finish(&mut self, device: &wgpu::Device) {
+        while !self.pending.is_empty() {
+            self.poll(device, true);
+        }
+
+        if let Some((sender, handle)) = self.writer.take() {
+            drop(sender);
+            if handle.join().is_err() {
+                log::error!("Capture writer thread panicked");
+            }
+        }
+    }
+}
+
+fn create_target(device: &wgpu::Device, format: wgpu::TextureFormat, width: u32, height: u32) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Capture texture"),
+        size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[]
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    (texture, view)
+}
+
+//ffmpeg process encoding raw RGBA frames read from stdin
+struct Ffmpeg {
+    child: Child,
+    stdin: ChildStdin,
+    width: u32,
+    height: u32
+}
+
+impl Ffmpeg {
+    fn finish(self) {
+        let Ffmpeg { mut child, stdin, .. } = self;
+        //Closing stdin lets ffmpeg finish the video
+        drop(stdin);
+        if let Err(err) = child.wait() {
+            log::error!("ffmpeg failed: {err}");
+        }
+    }
+}
+
+fn spawn_ffmpeg(output: &Path, width: u32, height: u32, fps: u32) -> Option<Ffmpeg> {
+    let res = Command::new("ffmpeg")
+        .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgba"])
+        .args(["-s", &format!("{width}x{height}"), "-r", &fps.to_string(), "-i", "-"])
+        .args(["-c:v", "libx264", "-pix_fmt", "yuv420p"])
+        .arg(output)
+        .stdin(Stdio::piped())
+        .spawn();
+
+    match res {
+        Ok(mut child) => {
+            let stdin = child.stdin.take()?;
+            Some(Ffmpeg { child, stdin, width, height })
+        },
+        Err(err) => {
+            log::error!("Failed to spawn ffmpeg: {err}");
+            None
+        }
+    }
+}
+
+fn write_frame(job: &FrameJob, capture_dir: Option<&Path>, screenshot_dir: &Path, ffmpeg: &mut Option<Ffmpeg>) {
+    let rgba = job.rgba();
+
+    if job.sequence {
+        if let Some(dir) = capture_dir {
+            let path = dir.join(format!("frame_{:06}.png", job.frame));
+            if let Err(err) = write_png(&path, job.width, job.height, &rgba) {
+                log::error!("Failed to write {}: {err}", path.display());
+            }
+        }
+
+        if let Some(stream) = ffmpeg {
+            if stream.width != job.width || stream.height != job.height {
+                log::warn!("Frame {} has a different size than the video, skipping it", job.frame);
+            } else if let Err(err) = stream.stdin.write_all(&rgba) {
+                log::error!("Failed to pipe frame to ffmpeg: {err}");
+                if let Some(stream) = ffmpeg.take() {
+                    stream.finish();
+                }
+            }
+        }
+    }
+
+    if job.screenshot {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let path = screenshot_dir.join(format!("screenshot_{timestamp}_{:06}.png", job.frame));
+        match write_png(&path, job.width, job.height, &rgba) {
+            Ok(_) => log::info!("Saved screenshot to {}", path.display()),
+            Err(err) => log::error!("Failed to write {}: {err}", path.display())
+        }
+    }
+}
+
+fn write_png(path: &Path, width: u32, height: u32, rgba: &[u8]) -> Result<(), png::EncodingError> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(rgba)
+}
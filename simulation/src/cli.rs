@@ -0,0 +1,168 @@
+use std::path::PathBuf;
+
+use clap::{Args as ClapArgs, Parser};
+use settings::descriptor::ParameterOverride;
+use settings::transport::Endpoint;
+
+use crate::particle_export::{Attribute, ExportFormat};
+use crate::surface::SurfaceField;
+
+#[derive(Parser, Debug, Clone)]
+#[command(about = "Particle based water simulation")]
+pub struct Args {
+    /// Run without a window, rendering only what is captured or exported
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Scene to start from, a preset file with the simulation parameters and optionally a script (.toml or .json)
+    #[arg(long, value_name = "FILE")]
+    pub scene: Option<PathBuf>,
+
+    /// Rhai script run before every step, replaces the script of the scene
+    #[arg(long, value_name = "FILE")]
+    pub script: Option<PathBuf>,
+
+    /// Where to listen for the settings UI, as host:port or unix:path
+    #[arg(long, value_name = "ENDPOINT", env = "WSIM_ADDRESS", default_value_t = Endpoint::default())]
+    pub address: Endpoint,
+
+    /// Serve the HTTP/WebSocket control API on this port of localhost
+    #[arg(long, value_name = "PORT", env = "WSIM_API_PORT")]
+    pub api: Option<u16>,
+
+    /// Show the settings overlay at startup (toggle with F1)
+    #[arg(long)]
+    pub overlay: bool,
+
+    /// Override a parameter of the scene, vectors take comma separated components and choices their name, e.g. `--set gravity=0,-9.8` or `--set pressure_solver=Pbf` (repeatable)
+    #[arg(long = "set", value_name = "NAME=VALUE")]
+    pub overrides: Vec<ParameterOverride>,
+
+    /// Stop after step N, resumed runs keep counting from the snapshot [default: run until closed]
+    #[arg(long, value_name = "N")]
+    pub steps: Option<u64>,
+
+    /// Print the wall time per step when a headless run ends, to compare options on the same scene
+    #[arg(long)]
+    pub timing: bool,
+
+    /// Seed of the random numbers used by the simulation, runs with the same seed and scene are identical on the same adapter [default: 0, or the seed of the resumed snapshot]
+    #[arg(long)]
+    pub seed: Option<u32>,
+
+    /// Subgroup size of the radix sort, skips detecting it on the adapter (which can hang on software adapters)
+    #[arg(long, value_name = "SIZE", env = "WSIM_SORT_SUBGROUP_SIZE")]
+    pub sort_subgroup_size: Option<u32>,
+
+    /// Move the particle data into cell order after every neighbour search instead of reaching neighbours through the sorted ids
+    #[arg(long)]
+    pub reorder_particles: bool,
+
+    #[command(flatten)]
+    pub surface: SurfaceArgs,
+
+    #[command(flatten)]
+    pub capture: CaptureArgs,
+
+    #[command(flatten)]
+    pub export: ExportArgs,
+
+    #[command(flatten)]
+    pub snapshot: SnapshotArgs,
+}
+
+#[derive(ClapArgs, Debug, Clone)]
+pub struct SurfaceArgs {
+    /// Directory to export the extracted free surface to, as SVG and OBJ files
+    #[arg(long, value_name = "DIR")]
+    pub surface_export: Option<PathBuf>,
+
+    /// Export the surface every N steps
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub surface_every: u32,
+
+    /// Field the surface is extracted from
+    #[arg(long, value_enum, default_value_t = SurfaceField::Colour)]
+    pub surface_field: SurfaceField,
+
+    /// Size of a cell of the surface grid, in pixels
+    #[arg(long, value_name = "PIXELS", default_value_t = 4.0, value_parser = positive)]
+    pub surface_cell_size: f32,
+
+    /// Iso value of the surface [default: 0.5 for colour, half of the rest density for density]
+    #[arg(long, value_name = "VALUE")]
+    pub surface_iso: Option<f32>,
+
+    /// Render the extracted surface instead of particles (toggle with M)
+    #[arg(long)]
+    pub render_surface: bool,
+}
+
+#[derive(ClapArgs, Debug, Clone)]
+pub struct CaptureArgs {
+    /// Directory to write the rendered frames to, as numbered PNG files
+    #[arg(long, value_name = "DIR")]
+    pub capture: Option<PathBuf>,
+
+    /// Capture every N steps
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub capture_every: u32,
+
+    /// Encode the captured frames into a video with a local ffmpeg
+    #[arg(long, value_name = "FILE")]
+    pub ffmpeg: Option<PathBuf>,
+
+    /// Frame rate of the encoded video
+    #[arg(long, default_value_t = 60)]
+    pub fps: u32,
+
+    /// Directory to save screenshots to (taken with F12)
+    #[arg(long, value_name = "DIR", default_value = ".")]
+    pub screenshot_dir: PathBuf,
+}
+
+#[derive(ClapArgs, Debug, Clone)]
+pub struct ExportArgs {
+    /// Directory to export the particle data to
+    #[arg(long, value_name = "DIR")]
+    pub export: Option<PathBuf>,
+
+    /// File format of the exported particle data
+    #[arg(long, value_enum, default_value_t = ExportFormat::Vtu)]
+    pub export_format: ExportFormat,
+
+    /// Export every N steps
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub export_every: u32,
+
+    /// Attributes exported along with the positions
+    #[arg(long, value_enum, value_delimiter = ',', default_values_t = [
+        Attribute::Velocity, Attribute::Density, Attribute::NearDensity, Attribute::SurfaceNormal, Attribute::Vorticity
+    ])]
+    pub export_attributes: Vec<Attribute>,
+}
+
+#[derive(ClapArgs, Debug, Clone)]
+pub struct SnapshotArgs {
+    /// Resume the simulation from a snapshot file
+    #[arg(long, value_name = "FILE")]
+    pub resume: Option<PathBuf>,
+
+    /// Save a snapshot every N steps
+    #[arg(long, value_name = "N")]
+    pub snapshot_every: Option<u32>,
+
+    /// Directory to save snapshots to (taken with F5)
+    #[arg(long, value_name = "DIR", default_value = ".")]
+    pub snapshot_dir: PathBuf,
+}
+
+//Sizes that are divided by
+fn positive(text: &str) -> Result<f32, String> {
+    let value: f32 = text.parse().map_err(|error| format!("{error}"))?;
+    if value.is_finite() && value > 0.0 {
+        Ok(value)
+    } else {
+        Err("must be greater than 0".to_string())
+    }
+}
@@ -0,0 +1,159 @@
+use crate::particle::NeighbourSearchGridState;
+
+//Buckets a workgroup of the scan covers, SCAN_BLOCK in counting_sort.wgsl
+const SCAN_BLOCK: u32 = 1024;
+
+//Sorts the particles by bucket without wgpu_sort: particles are counted per bucket, the counts
+//summed up into the start and end of every bucket and each particle written to its place
+pub struct CountingSortState {
+    count_buffer: wgpu::Buffer,
+    rank_buffer: wgpu::Buffer,
+    key_buffer: wgpu::Buffer,
+    unordered_buffer: wgpu::Buffer,
+    block_sums_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    count_pipeline: wgpu::ComputePipeline,
+    scan_blocks_pipeline: wgpu::ComputePipeline,
+    scan_block_sums_pipeline: wgpu::ComputePipeline,
+    add_block_offsets_pipeline: wgpu::ComputePipeline,
+    scatter_pipeline: wgpu::ComputePipeline,
+    order_pipeline: wgpu::ComputePipeline
+}
+
+impl CountingSortState {
+    pub fn new(device: &wgpu::Device, grid_state: &NeighbourSearchGridState) -> Self {
+        let length = (grid_state.key_cell_hash_buffer.size() / std::mem::size_of::<u32>() as u64) as u32;
+
+        //Sized for the table before every sort
+        let count_buffer = Self::create_buffer(device, "Counting sort count buffer", 1);
+        let rank_buffer = Self::create_buffer(device, "Counting sort rank buffer", length);
+        let key_buffer = Self::create_buffer(device, "Counting sort key buffer", length);
+        let unordered_buffer = Self::create_buffer(device, "Counting sort unordered buffer", length);
+        let block_sums_buffer = Self::create_buffer(device, "Counting sort block sums buffer", 1);
+
+        let storage = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Counting sort bind group layout"),
+            entries: &[
+                //Particles per bucket
+                storage(0),
+                //Rank of every particle in its bucket
+                storage(1),
+                //Bucket of every particle
+                storage(2),
+                //Particles in their bucket before ordering them
+                storage(3),
+                //Totals of the blocks of the scan
+                storage(4),
+            ]
+        });
+
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, [&count_buffer, &rank_buffer, &key_buffer, &unordered_buffer, &block_sums_buffer]);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Counting sort shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/counting_sort.wgsl").into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Counting sort pipeline layout"),
+            bind_group_layouts: &[
+                &grid_state.bind_group_layout,
+                &bind_group_layout
+            ],
+            push_constant_ranges: &[]
+        });
+
+        let pipeline = |entry_point: &str| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(entry_point),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point
+        });
+
+        CountingSortState {
+            count_pipeline: pipeline("countCells"),
+            scan_blocks_pipeline: pipeline("scanBlocks"),
+            scan_block_sums_pipeline: pipeline("scanBlockSums"),
+            add_block_offsets_pipeline: pipeline("addBlockOffsets"),
+            scatter_pipeline: pipeline("scatterParticles"),
+            order_pipeline: pipeline("orderCells"),
+            count_buffer,
+            rank_buffer,
+            key_buffer,
+            unordered_buffer,
+            block_sums_buffer,
+            bind_group_layout,
+            bind_group
+        }
+    }
+
+    fn create_buffer(device: &wgpu::Device, label: &str, length: u32) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: (std::mem::size_of::<u32>() * length as usize) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        })
+    }
+
+    //Buffers in the order of their bindings
+    fn create_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, buffers: [&wgpu::Buffer; 5]) -> wgpu::BindGroup {
+        let entries: Vec<_> = buffers.iter().zip(0..).map(|(buffer, binding)| wgpu::BindGroupEntry {
+            binding,
+            resource: buffer.as_entire_binding()
+        }).collect();
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Counting sort bind group"),
+            layout,
+            entries: &entries
+        })
+    }
+
+    //Resize the counts for the table of the grid state
+    fn prepare(&mut self, device: &wgpu::Device, table_size: u32) {
+        if self.count_buffer.size() == (std::mem::size_of::<u32>() * table_size as usize) as u64 {
+            return;
+        }
+        self.count_buffer = Self::create_buffer(device, "Counting sort count buffer", table_size);
+        self.block_sums_buffer = Self::create_buffer(device, "Counting sort block sums buffer", table_size.div_ceil(SCAN_BLOCK));
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, [&self.count_buffer, &self.rank_buffer, &self.key_buffer, &self.unordered_buffer, &self.block_sums_buffer]);
+    }
+
+    //Sort the keys calcHash left in the grid state and fill in the start and end of every bucket
+    pub fn sort(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, grid_state: &NeighbourSearchGridState) {
+        let table_size = (grid_state.cell_start_buffer.size() / std::mem::size_of::<u32>() as u64) as u32;
+        self.prepare(device, table_size);
+
+        encoder.clear_buffer(&self.count_buffer, 0, None);
+        encoder.copy_buffer_to_buffer(&grid_state.key_cell_hash_buffer, 0, &self.key_buffer, 0, self.key_buffer.size());
+
+        let length = (self.key_buffer.size() / std::mem::size_of::<u32>() as u64) as u32;
+        let dispatch = |encoder: &mut wgpu::CommandEncoder, pipeline: &wgpu::ComputePipeline, workgroups: u32| {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            compute_pass.set_pipeline(pipeline);
+            compute_pass.set_bind_group(0, &grid_state.bind_group, &[]);
+            compute_pass.set_bind_group(1, &self.bind_group, &[]);
+            compute_pass.dispatch_workgroups(workgroups, 1, 1);
+        };
+
+        dispatch(encoder, &self.count_pipeline, length.div_ceil(64));
+        dispatch(encoder, &self.scan_blocks_pipeline, table_size.div_ceil(SCAN_BLOCK));
+        dispatch(encoder, &self.scan_block_sums_pipeline, 1);
+        dispatch(encoder, &self.add_block_offsets_pipeline, table_size.div_ceil(64));
+        dispatch(encoder, &self.scatter_pipeline, length.div_ceil(64));
+        dispatch(encoder, &self.order_pipeline, length.div_ceil(64));
+    }
+}
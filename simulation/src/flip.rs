@@ -0,0 +1,255 @@
+use settings::SimulationParameters;
+
+use crate::particle::ParticlesState;
+use crate::readback::read_buffer;
+
+//Size and placement of the MAC grid, Grid in flip.wgsl
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+struct GridUniform {
+    origin: [f32; 2],
+    size: [u32; 2],
+    //In pixels
+    cell_size: f32,
+    _padding: [f32; 3]
+}
+
+impl GridUniform {
+    //Covers the bounding box with cells of flip_cell_size
+    fn new(sim: &SimulationParameters) -> Self {
+        let position1 = sim.bounding_box.position1;
+        GridUniform {
+            origin: [position1[0], position1[1]],
+            size: sim.flip_grid_size(),
+            cell_size: sim.flip_cell_size * sim.pixels_per_metre,
+            ..Default::default()
+        }
+    }
+
+    fn cells(&self) -> u32 {
+        self.size[0] * self.size[1]
+    }
+
+    //u faces followed by v faces, validation keeps them under MAX_FLIP_FACES so this fits
+    fn faces(&self) -> u32 {
+        (self.size[0] + 1) * self.size[1] + self.size[0] * (self.size[1] + 1)
+    }
+}
+
+//Buffers of one grid size, recreated when the bounding box or the cell size change the number of cells
+struct GridBuffers {
+    size: [u32; 2],
+    accumulator_buffer: wgpu::Buffer,
+    cell_type_buffer: wgpu::Buffer,
+    pressure_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup
+}
+
+//Hybrid particle-grid solver. Particles carry the fluid and are rendered as usual, the pressure
+//is solved on a MAC grid every step
+pub struct FlipState {
+    grid_buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    particles_to_grid_pipeline: wgpu::ComputePipeline,
+    normalize_pipeline: wgpu::ComputePipeline,
+    divergence_pipeline: wgpu::ComputePipeline,
+    jacobi_even_pipeline: wgpu::ComputePipeline,
+    jacobi_odd_pipeline: wgpu::ComputePipeline,
+    project_pipeline: wgpu::ComputePipeline,
+    grid_to_particles_pipeline: wgpu::ComputePipeline,
+    buffers: Option<GridBuffers>
+}
+
+impl FlipState {
+    pub fn new(device: &wgpu::Device, particles_state: &ParticlesState, uniform_bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+        let grid_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("FLIP grid uniform"),
+            size: std::mem::size_of::<GridUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+
+        let storage = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("FLIP grid bind group layout"),
+            entries: &[
+                //Accumulated momentum and weights
+                storage(0),
+                //Velocity
+                storage(1),
+                //Velocity before the projection
+                storage(2),
+                //Cell type
+                storage(3),
+                //Pressure
+                storage(4),
+                storage(5),
+                //Divergence
+                storage(6),
+                //APIC velocity gradients of the particles
+                storage(7),
+                //Grid
+                wgpu::BindGroupLayoutEntry {
+                    binding: 8,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ]
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("FLIP shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/flip.wgsl").into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("FLIP pipeline layout"),
+            bind_group_layouts: &[
+                &particles_state.particles_bind_group_layout,
+                &bind_group_layout,
+                uniform_bind_group_layout
+            ],
+            push_constant_ranges: &[]
+        });
+
+        let pipeline = |entry_point: &str| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(entry_point),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point
+        });
+
+        FlipState {
+            grid_buffer,
+            particles_to_grid_pipeline: pipeline("flip_particles_to_grid"),
+            normalize_pipeline: pipeline("flip_normalize"),
+            divergence_pipeline: pipeline("flip_divergence"),
+            jacobi_even_pipeline: pipeline("flip_jacobi_even"),
+            jacobi_odd_pipeline: pipeline("flip_jacobi_odd"),
+            project_pipeline: pipeline("flip_project"),
+            grid_to_particles_pipeline: pipeline("flip_grid_to_particles"),
+            bind_group_layout,
+            buffers: None
+        }
+    }
+
+    //Make sure the buffers fit the grid of the parameters and upload it
+    fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, sim: &SimulationParameters, particles_state: &ParticlesState) -> GridUniform {
+        let grid = GridUniform::new(sim);
+        queue.write_buffer(&self.grid_buffer, 0, bytemuck::cast_slice(&[grid]));
+
+        if self.buffers.as_ref().is_some_and(|buffers| buffers.size == grid.size) {
+            return grid;
+        }
+
+        let buffer = |label: &str, size: u32| device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: (std::mem::size_of::<u32>() as u32 * size) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+
+        let accumulator_buffer = buffer("FLIP accumulator buffer", 2 * grid.faces());
+        let velocity_buffer = buffer("FLIP velocity buffer", grid.faces());
+        let saved_velocity_buffer = buffer("FLIP saved velocity buffer", grid.faces());
+        let cell_type_buffer = buffer("FLIP cell type buffer", grid.cells());
+        let pressure_buffer = buffer("FLIP pressure buffer", grid.cells());
+        let pressure_next_buffer = buffer("FLIP next pressure buffer", grid.cells());
+        let divergence_buffer = buffer("FLIP divergence buffer", grid.cells());
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("FLIP grid bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: accumulator_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: velocity_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: saved_velocity_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: cell_type_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 4, resource: pressure_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 5, resource: pressure_next_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 6, resource: divergence_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 7, resource: particles_state.affine_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 8, resource: self.grid_buffer.as_entire_binding() },
+            ]
+        });
+
+        self.buffers = Some(GridBuffers {
+            size: grid.size,
+            accumulator_buffer,
+            cell_type_buffer,
+            pressure_buffer,
+            bind_group
+        });
+        grid
+    }
+
+    //Transfer the particles to the grid, make it divergence-free and move the particles with it
+    pub fn step(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, sim: &SimulationParameters, particles_state: &ParticlesState, uniform_bind_group: &wgpu::BindGroup) {
+        let grid = self.prepare(device, queue, sim, particles_state);
+        let Some(buffers) = &self.buffers else { return; };
+
+        encoder.clear_buffer(&buffers.accumulator_buffer, 0, None);
+        encoder.clear_buffer(&buffers.cell_type_buffer, 0, None);
+
+        let dispatch = |encoder: &mut wgpu::CommandEncoder, pipeline: &wgpu::ComputePipeline, count: u32| {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            compute_pass.set_pipeline(pipeline);
+            compute_pass.set_bind_group(0, &particles_state.particles_bind_group, &[]);
+            compute_pass.set_bind_group(1, &buffers.bind_group, &[]);
+            compute_pass.set_bind_group(2, uniform_bind_group, &[]);
+            compute_pass.dispatch_workgroups(count.div_ceil(64), 1, 1);
+        };
+
+        dispatch(encoder, &self.particles_to_grid_pipeline, sim.particles_amount);
+        dispatch(encoder, &self.normalize_pipeline, grid.faces());
+        dispatch(encoder, &self.divergence_pipeline, grid.cells());
+        //Iterations go in pairs, so the result ends up in the pressure kept for the next step
+        for _ in 0..sim.projection_iterations.max(1).div_ceil(2) {
+            dispatch(encoder, &self.jacobi_even_pipeline, grid.cells());
+            dispatch(encoder, &self.jacobi_odd_pipeline, grid.cells());
+        }
+        dispatch(encoder, &self.project_pipeline, grid.faces());
+        dispatch(encoder, &self.grid_to_particles_pipeline, sim.particles_amount);
+    }
+
+    //Pressure of the last projection, empty until the grid was used
+    pub fn pressure(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Vec<u8> {
+        match &self.buffers {
+            Some(buffers) => read_buffer(device, queue, &buffers.pressure_buffer),
+            None => Vec::new()
+        }
+    }
+
+    //Start the next projection from a saved pressure of the same grid
+    pub fn restore_pressure(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, sim: &SimulationParameters, particles_state: &ParticlesState, pressure: &[u8]) {
+        if pressure.is_empty() {
+            return;
+        }
+        self.prepare(device, queue, sim, particles_state);
+        if let Some(buffers) = &self.buffers {
+            if buffers.pressure_buffer.size() == pressure.len() as u64 {
+                queue.write_buffer(&buffers.pressure_buffer, 0, pressure);
+            }
+        }
+    }
+
+    //The first guess of the next projection starts from rest
+    pub fn reset(&mut self) {
+        self.buffers = None;
+    }
+}
@@ -1,4 +1,7 @@
-use std::{io::Read, net::TcpListener, sync::Arc};
+use std::sync::Arc;
+use std::time::Instant;
+use clap::Parser;
+use settings::scene::Scene;
 use log::debug;
 use winit::{
     event::*, event_loop::EventLoop, keyboard::{KeyCode, PhysicalKey}, window::WindowBuilder
@@ -12,8 +15,23 @@ mod particle;
 mod geometry;
 mod vertex;
 mod uniforms;
+mod cli;
+mod readback;
+mod surface;
+mod capture;
+mod particle_export;
+mod snapshot;
+mod remote;
+mod telemetry;
+mod overlay;
+mod api;
+mod script;
+mod solver;
+mod flip;
+mod reorder;
+mod counting_sort;
 
-pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
+pub async fn run(args: cli::Args, snapshot: Option<snapshot::Snapshot>, script: Option<script::ScriptState>) -> Result<(), Box<dyn std::error::Error>> {
     let event_loop = EventLoop::new().unwrap();
     let size = SIMULATION_PARAMETERS.lock().unwrap().bounding_box.position2;
     let window = WindowBuilder::new()
@@ -22,13 +40,17 @@ pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
     .build(&event_loop).unwrap();
 
     let window = Arc::new(window);
-    let mut state = state::State::new(window).await;
+    let mut state = state::State::new(Some(window.clone()), window.inner_size(), &args).await;
+    if let Some(snapshot) = &snapshot {
+        state.restore(snapshot);
+    }
+    state.script_state = script;
 
     event_loop.run(move |event, elwt| match event {
         Event::WindowEvent {
             ref event,
             window_id,
-        } if window_id == state.window().id() => if !state.input(event) { 
+        } if window_id == window.id() && !state.input(event) => {
             match event {
                 WindowEvent::CloseRequested
                 | WindowEvent::KeyboardInput {
@@ -54,12 +76,18 @@ pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
                         Err(wgpu::SurfaceError::OutOfMemory) => elwt.exit(),
                         Err(e) => eprintln!("{:?}", e),
                     }
+                    if args.steps.is_some_and(|steps| state.frame() >= steps) {
+                        elwt.exit();
+                    }
                 },
                 _ => {}
             }
         },
         Event::AboutToWait => {
-            state.window().request_redraw();
+            window.request_redraw();
+        }
+        Event::LoopExiting => {
+            state.finish();
         }
         _ => {}
     })?;
@@ -67,46 +95,76 @@ pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn ui_listener() {
-    let res = TcpListener::bind("127.0.0.1:12345");
-
-    if let Err(err) = res {
-        log::error!("Failed to connect the socket: {err}");
-        return;
+pub async fn run_headless(args: cli::Args, snapshot: Option<snapshot::Snapshot>, script: Option<script::ScriptState>) -> Result<(), Box<dyn std::error::Error>> {
+    let size = SIMULATION_PARAMETERS.lock().unwrap().bounding_box.position2;
+    let size = winit::dpi::PhysicalSize::new(size[0] as u32, size[1] as u32);
+    let mut state = state::State::new(None, size, &args).await;
+    if let Some(snapshot) = &snapshot {
+        state.restore(snapshot);
     }
+    state.script_state = script;
 
-    let listener = res.unwrap();
-    std::thread::spawn(move || {
-        let mut buffer = vec![0u8; std::mem::size_of::<settings::SimulationParameters>()];
-        for stream in listener.incoming() {
-            if stream.is_err() {
-                log::error!("Failed to accept connection: {}", stream.err().unwrap());
-                continue;
-            }
-            let mut stream = stream.unwrap();
-
-            let read_res = stream.read_exact(&mut buffer);
-            if read_res.is_err() {
-                log::error!("Failed to read from socket: {}", read_res.err().unwrap());
-                continue;
-            }
+    let (start, first_frame) = (Instant::now(), state.frame());
+    while args.steps.is_none_or(|steps| state.frame() < steps) {
+        state.update();
+        state.render()?;
+    }
+    state.finish();
 
-            let deser_res = bincode::deserialize::<settings::SimulationParameters>(&buffer);
-            if deser_res.is_err() {
-                log::error!("Failed to deserialize settings: {}", deser_res.err().unwrap());
-                continue;
-            }
+    if args.timing {
+        let steps = state.frame() - first_frame;
+        let elapsed = start.elapsed().as_secs_f64();
+        println!("{steps} steps in {elapsed:.2} s, {:.2} ms per step", elapsed * 1000.0 / steps.max(1) as f64);
+    }
 
-            let settings = deser_res.unwrap();
-            let mut sim = SIMULATION_PARAMETERS.lock().unwrap();
-            *sim = settings;
-        }
-    });
+    Ok(())
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
-    ui_listener();
-    pollster::block_on(run())?;
+    let mut args = cli::Args::parse();
+
+    //Buffers and the window are sized from the parameters, so they have to be set before anything is created
+    let scene = args.scene.as_deref().map(Scene::load).transpose()?;
+    if let Some(scene) = &scene {
+        *SIMULATION_PARAMETERS.lock().unwrap() = scene.parameters;
+        *remote::TIMELINE.lock().unwrap() = scene.timeline.clone();
+    }
+    let snapshot = args.snapshot.resume.as_deref().map(snapshot::Snapshot::load).transpose()?;
+    if let Some(snapshot) = &snapshot {
+        *SIMULATION_PARAMETERS.lock().unwrap() = snapshot.parameters;
+        *remote::TIMELINE.lock().unwrap() = snapshot.timeline.clone();
+        args.seed = args.seed.or(Some(snapshot.seed));
+    }
+    {
+        let mut parameters = SIMULATION_PARAMETERS.lock().unwrap();
+        for parameter in &args.overrides {
+            parameter.apply(&mut parameters)?;
+        }
+
+        let validation = parameters.validate();
+        for warning in &validation.warnings {
+            log::warn!("{warning}");
+        }
+        if !validation.is_valid() {
+            return Err(format!("invalid parameters: {}", validation.error_message()).into());
+        }
+    }
+
+    let source = match &args.script {
+        Some(path) => Some(std::fs::read_to_string(path)?),
+        None => scene.and_then(|scene| scene.script)
+    };
+    let script = source.as_deref().map(script::ScriptState::new).transpose()?;
+
+    remote::ui_listener(&args.address);
+    if let Some(port) = args.api {
+        api::listener(port);
+    }
+    if args.headless {
+        pollster::block_on(run_headless(args, snapshot, script))?;
+    } else {
+        pollster::block_on(run(args, snapshot, script))?;
+    }
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,185 @@
+use std::sync::Arc;
+
+use settings::protocol::Telemetry;
+use settings::timeline::Timeline;
+use settings::ui::{command_bar, ParametersPanel, PresetPanel, TelemetryHistory, TimelinePanel};
+use winit::event::{ElementState, KeyEvent, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
+use winit::window::Window;
+
+use crate::remote::{COMMANDS, TIMELINE};
+use crate::uniforms::parameters::SIMULATION_PARAMETERS;
+
+//Settings drawn with egui on top of the simulation, so no separate UI process is needed
+pub struct OverlayState {
+    pub visible: bool,
+    window: Arc<Window>,
+    context: egui::Context,
+    winit_state: egui_winit::State,
+    renderer: egui_wgpu::Renderer,
+    parameters: ParametersPanel,
+    presets: PresetPanel,
+    telemetry: TelemetryHistory,
+    timeline_panel: TimelinePanel,
+    //Edited timeline and what was last handed to the simulation, which other clients can replace too
+    timeline: Timeline,
+    timeline_sent: Timeline
+}
+
+impl OverlayState {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, window: Arc<Window>, visible: bool) -> Self {
+        let context = egui::Context::default();
+        context.set_visuals(egui::Visuals::dark());
+        let winit_state = egui_winit::State::new(
+            context.clone(),
+            egui::ViewportId::ROOT,
+            &*window,
+            Some(window.scale_factor() as f32),
+            Some(device.limits().max_texture_dimension_2d as usize)
+        );
+        let renderer = egui_wgpu::Renderer::new(device, format, None, 1);
+
+        //The timeline of a scene keeps playing
+        let timeline = TIMELINE.lock().unwrap().clone();
+        let mut timeline_panel = TimelinePanel::new();
+        timeline_panel.playing = !timeline.is_empty();
+
+        OverlayState {
+            visible,
+            window,
+            context,
+            winit_state,
+            renderer,
+            parameters: ParametersPanel::new(&SIMULATION_PARAMETERS.lock().unwrap()),
+            presets: PresetPanel::new(),
+            telemetry: TelemetryHistory::new(),
+            timeline_panel,
+            timeline_sent: timeline.clone(),
+            timeline
+        }
+    }
+
+    //F1 toggles the overlay, events used by a visible overlay don't reach the simulation
+    pub fn input(&mut self, event: &WindowEvent) -> bool {
+        if let WindowEvent::KeyboardInput {
+            event:
+                KeyEvent {
+                    state: ElementState::Pressed,
+                    physical_key: PhysicalKey::Code(KeyCode::F1),
+                    ..
+                },
+            ..
+        } = event {
+            self.visible = !self.visible;
+            return true;
+        }
+
+        if !self.visible {
+            return false;
+        }
+        self.winit_state.on_window_event(&self.window, event).consumed
+    }
+
+    pub fn telemetry(&mut self, telemetry: Telemetry) {
+        self.telemetry.push(telemetry);
+    }
+
+    //Draws over what is already in the view
+    pub fn draw(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView, size: [u32; 2], paused: bool) {
+        if !self.visible {
+            return;
+        }
+
+        let mut parameters = *SIMULATION_PARAMETERS.lock().unwrap();
+        let edited = parameters;
+        let time = self.telemetry.latest().map_or(0.0, |telemetry| telemetry.time);
+
+        let input = self.winit_state.take_egui_input(&self.window);
+        let output = self.context.run(input, |ctx| {
+            egui::Window::new("Settings")
+            .default_width(360.0)
+            .default_height(600.0)
+            .vscroll(true)
+            .show(ctx, |ui| {
+                if let Some(command) = command_bar(ui, paused) {
+                    COMMANDS.lock().unwrap().push_back(command);
+                }
+                ui.separator();
+
+                egui::CollapsingHeader::new(egui::RichText::new("Presets").strong())
+                .show(ui, |ui| {
+                    self.presets.ui(ui, &mut parameters, &mut self.timeline);
+                });
+                ui.separator();
+
+                self.parameters.ui(ui, &mut parameters);
+
+                ui.separator();
+                egui::CollapsingHeader::new(egui::RichText::new("Timeline").strong())
+                .show(ui, |ui| {
+                    self.timeline_panel.ui(ui, &mut self.timeline, &parameters, time);
+                });
+
+                ui.separator();
+                egui::CollapsingHeader::new(egui::RichText::new("Statistics").strong())
+                .show(ui, |ui| {
+                    self.telemetry.ui(ui);
+                });
+            });
+        });
+        self.winit_state.handle_platform_output(&self.window, output.platform_output);
+
+        let playback = self.timeline_panel.playback(&self.timeline);
+        if playback != self.timeline_sent {
+            *TIMELINE.lock().unwrap() = playback.clone();
+            self.timeline_sent = playback;
+        }
+
+        //Buffers of a running simulation can't be resized, invalid edits are dropped
+        if parameters != edited {
+            let validation = parameters.validate();
+            if validation.is_valid() {
+                let mut global = SIMULATION_PARAMETERS.lock().unwrap();
+                parameters.particles_amount = global.particles_amount;
+                *global = parameters;
+            } else {
+                log::error!("Ignoring invalid parameters: {}", validation.error_message());
+            }
+        }
+
+        let screen = egui_wgpu::ScreenDescriptor {
+            size_in_pixels: size,
+            pixels_per_point: output.pixels_per_point
+        };
+        let paint_jobs = self.context.tessellate(output.shapes, output.pixels_per_point);
+        for (id, delta) in &output.textures_delta.set {
+            self.renderer.update_texture(device, queue, *id, delta);
+        }
+        let buffers = self.renderer.update_buffers(device, queue, encoder, &paint_jobs, &screen);
+        if !buffers.is_empty() {
+            queue.submit(buffers);
+        }
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Overlay Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            self.renderer.render(&mut render_pass, &paint_jobs, &screen);
+        }
+
+        for id in &output.textures_delta.free {
+            self.renderer.free_texture(id);
+        }
+    }
+}
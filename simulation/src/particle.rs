@@ -4,8 +4,15 @@ use cgmath::{Vector3, Vector4};
 use wgpu::util::DeviceExt;
 
 pub const SEGMENTS: u32 = 32;
+//Springs a viscoelastic particle can hold, MAX_SPRINGS in simulation.wgsl
+pub const MAX_SPRINGS: usize = 16;
 
+use settings::neighbour::{NeighbourSearch, NeighbourSort};
+use settings::SimulationParameters;
+
+use crate::counting_sort::CountingSortState;
 use crate::geometry;
+use crate::readback::read_buffer;
 use crate::uniforms::parameters::SIMULATION_PARAMETERS;
 
 
@@ -25,9 +32,11 @@ impl Particle {
         }
     }
 
-    pub fn into_raw(self) -> ParticleRaw {
+    //The id stays with the particle when its data is moved into cell order
+    pub fn into_raw(self, id: u32) -> ParticleRaw {
         ParticleRaw {
             position: self.position.into(),
+            id,
             velocity: self.velocity.into(),
             color: self.color.into(),
             ..Default::default()
@@ -42,11 +51,11 @@ impl Particle {
 #[repr(C)]
 #[derive(Copy, Clone, Default, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct ParticleRaw {
-    position: [f32; 3],
-    _padding: f32,
-    velocity: [f32; 3],
+    pub position: [f32; 3],
+    pub id: u32,
+    pub velocity: [f32; 3],
     _padding2: f32,
-    color: [f32; 4]
+    pub color: [f32; 4]
 }
 
 impl ParticleRaw {
@@ -85,6 +94,12 @@ pub struct ParticlesState {
     pub predicted_buffer: wgpu::Buffer,
     pub surface_normals_buffer: wgpu::Buffer,
     pub vorticity_buffer: wgpu::Buffer,
+    pub solver_scalar_buffer: wgpu::Buffer,
+    pub solver_field_buffer: wgpu::Buffer,
+    pub viscosity_field_buffer: wgpu::Buffer,
+    pub springs_buffer: wgpu::Buffer,
+    pub stress_buffer: wgpu::Buffer,
+    pub affine_buffer: wgpu::Buffer,
 
     pub particles_bind_group: wgpu::BindGroup,
     pub fields_bind_group: wgpu::BindGroup,
@@ -117,33 +132,33 @@ impl ParticlesState {
             particles.push(Particle::new(position, velocity, color));
         }
 
-        let particles_raw: Vec<_> = particles.iter().map(|p| p.into_raw()).collect();
+        let particles_raw: Vec<_> = particles.iter().zip(0..).map(|(p, id)| p.into_raw(id)).collect();
         
         let particles_buffer = device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
                 label: Some("Particles"),
                 contents: bytemuck::cast_slice(&particles_raw),
-                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST
             }
         );
 
         let density_field_buffer = device.create_buffer(&wgpu::BufferDescriptor{
             label: Some("Density buffer"),
             size: (std::mem::size_of::<f32>() * particles.len()) as u64,
-            usage: wgpu::BufferUsages::STORAGE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false
         });
         let near_density_field_buffer = device.create_buffer(&wgpu::BufferDescriptor{
             label: Some("Near Density buffer"),
             size: (std::mem::size_of::<f32>() * particles.len()) as u64,
-            usage: wgpu::BufferUsages::STORAGE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false
         });
         let predicted_buffer = device.create_buffer(
             &wgpu::BufferDescriptor {
                 label: Some("Predicted buffer"),
                 size: (std::mem::size_of::<[f32; 8]>() * particles.len()) as u64,
-                usage: wgpu::BufferUsages::STORAGE,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
                 mapped_at_creation: false
             }
         );
@@ -151,7 +166,7 @@ impl ParticlesState {
             &wgpu::BufferDescriptor {
                 label: Some("Surface normal buffer"),
                 size: (std::mem::size_of::<[f32; 4]>() * particles.len()) as u64,
-                usage: wgpu::BufferUsages::STORAGE,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
                 mapped_at_creation: false
             }
         );
@@ -159,10 +174,54 @@ impl ParticlesState {
             &wgpu::BufferDescriptor {
                 label: Some("Vorticity buffer"),
                 size: (std::mem::size_of::<[f32; 4]>() * particles.len()) as u64,
-                usage: wgpu::BufferUsages::STORAGE,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
                 mapped_at_creation: false
             }
-        );     
+        );
+        //Scratch space of the iterative pressure solvers, rewritten every step
+        let solver_scalar_buffer = device.create_buffer(&wgpu::BufferDescriptor{
+            label: Some("Solver scalar buffer"),
+            size: (std::mem::size_of::<f32>() * particles.len()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        let solver_field_buffer = device.create_buffer(&wgpu::BufferDescriptor{
+            label: Some("Solver field buffer"),
+            size: (std::mem::size_of::<[f32; 4]>() * particles.len()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+
+        //Apparent viscosity of non-Newtonian fluids
+        let viscosity_field_buffer = device.create_buffer(&wgpu::BufferDescriptor{
+            label: Some("Viscosity buffer"),
+            size: (std::mem::size_of::<f32>() * particles.len()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+        //Viscoelastic springs, a fixed number of slots per particle
+        let springs_buffer = device.create_buffer(&wgpu::BufferDescriptor{
+            label: Some("Springs buffer"),
+            size: (std::mem::size_of::<[u32; 2]>() * MAX_SPRINGS * particles.len()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+
+        //Stress of granular particles
+        let stress_buffer = device.create_buffer(&wgpu::BufferDescriptor{
+            label: Some("Stress buffer"),
+            size: (std::mem::size_of::<[f32; 4]>() * particles.len()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+
+        //Velocity gradient of APIC particles, bound by the grid solver
+        let affine_buffer = device.create_buffer(&wgpu::BufferDescriptor{
+            label: Some("Affine buffer"),
+            size: (std::mem::size_of::<[f32; 4]>() * particles.len()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
 
         let particles_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries: &[
@@ -176,6 +235,28 @@ impl ParticlesState {
                         min_binding_size: None,
                     },
                     count: None,
+                },
+                //Springs
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::all(),
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                //Stress
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::all(),
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
                 }
             ],
             label: Some("Particles bind group layout")
@@ -238,6 +319,39 @@ impl ParticlesState {
                     },
                     count: None,
                 },
+                //Solver scalars
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::all(),
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                //Solver vectors
+                wgpu::BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: wgpu::ShaderStages::all(),
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                //Viscosity
+                wgpu::BindGroupLayoutEntry {
+                    binding: 7,
+                    visibility: wgpu::ShaderStages::all(),
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
             label: Some("Fields bind group layout")
         });
@@ -250,6 +364,14 @@ impl ParticlesState {
                     binding: 0,
                     resource: particles_buffer.as_entire_binding(),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: springs_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: stress_buffer.as_entire_binding(),
+                },
                 ]
             });
 
@@ -277,6 +399,18 @@ impl ParticlesState {
                     binding: 4,
                     resource: vorticity_buffer.as_entire_binding()
                 },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: solver_scalar_buffer.as_entire_binding()
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: solver_field_buffer.as_entire_binding()
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: viscosity_field_buffer.as_entire_binding()
+                },
             ]
         });
 
@@ -288,20 +422,71 @@ impl ParticlesState {
             predicted_buffer,
             surface_normals_buffer,
             vorticity_buffer,
+            solver_scalar_buffer,
+            solver_field_buffer,
+            viscosity_field_buffer,
+            springs_buffer,
+            stress_buffer,
+            affine_buffer,
             particles_bind_group,
             fields_bind_group,
             particles_bind_group_layout,
             fields_bind_group_layout
         }
     }
+
+    //Put the particles back on the initial grid and clear the fields
+    pub fn reset(&self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let particles_raw: Vec<_> = self.particles.iter().zip(0..).map(|(p, id)| p.into_raw(id)).collect();
+        queue.write_buffer(&self.particles_buffer, 0, bytemuck::cast_slice(&particles_raw));
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Reset Encoder"),
+        });
+        for buffer in [&self.density_field_buffer, &self.near_density_field_buffer, &self.predicted_buffer, &self.surface_normals_buffer, &self.vorticity_buffer, &self.solver_scalar_buffer, &self.solver_field_buffer, &self.viscosity_field_buffer, &self.springs_buffer, &self.stress_buffer, &self.affine_buffer] {
+            encoder.clear_buffer(buffer, 0, None);
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+    }
 }
 
 
+//Cell range of the dense neighbour search, NeighbourGrid in simulation.wgsl and sort_prep.wgsl
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+struct NeighbourGridUniform {
+    origin: [i32; 2],
+    size: [u32; 2]
+}
+
+impl NeighbourGridUniform {
+    fn new(sim: &SimulationParameters) -> Self {
+        let cell_size = sim.grid_size * sim.pixels_per_metre;
+        let position1 = sim.bounding_box.position1;
+        NeighbourGridUniform {
+            origin: [(position1[0] / cell_size).floor() as i32, (position1[1] / cell_size).floor() as i32],
+            size: sim.dense_grid_size()
+        }
+    }
+}
+
+//Statistics of the last neighbour search
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NeighbourStats {
+    pub occupied_cells: u32,
+    pub hash_collisions: u32,
+    pub max_cell_particles: u32
+}
+
 pub struct NeighbourSearchGridState {
     //All buffers are Vec<u32>
     pub key_cell_hash_buffer: wgpu::Buffer,
     pub value_particle_id_buffer: wgpu::Buffer,
+    //One bucket per hash or per cell of the dense grid
     pub cell_start_buffer: wgpu::Buffer,
+    pub cell_end_buffer: wgpu::Buffer,
+    pub stats_buffer: wgpu::Buffer,
+    grid_buffer: wgpu::Buffer,
     pub bind_group: wgpu::BindGroup,
     pub bind_group_layout: wgpu::BindGroupLayout
 }
@@ -326,11 +511,22 @@ impl NeighbourSearchGridState {
             mapped_at_creation: false  
         });
 
-        let cell_start_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Key cell hash buffer"),
-            size: (std::mem::size_of::<u32>() * length as usize) as u64,
-            usage: wgpu::BufferUsages::STORAGE |  wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false  
+        //Sized for the parameters before every step
+        let cell_start_buffer = Self::create_table_buffer(device, "Cell start buffer", 1);
+        let cell_end_buffer = Self::create_table_buffer(device, "Cell end buffer", 1);
+
+        let stats_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Neighbour search statistics buffer"),
+            size: (std::mem::size_of::<u32>() * 4) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+
+        let grid_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Neighbour grid uniform"),
+            size: std::mem::size_of::<NeighbourGridUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
         });
 
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor{
@@ -366,86 +562,192 @@ impl NeighbourSearchGridState {
                     },
                     count: None
                 },
-            ]
-        });
-
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor { 
-            label: Some("NeighbourSearchGridState bind group"), 
-            layout: &bind_group_layout, 
-            entries: &[
-                wgpu::BindGroupEntry{
-                    binding: 0,
-                    resource: key_cell_hash_buffer.as_entire_binding()
+                //Statistics
+                wgpu::BindGroupLayoutEntry{
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer { 
+                        ty: wgpu::BufferBindingType::Storage { read_only: false }, 
+                        has_dynamic_offset: false, 
+                        min_binding_size: None 
+                    },
+                    count: None
                 },
-                wgpu::BindGroupEntry{
-                    binding: 1,
-                    resource: value_particle_id_buffer.as_entire_binding()
+                //Dense grid
+                wgpu::BindGroupLayoutEntry{
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer { 
+                        ty: wgpu::BufferBindingType::Uniform, 
+                        has_dynamic_offset: false, 
+                        min_binding_size: None 
+                    },
+                    count: None
                 },
-                wgpu::BindGroupEntry{
-                    binding: 2,
-                    resource: cell_start_buffer.as_entire_binding()
+                //Cell end
+                wgpu::BindGroupLayoutEntry{
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer { 
+                        ty: wgpu::BufferBindingType::Storage { read_only: false }, 
+                        has_dynamic_offset: false, 
+                        min_binding_size: None 
+                    },
+                    count: None
                 },
-            ] 
+            ]
         });
 
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, [&key_cell_hash_buffer, &value_particle_id_buffer, &cell_start_buffer, &stats_buffer, &grid_buffer, &cell_end_buffer]);
+
         NeighbourSearchGridState {
             key_cell_hash_buffer, 
             value_particle_id_buffer,
             cell_start_buffer,
+            cell_end_buffer,
+            stats_buffer,
+            grid_buffer,
             bind_group,
             bind_group_layout
         }
     }
+
+    fn create_table_buffer(device: &wgpu::Device, label: &str, length: u32) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: (std::mem::size_of::<u32>() * length as usize) as u64,
+            usage: wgpu::BufferUsages::STORAGE |  wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false  
+        })
+    }
+
+    //Buffers in the order of their bindings
+    fn create_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, buffers: [&wgpu::Buffer; 6]) -> wgpu::BindGroup {
+        let entries: Vec<_> = buffers.iter().zip(0..).map(|(buffer, binding)| wgpu::BindGroupEntry {
+            binding,
+            resource: buffer.as_entire_binding()
+        }).collect();
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor { 
+            label: Some("NeighbourSearchGridState bind group"), 
+            layout, 
+            entries: &entries
+        })
+    }
+
+    //Buckets the parameters need, the hash table or one per cell of the bounding box
+    pub fn table_size(sim: &SimulationParameters) -> u32 {
+        match sim.neighbour_search() {
+            NeighbourSearch::Hashed => sim.hash_table_size,
+            NeighbourSearch::Dense => {
+                let [x, y] = sim.dense_grid_size();
+                x * y
+            }
+        }
+    }
+
+    //Resize the table for the parameters of the step and upload the dense grid
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, sim: &SimulationParameters) {
+        queue.write_buffer(&self.grid_buffer, 0, bytemuck::cast_slice(&[NeighbourGridUniform::new(sim)]));
+
+        let length = Self::table_size(sim).max(1);
+        if self.cell_start_buffer.size() == (std::mem::size_of::<u32>() * length as usize) as u64 {
+            return;
+        }
+        self.cell_start_buffer = Self::create_table_buffer(device, "Cell start buffer", length);
+        self.cell_end_buffer = Self::create_table_buffer(device, "Cell end buffer", length);
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, [&self.key_cell_hash_buffer, &self.value_particle_id_buffer, &self.cell_start_buffer, &self.stats_buffer, &self.grid_buffer, &self.cell_end_buffer]);
+    }
+
+    pub fn stats(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> NeighbourStats {
+        let stats: Vec<u32> = read_buffer(device, queue, &self.stats_buffer);
+        NeighbourStats {
+            occupied_cells: stats[0],
+            hash_collisions: stats[1],
+            max_cell_particles: stats[2]
+        }
+    }
+}
+
+//wgpu_sort's radix sort with buffers for all particles
+struct RadixSort {
+    sorter: wgpu_sort::GPUSorter,
+    sort_buffers: wgpu_sort::SortBuffers
 }
 
 pub struct NeighbourSearchSortState {
     pub grid_state: NeighbourSearchGridState,
-    pub sorter: wgpu_sort::GPUSorter,
-    pub sort_buffers: wgpu_sort::SortBuffers
+    //Detected on the adapter when not given
+    subgroup_size: Option<u32>,
+    //Created when it's first used, detecting the subgroup size takes a while
+    radix_sort: Option<RadixSort>,
+    counting_sort: CountingSortState
 }
 
 impl NeighbourSearchSortState {
-    pub fn new(device: &wgpu::Device, subgroup_size: u32) -> Self {
+    pub fn new(device: &wgpu::Device, subgroup_size: Option<u32>) -> Self {
         let sim = SIMULATION_PARAMETERS.lock().unwrap();
         let grid_state = NeighbourSearchGridState::new(device, sim.particles_amount);
-        let sorter = wgpu_sort::GPUSorter::new(device, subgroup_size);
-        let sort_buffers = sorter.create_sort_buffers(device, NonZeroU32::new(sim.particles_amount).unwrap());
+        let counting_sort = CountingSortState::new(device, &grid_state);
 
         NeighbourSearchSortState {
             grid_state,
-            sorter,
-            sort_buffers
+            subgroup_size,
+            radix_sort: None,
+            counting_sort
+        }
+    }
+
+    //Sort the particles by the keys of calcHash with the sort the parameters pick
+    pub fn sort(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, sim: &SimulationParameters) {
+        match sim.neighbour_sort() {
+            NeighbourSort::Radix => self.radix_sort(device, queue, encoder),
+            NeighbourSort::Counting => self.counting_sort.sort(device, encoder, &self.grid_state)
         }
     }
 
-    pub fn sort(&self, encoder: &mut wgpu::CommandEncoder, queue: &wgpu::Queue) {
+    fn radix_sort(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder) {
+        let length = (self.grid_state.key_cell_hash_buffer.size() / std::mem::size_of::<u32>() as u64) as u32;
+        let RadixSort { sorter, sort_buffers } = self.radix_sort.get_or_insert_with(|| {
+            let subgroup_size = match self.subgroup_size {
+                Some(subgroup_size) => subgroup_size,
+                None => pollster::block_on(wgpu_sort::utils::guess_workgroup_size(device, queue)).unwrap()
+            };
+            let sorter = wgpu_sort::GPUSorter::new(device, subgroup_size);
+            let sort_buffers = sorter.create_sort_buffers(device, NonZeroU32::new(length).unwrap());
+            RadixSort {
+                sorter,
+                sort_buffers
+            }
+        });
+
         //Copy keys
         encoder.copy_buffer_to_buffer(
             &self.grid_state.key_cell_hash_buffer, 
             0, 
-            self.sort_buffers.keys(), 
+            sort_buffers.keys(), 
             0,
             self.grid_state.key_cell_hash_buffer.size());
         //Copy values
         encoder.copy_buffer_to_buffer(
             &self.grid_state.value_particle_id_buffer, 
             0, 
-            self.sort_buffers.values(), 
+            sort_buffers.values(), 
             0,
             self.grid_state.value_particle_id_buffer.size());
 
-        self.sorter.sort(encoder, queue, &self.sort_buffers, None);
+        sorter.sort(encoder, queue, sort_buffers, None);
 
         //Copy keys back
         encoder.copy_buffer_to_buffer(
-            self.sort_buffers.keys(), 
+            sort_buffers.keys(), 
             0, 
             &self.grid_state.key_cell_hash_buffer, 
             0,
             self.grid_state.key_cell_hash_buffer.size());
         //Copy values back
         encoder.copy_buffer_to_buffer(
-            self.sort_buffers.values(), 
+            sort_buffers.values(), 
             0, 
             &self.grid_state.value_particle_id_buffer, 
             0,
@@ -0,0 +1,308 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::cli::ExportArgs;
+use crate::particle::{ParticleRaw, ParticlesState};
+use crate::readback::read_buffer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    //VTK XML unstructured grid
+    Vtu,
+    //Legacy VTK polydata
+    Vtk,
+    //Binary little endian PLY
+    Ply,
+    Csv
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Vtu => "vtu",
+            ExportFormat::Vtk => "vtk",
+            ExportFormat::Ply => "ply",
+            ExportFormat::Csv => "csv"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Attribute {
+    Velocity,
+    Density,
+    NearDensity,
+    SurfaceNormal,
+    Vorticity
+}
+
+impl Attribute {
+    fn name(self) -> &'static str {
+        match self {
+            Attribute::Velocity => "velocity",
+            Attribute::Density => "density",
+            Attribute::NearDensity => "near_density",
+            Attribute::SurfaceNormal => "surface_normal",
+            Attribute::Vorticity => "vorticity"
+        }
+    }
+
+    fn components(self) -> usize {
+        match self {
+            Attribute::Density | Attribute::NearDensity => 1,
+            Attribute::Velocity | Attribute::SurfaceNormal | Attribute::Vorticity => 3
+        }
+    }
+}
+
+//Particle state copied from the GPU
+pub struct ParticleData {
+    pub positions: Vec<[f32; 3]>,
+    //Attribute values in the same order as the requested attributes, flattened per particle
+    pub attributes: Vec<(Attribute, Vec<f32>)>
+}
+
+impl ParticleData {
+    pub fn read(device: &wgpu::Device, queue: &wgpu::Queue, particles_state: &ParticlesState, attributes: &[Attribute]) -> Self {
+        let particles: Vec<ParticleRaw> = read_buffer(device, queue, &particles_state.particles_buffer);
+
+        //Reordered particles are written by their id, so rows stay the same particle across frames
+        let mut order = vec![0; particles.len()];
+        for (i, particle) in particles.iter().enumerate() {
+            order[particle.id as usize] = i;
+        }
+        let positions = order.iter().map(|&i| particles[i].position).collect();
+
+        //vec3 fields are stored with a 16 byte stride
+        let vectors = |buffer: &wgpu::Buffer| -> Vec<f32> {
+            read_buffer::<[f32; 4]>(device, queue, buffer).iter().flat_map(|v| [v[0], v[1], v[2]]).collect()
+        };
+
+        let attributes = attributes.iter().map(|&attribute| {
+            let values: Vec<f32> = match attribute {
+                Attribute::Velocity => particles.iter().flat_map(|p| p.velocity).collect(),
+                Attribute::Density => read_buffer(device, queue, &particles_state.density_field_buffer),
+                Attribute::NearDensity => read_buffer(device, queue, &particles_state.near_density_field_buffer),
+                Attribute::SurfaceNormal => vectors(&particles_state.surface_normals_buffer),
+                Attribute::Vorticity => vectors(&particles_state.vorticity_buffer)
+            };
+            let n = attribute.components();
+            (attribute, order.iter().flat_map(|&i| values[i * n..(i + 1) * n].iter().copied()).collect())
+        }).collect();
+
+        ParticleData {
+            positions,
+            attributes
+        }
+    }
+
+    fn value(&self, attribute: usize, particle: usize) -> &[f32] {
+        let (attr, values) = &self.attributes[attribute];
+        let n = attr.components();
+        &values[particle * n..(particle + 1) * n]
+    }
+
+    pub fn write(&self, path: &Path, format: ExportFormat) -> std::io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        match format {
+            ExportFormat::Vtu => self.write_vtu(&mut file)?,
+            ExportFormat::Vtk => self.write_vtk(&mut file)?,
+            ExportFormat::Ply => self.write_ply(&mut file)?,
+            ExportFormat::Csv => self.write_csv(&mut file)?
+        }
+        file.flush()
+    }
+
+    fn write_csv(&self, file: &mut impl Write) -> std::io::Result<()> {
+        let mut header = vec!["x".to_string(), "y".to_string(), "z".to_string()];
+        for (attribute, _) in &self.attributes {
+            match attribute.components() {
+                1 => header.push(attribute.name().to_string()),
+                _ => header.extend(["x", "y", "z"].map(|c| format!("{}_{c}", attribute.name())))
+            }
+        }
+        writeln!(file, "{}", header.join(","))?;
+
+        for (i, position) in self.positions.iter().enumerate() {
+            write!(file, "{},{},{}", position[0], position[1], position[2])?;
+            for a in 0..self.attributes.len() {
+                for v in self.value(a, i) {
+                    write!(file, ",{v}")?;
+                }
+            }
+            writeln!(file)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_ply(&self, file: &mut impl Write) -> std::io::Result<()> {
+        writeln!(file, "ply")?;
+        writeln!(file, "format binary_little_endian 1.0")?;
+        writeln!(file, "element vertex {}", self.positions.len())?;
+        for c in ["x", "y", "z"] {
+            writeln!(file, "property float {c}")?;
+        }
+        for (attribute, _) in &self.attributes {
+            match attribute.components() {
+                1 => writeln!(file, "property float {}", attribute.name())?,
+                _ => for c in ["x", "y", "z"] {
+                    writeln!(file, "property float {}_{c}", attribute.name())?;
+                }
+            }
+        }
+        writeln!(file, "end_header")?;
+
+        for (i, position) in self.positions.iter().enumerate() {
+            for v in position {
+                file.write_all(&v.to_le_bytes())?;
+            }
+            for a in 0..self.attributes.len() {
+                for v in self.value(a, i) {
+                    file.write_all(&v.to_le_bytes())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    //Legacy format stores binary data big endian
+    fn write_vtk(&self, file: &mut impl Write) -> std::io::Result<()> {
+        let n = self.positions.len();
+
+        writeln!(file, "# vtk DataFile Version 3.0")?;
+        writeln!(file, "Water simulation particles")?;
+        writeln!(file, "BINARY")?;
+        writeln!(file, "DATASET POLYDATA")?;
+
+        writeln!(file, "POINTS {n} float")?;
+        for v in self.positions.iter().flatten() {
+            file.write_all(&v.to_be_bytes())?;
+        }
+        writeln!(file)?;
+
+        writeln!(file, "VERTICES {n} {}", 2 * n)?;
+        for i in 0..n as i32 {
+            file.write_all(&1i32.to_be_bytes())?;
+            file.write_all(&i.to_be_bytes())?;
+        }
+        writeln!(file)?;
+
+        if self.attributes.is_empty() {
+            return Ok(());
+        }
+
+        writeln!(file, "POINT_DATA {n}")?;
+        for (attribute, values) in &self.attributes {
+            match attribute.components() {
+                1 => {
+                    writeln!(file, "SCALARS {} float 1", attribute.name())?;
+                    writeln!(file, "LOOKUP_TABLE default")?;
+                },
+                _ => writeln!(file, "VECTORS {} float", attribute.name())?
+            }
+            for v in values {
+                file.write_all(&v.to_be_bytes())?;
+            }
+            writeln!(file)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_vtu(&self, file: &mut impl Write) -> std::io::Result<()> {
+        let n = self.positions.len();
+
+        writeln!(file, r#"<?xml version="1.0"?>"#)?;
+        writeln!(file, r#"<VTKFile type="UnstructuredGrid" version="0.1" byte_order="LittleEndian">"#)?;
+        writeln!(file, "<UnstructuredGrid>")?;
+        writeln!(file, r#"<Piece NumberOfPoints="{n}" NumberOfCells="{n}">"#)?;
+
+        writeln!(file, "<PointData>")?;
+        for (attribute, values) in &self.attributes {
+            writeln!(file, r#"<DataArray type="Float32" Name="{}" NumberOfComponents="{}" format="ascii">"#, attribute.name(), attribute.components())?;
+            write_values(file, values.iter())?;
+            writeln!(file, "</DataArray>")?;
+        }
+        writeln!(file, "</PointData>")?;
+
+        writeln!(file, "<Points>")?;
+        writeln!(file, r#"<DataArray type="Float32" NumberOfComponents="3" format="ascii">"#)?;
+        write_values(file, self.positions.iter().flatten())?;
+        writeln!(file, "</DataArray>")?;
+        writeln!(file, "</Points>")?;
+
+        //Every particle is a VTK_VERTEX cell
+        writeln!(file, "<Cells>")?;
+        writeln!(file, r#"<DataArray type="Int32" Name="connectivity" format="ascii">"#)?;
+        write_values(file, 0..n)?;
+        writeln!(file, "</DataArray>")?;
+        writeln!(file, r#"<DataArray type="Int32" Name="offsets" format="ascii">"#)?;
+        write_values(file, 1..=n)?;
+        writeln!(file, "</DataArray>")?;
+        writeln!(file, r#"<DataArray type="UInt8" Name="types" format="ascii">"#)?;
+        write_values(file, std::iter::repeat_n(1, n))?;
+        writeln!(file, "</DataArray>")?;
+        writeln!(file, "</Cells>")?;
+
+        writeln!(file, "</Piece>")?;
+        writeln!(file, "</UnstructuredGrid>")?;
+        writeln!(file, "</VTKFile>")
+    }
+}
+
+fn write_values<T: std::fmt::Display>(file: &mut impl Write, values: impl Iterator<Item = T>) -> std::io::Result<()> {
+    for (i, v) in values.enumerate() {
+        if i > 0 {
+            write!(file, "{}", if i % 12 == 0 { "\n" } else { " " })?;
+        }
+        write!(file, "{v}")?;
+    }
+    writeln!(file)
+}
+
+pub struct ExportState {
+    dir: Option<PathBuf>,
+    format: ExportFormat,
+    every: u32,
+    attributes: Vec<Attribute>
+}
+
+impl ExportState {
+    pub fn new(args: &ExportArgs) -> Self {
+        if let Some(dir) = &args.export {
+            if let Err(err) = std::fs::create_dir_all(dir) {
+                log::error!("Failed to create export directory {}: {err}", dir.display());
+            }
+        }
+
+        let mut attributes = vec![];
+        for &attribute in &args.export_attributes {
+            if !attributes.contains(&attribute) {
+                attributes.push(attribute);
+            }
+        }
+
+        ExportState {
+            dir: args.export.clone(),
+            format: args.export_format,
+            every: args.export_every.max(1),
+            attributes
+        }
+    }
+
+    pub fn update(&self, device: &wgpu::Device, queue: &wgpu::Queue, particles_state: &ParticlesState, frame: u64) {
+        let Some(dir) = &self.dir else { return; };
+        if !frame.is_multiple_of(self.every as u64) {
+            return;
+        }
+
+        let data = ParticleData::read(device, queue, particles_state, &self.attributes);
+        let path = dir.join(format!("particles_{frame:06}.{}", self.format.extension()));
+        if let Err(err) = data.write(&path, self.format) {
+            log::error!("Failed to write {}: {err}", path.display());
+        }
+    }
+}
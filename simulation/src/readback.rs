@@ -0,0 +1,28 @@
+//Blocking copy of a GPU buffer into host memory. The buffer needs COPY_SRC usage
+pub fn read_buffer<T: bytemuck::Pod>(device: &wgpu::Device, queue: &wgpu::Queue, buffer: &wgpu::Buffer) -> Vec<T> {
+    let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback staging buffer"),
+        size: buffer.size(),
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Readback encoder"),
+    });
+    encoder.copy_buffer_to_buffer(buffer, 0, &staging_buffer, 0, buffer.size());
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let slice = staging_buffer.slice(..);
+    slice.map_async(wgpu::MapMode::Read, |res| {
+        if let Err(err) = res {
+            log::error!("Failed to map readback buffer: {err}");
+        }
+    });
+    device.poll(wgpu::Maintain::Wait);
+
+    let data = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+    staging_buffer.unmap();
+
+    data
+}
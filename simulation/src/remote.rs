@@ -0,0 +1,138 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+
+use once_cell::sync::Lazy;
+use settings::protocol::{self, Command, Message, ProtocolError};
+use settings::timeline::Timeline;
+use settings::transport::{Endpoint, Listener, Stream};
+use settings::SimulationParameters;
+
+use crate::uniforms::parameters::SIMULATION_PARAMETERS;
+
+//Commands received from the settings UI, applied by the simulation at the start of the next step
+pub static COMMANDS: Lazy<Mutex<VecDeque<Command>>> = Lazy::new(|| Mutex::new(VecDeque::new()));
+
+//Keyframed parameters, applied by the simulation before every step
+pub static TIMELINE: Lazy<Mutex<Timeline>> = Lazy::new(|| Mutex::new(Timeline::default()));
+
+//Queues of the connected clients, every client has a thread writing them out so a slow one can't hold up the simulation
+static CLIENTS: Lazy<Mutex<Vec<Arc<Sender<Message>>>>> = Lazy::new(|| Mutex::new(vec![]));
+
+//Parameters the clients were last told about
+static PUBLISHED: Lazy<Mutex<Option<SimulationParameters>>> = Lazy::new(|| Mutex::new(None));
+
+pub fn has_clients() -> bool {
+    !CLIENTS.lock().unwrap().is_empty()
+}
+
+//Queue a message for every connected client, dropping the ones whose connection closed
+pub fn publish(message: &Message) {
+    CLIENTS.lock().unwrap().retain(|client| client.send(message.clone()).is_ok());
+}
+
+//Changes from the timeline, scripts, the overlay and the HTTP API all end up here once per step
+pub fn publish_parameters(parameters: &SimulationParameters) {
+    let mut published = PUBLISHED.lock().unwrap();
+    if published.as_ref() != Some(parameters) {
+        *published = Some(*parameters);
+        publish(&Message::Parameters(Box::new(*parameters)));
+    }
+}
+
+pub fn ui_listener(endpoint: &Endpoint) {
+    let listener = match Listener::bind(endpoint) {
+        Ok(listener) => listener,
+        Err(err) => {
+            log::error!("Failed to listen on {endpoint}: {err}");
+            return;
+        }
+    };
+    log::info!("Listening for the settings UI on {endpoint}");
+
+    std::thread::spawn(move || {
+        loop {
+            match listener.accept() {
+                Ok(stream) => {
+                    std::thread::spawn(move || {
+                        if let Err(err) = serve(stream) {
+                            log::error!("Settings connection closed: {err}");
+                        }
+                    });
+                },
+                Err(err) => log::error!("Failed to accept connection: {err}")
+            }
+        }
+    });
+}
+
+//Serve one settings client until it disconnects
+fn serve(mut stream: Stream) -> Result<(), ProtocolError> {
+    protocol::handshake(&mut stream, true)?;
+    let mut writer = stream.try_clone()?;
+    let (sender, receiver) = channel();
+    let sender = Arc::new(sender);
+
+    //Ends once the client is gone or every sender was dropped
+    std::thread::spawn(move || {
+        for message in receiver {
+            if let Err(err) = protocol::write_message(&mut writer, &message) {
+                log::error!("Failed to write to the settings UI: {err}");
+                break;
+            }
+        }
+    });
+
+    //A restarted UI picks up the parameters the simulation is running with
+    let parameters = *SIMULATION_PARAMETERS.lock().unwrap();
+    let _ = sender.send(Message::Parameters(Box::new(parameters)));
+
+    CLIENTS.lock().unwrap().push(sender.clone());
+    let res = serve_messages(&mut stream, &sender);
+    CLIENTS.lock().unwrap().retain(|client| !Arc::ptr_eq(client, &sender));
+
+    res
+}
+
+fn serve_messages(stream: &mut Stream, writer: &Arc<Sender<Message>>) -> Result<(), ProtocolError> {
+    loop {
+        let message = match protocol::read_message(stream) {
+            Ok(message) => message,
+            Err(ProtocolError::Io(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(err)
+        };
+
+        let reply = match message {
+            Message::Parameters(parameters) => {
+                let mut sim = SIMULATION_PARAMETERS.lock().unwrap();
+                if parameters.particles_amount != sim.particles_amount {
+                    Message::Error("the particle count can't be changed while running".to_string())
+                } else {
+                    let validation = parameters.validate();
+                    if validation.is_valid() {
+                        *sim = *parameters;
+                        //Echoing them back would undo edits the client made since, the other clients are told right away
+                        *PUBLISHED.lock().unwrap() = Some(*parameters);
+                        let update = Message::Parameters(parameters);
+                        CLIENTS.lock().unwrap().retain(|client| Arc::ptr_eq(client, writer) || client.send(update.clone()).is_ok());
+                        Message::Ack
+                    } else {
+                        Message::Error(validation.error_message())
+                    }
+                }
+            },
+            Message::Command(command) => {
+                COMMANDS.lock().unwrap().push_back(command);
+                Message::Ack
+            },
+            Message::Timeline(timeline) => {
+                *TIMELINE.lock().unwrap() = timeline;
+                Message::Ack
+            },
+            _ => Message::Error(ProtocolError::UnexpectedMessage.to_string())
+        };
+        if writer.send(reply).is_err() {
+            return Ok(());
+        }
+    }
+}
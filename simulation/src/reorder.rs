@@ -0,0 +1,149 @@
+use settings::material::{Material, ViscosityModel};
+use settings::SimulationParameters;
+
+use crate::particle::{NeighbourSearchGridState, ParticlesState};
+
+//Moves the particle data into the order of the sorted cells after every neighbour search, so the
+//neighbour loops read memory close together instead of gathering through the sorted ids.
+//Particles keep their id, exports are written in that order. Springs and stress are only moved while
+//the viscoelastic model or granular material use them, both start over when they are picked again.
+//The APIC velocity gradients are left alone, only SPH steps reorder and APIC starts without them
+pub struct ReorderState {
+    particles_buffer: wgpu::Buffer,
+    predicted_buffer: wgpu::Buffer,
+    springs_buffer: wgpu::Buffer,
+    stress_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    index_pipeline: wgpu::ComputePipeline,
+    springs_pipeline: wgpu::ComputePipeline,
+    stress_pipeline: wgpu::ComputePipeline,
+    gather_pipeline: wgpu::ComputePipeline
+}
+
+impl ReorderState {
+    pub fn new(device: &wgpu::Device, particles_state: &ParticlesState, grid_state: &NeighbourSearchGridState) -> Self {
+        //Copies of the data taken before every reordering
+        let copy = |label: &str, buffer: &wgpu::Buffer| device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: buffer.size(),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false
+        });
+
+        let particles_buffer = copy("Reorder particles buffer", &particles_state.particles_buffer);
+        let predicted_buffer = copy("Reorder predicted buffer", &particles_state.predicted_buffer);
+        let springs_buffer = copy("Reorder springs buffer", &particles_state.springs_buffer);
+        let stress_buffer = copy("Reorder stress buffer", &particles_state.stress_buffer);
+        let new_index_buffer = copy("Reorder index buffer", &grid_state.value_particle_id_buffer);
+
+        let storage = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Reorder bind group layout"),
+            entries: &[
+                //Particles before the reordering
+                storage(0),
+                //Predicted positions and their copy
+                storage(1),
+                storage(2),
+                //Springs before the reordering
+                storage(3),
+                //Stress before the reordering
+                storage(4),
+                //New index of every particle
+                storage(5),
+            ]
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Reorder bind group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: particles_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: particles_state.predicted_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: predicted_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: springs_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 4, resource: stress_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 5, resource: new_index_buffer.as_entire_binding() },
+            ]
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Reorder shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/reorder.wgsl").into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Reorder pipeline layout"),
+            bind_group_layouts: &[
+                &particles_state.particles_bind_group_layout,
+                &bind_group_layout,
+                &grid_state.bind_group_layout
+            ],
+            push_constant_ranges: &[]
+        });
+
+        let pipeline = |entry_point: &str| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(entry_point),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point
+        });
+
+        ReorderState {
+            particles_buffer,
+            predicted_buffer,
+            springs_buffer,
+            stress_buffer,
+            bind_group,
+            index_pipeline: pipeline("reorder_index"),
+            springs_pipeline: pipeline("reorder_springs"),
+            stress_pipeline: pipeline("reorder_stress"),
+            gather_pipeline: pipeline("reorder_gather")
+        }
+    }
+
+    //Permute the particles into the sorted order and point the sorted ids at them
+    pub fn reorder(&self, encoder: &mut wgpu::CommandEncoder, sim: &SimulationParameters, particles_state: &ParticlesState, grid_state: &NeighbourSearchGridState) {
+        let springs = sim.viscosity_model() == ViscosityModel::Viscoelastic;
+        let stress = sim.material() == Material::Granular;
+
+        let mut copies = vec![
+            (&particles_state.particles_buffer, &self.particles_buffer),
+            (&particles_state.predicted_buffer, &self.predicted_buffer)
+        ];
+        let mut pipelines = vec![&self.index_pipeline];
+        if springs {
+            copies.push((&particles_state.springs_buffer, &self.springs_buffer));
+            pipelines.push(&self.springs_pipeline);
+        }
+        if stress {
+            copies.push((&particles_state.stress_buffer, &self.stress_buffer));
+            pipelines.push(&self.stress_pipeline);
+        }
+        pipelines.push(&self.gather_pipeline);
+
+        for (from, to) in copies {
+            encoder.copy_buffer_to_buffer(from, 0, to, 0, from.size());
+        }
+
+        let count = particles_state.particles.len() as u32;
+        for pipeline in pipelines {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            compute_pass.set_pipeline(pipeline);
+            compute_pass.set_bind_group(0, &particles_state.particles_bind_group, &[]);
+            compute_pass.set_bind_group(1, &self.bind_group, &[]);
+            compute_pass.set_bind_group(2, &grid_state.bind_group, &[]);
+            compute_pass.dispatch_workgroups(count.div_ceil(64), 1, 1);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+use settings::protocol::Command;
+use settings::SimulationParameters;
+
+use crate::remote::COMMANDS;
+use crate::uniforms::parameters::SIMULATION_PARAMETERS;
+
+//Limits that stop a runaway script at the step it ran away in, which switches it off.
+//A million operations take a few milliseconds
+const MAX_OPERATIONS: u64 = 1_000_000;
+const MAX_CALL_LEVELS: usize = 64;
+const MAX_STRING_SIZE: usize = 1 << 16;
+//Elements of an array or entries of a map, also bounds what `state` can collect over steps
+const MAX_COLLECTION_SIZE: usize = 10_000;
+
+//Rhai script run before every step. It sees `time`, `frame` and `dt` of the step about to run,
+//can change `params` and keep values between steps in the `state` map, e.g.
+//  if time >= 2.0 && state.tilted != true {
+//      let angle = 30.0.to_radians();
+//      params.gravity = [15.0 * angle.sin(), -15.0 * angle.cos(), 0.0];
+//      state.tilted = true;
+//  }
+//The commands of the settings UI are available as functions: pause(), resume(), step(), reset(), snapshot() and screenshot()
+pub struct ScriptState {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    //Length of the scope without what the script declared, so variables don't pile up over steps
+    base: usize,
+    failed: bool
+}
+
+impl ScriptState {
+    pub fn new(source: &str) -> Result<Self, String> {
+        let mut engine = Engine::new();
+        engine
+            .set_max_operations(MAX_OPERATIONS)
+            .set_max_call_levels(MAX_CALL_LEVELS)
+            .set_max_string_size(MAX_STRING_SIZE)
+            .set_max_array_size(MAX_COLLECTION_SIZE)
+            .set_max_map_size(MAX_COLLECTION_SIZE);
+        let commands = [
+            ("pause", Command::Pause),
+            ("resume", Command::Resume),
+            ("step", Command::Step),
+            ("reset", Command::Reset),
+            ("snapshot", Command::Snapshot),
+            ("screenshot", Command::Screenshot)
+        ];
+        for (name, command) in commands {
+            engine.register_fn(name, move || COMMANDS.lock().unwrap().push_back(command));
+        }
+
+        let ast = engine.compile(source).map_err(|err| format!("Failed to compile the script: {err}"))?;
+
+        let mut scope = Scope::new();
+        scope.push("state", Map::new());
+        scope.push("time", 0.0);
+        scope.push("frame", 0_i64);
+        scope.push("dt", 0.0);
+        scope.push("params", Dynamic::UNIT);
+        let base = scope.len();
+
+        Ok(ScriptState {
+            engine,
+            ast,
+            scope,
+            base,
+            failed: false
+        })
+    }
+
+    //A failing script is switched off instead of reporting the same error every step
+    pub fn run(&mut self, frame: u64, time: f64) {
+        if self.failed {
+            return;
+        }
+        if let Err(err) = self.execute(frame, time) {
+            log::error!("Script failed at frame {frame}, it won't run anymore: {err}");
+            self.failed = true;
+        }
+        self.scope.rewind(self.base);
+    }
+
+    fn execute(&mut self, frame: u64, time: f64) -> Result<(), String> {
+        let parameters = *SIMULATION_PARAMETERS.lock().unwrap();
+        self.scope.set_value("time", time);
+        self.scope.set_value("frame", frame as i64);
+        self.scope.set_value("dt", parameters.time_step as f64);
+        self.scope.set_value("params", rhai::serde::to_dynamic(parameters).map_err(|err| err.to_string())?);
+
+        self.engine.run_ast_with_scope(&mut self.scope, &self.ast).map_err(|err| err.to_string())?;
+
+        //Rhai numbers are f64 and i64, JSON narrows them to the types of the parameters
+        let params = self.scope.get_value::<Dynamic>("params").unwrap_or_default();
+        let mut edited: SimulationParameters = serde_json::to_value(&params)
+            .and_then(serde_json::from_value)
+            .map_err(|err| format!("invalid params: {err}"))?;
+
+        //Buffers of a running simulation can't be resized
+        edited.particles_amount = parameters.particles_amount;
+        if edited != parameters {
+            *SIMULATION_PARAMETERS.lock().unwrap() = edited;
+        }
+        Ok(())
+    }
+}
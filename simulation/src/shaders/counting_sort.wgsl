@@ -0,0 +1,134 @@
+//Buckets a workgroup of the scan covers, 4 per thread
+const SCAN_BLOCK: u32 = 1024u;
+const SCAN_THREADS: u32 = 256u;
+
+@group(0) @binding(0) var<storage, read_write> cell_hash : array<u32>;
+@group(0) @binding(1) var<storage, read_write> particle_id : array<u32>;
+@group(0) @binding(2) var<storage, read_write> cell_start : array<u32>;
+@group(0) @binding(5) var<storage, read_write> cell_end : array<u32>;
+//Particles per bucket
+@group(1) @binding(0) var<storage, read_write> cell_count : array<atomic<u32>>;
+//Particles counted in the bucket before each particle
+@group(1) @binding(1) var<storage, read_write> rank : array<u32>;
+//Bucket of every particle, by particle
+@group(1) @binding(2) var<storage, read_write> particle_key : array<u32>;
+//Particles in their bucket, in the order they were counted
+@group(1) @binding(3) var<storage, read_write> unordered : array<u32>;
+//Total of every block of the scan, then its offset
+@group(1) @binding(4) var<storage, read_write> block_sums : array<u32>;
+
+var<workgroup> scan : array<u32, SCAN_THREADS>;
+
+@compute @workgroup_size(64)
+fn countCells(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+    let idx = global_invocation_id.x;
+    if(idx >= arrayLength(&particle_key)) { return; }
+
+    rank[idx] = atomicAdd(&cell_count[particle_key[idx]], 1u);
+}
+
+//Exclusive prefix sum over the values of the workgroup's threads
+fn workgroup_scan(thread: u32, value: u32) -> u32 {
+    scan[thread] = value;
+    workgroupBarrier();
+    for(var offset = 1u; offset < SCAN_THREADS; offset <<= 1u) {
+        var add = 0u;
+        if(thread >= offset) { add = scan[thread - offset]; }
+        workgroupBarrier();
+        scan[thread] += add;
+        workgroupBarrier();
+    }
+    return scan[thread] - value;
+}
+
+//Offsets of the buckets within each block of SCAN_BLOCK, and the total of the block
+@compute @workgroup_size(256)
+fn scanBlocks(@builtin(local_invocation_id) local_invocation_id : vec3u, @builtin(workgroup_id) workgroup_id : vec3u) {
+    let thread = local_invocation_id.x;
+    let table_size = arrayLength(&cell_start);
+    let first = workgroup_id.x * SCAN_BLOCK + thread * 4u;
+
+    var counts = array<u32, 4>(0u, 0u, 0u, 0u);
+    var sum = 0u;
+    for(var k = 0u; k < 4u; k++) {
+        if(first + k < table_size) { counts[k] = atomicLoad(&cell_count[first + k]); }
+        sum += counts[k];
+    }
+
+    var offset = workgroup_scan(thread, sum);
+    for(var k = 0u; k < 4u; k++) {
+        if(first + k < table_size) { cell_start[first + k] = offset; }
+        offset += counts[k];
+    }
+    if(thread == SCAN_THREADS - 1u) {
+        block_sums[workgroup_id.x] = offset;
+    }
+}
+
+//Offsets of the blocks, a single workgroup goes over all of them
+@compute @workgroup_size(256)
+fn scanBlockSums(@builtin(local_invocation_id) local_invocation_id : vec3u) {
+    let thread = local_invocation_id.x;
+    let blocks = arrayLength(&block_sums);
+    let per_thread = (blocks + SCAN_THREADS - 1u) / SCAN_THREADS;
+    let first = thread * per_thread;
+
+    var sum = 0u;
+    for(var k = first; k < min(first + per_thread, blocks); k++) {
+        sum += block_sums[k];
+    }
+
+    var offset = workgroup_scan(thread, sum);
+    for(var k = first; k < min(first + per_thread, blocks); k++) {
+        let total = block_sums[k];
+        block_sums[k] = offset;
+        offset += total;
+    }
+}
+
+//Range of every bucket, empty buckets start where they end
+@compute @workgroup_size(64)
+fn addBlockOffsets(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+    let idx = global_invocation_id.x;
+    if(idx >= arrayLength(&cell_start)) { return; }
+
+    let start = cell_start[idx] + block_sums[idx / SCAN_BLOCK];
+    cell_start[idx] = start;
+    cell_end[idx] = start + atomicLoad(&cell_count[idx]);
+}
+
+@compute @workgroup_size(64)
+fn scatterParticles(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+    let idx = global_invocation_id.x;
+    if(idx >= arrayLength(&particle_key)) { return; }
+
+    unordered[cell_start[particle_key[idx]] + rank[idx]] = idx;
+}
+
+//The order of the atomics changes between runs, so particles are ordered by index within their bucket.
+//Each one counts the smaller indices of its bucket, which is quadratic in the bucket size. Buckets of
+//more than MAX_ORDERED_BUCKET particles keep the order they were counted in and differ between runs
+const MAX_ORDERED_BUCKET: u32 = 1024u;
+
+@compute @workgroup_size(64)
+fn orderCells(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+    let idx = global_invocation_id.x;
+    if(idx >= arrayLength(&particle_key)) { return; }
+
+    let id = unordered[idx];
+    let key = particle_key[id];
+    let start = cell_start[key];
+    let end = cell_end[key];
+
+    var place = idx;
+    if(end - start <= MAX_ORDERED_BUCKET) {
+        var before = 0u;
+        for(var i = start; i < end; i++) {
+            if(unordered[i] < id) { before++; }
+        }
+        place = start + before;
+    }
+
+    particle_id[place] = id;
+    cell_hash[place] = key;
+}
@@ -0,0 +1,138 @@
+
+///
+/// Divergence-free SPH (Bender and Koschier 2015), appended to simulation.wgsl.
+/// Pressure is solved for implicitly by correcting the velocities, first so the density
+/// after the step is the rest density, then so the density stops changing.
+/// Both solves share the neighbourhood of the predicted positions and run a fixed number of
+/// iterations, checking the error would mean reading it back every pass.
+/// solver_field.x holds the DFSPH factor, solver_scalar the stiffness of the current pass
+///
+
+@compute @workgroup_size(64)
+fn dfsph_factor(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+  let idx = global_invocation_id.x;
+  if(idx >= sim.particles_amount) { return; }
+
+  let p1_pos = predicted[idx].position;
+
+  var own_gradient = vec3f(0.0);
+  var gradients_sum = 0.0;
+
+  let center = get_cell_coord(p1_pos);
+  //Neighbour search
+  for(var x = -1; x <= 1; x++) {
+    for(var y = -1; y <= 1; y++) {
+      let cur_pos = center + vec3i(x, y, 0);
+
+      let hash = cell_key(cur_pos);
+      if hash == MAX_U32 { continue; }
+
+      for(var i = cell_start[hash]; i < cell_end[hash]; i++) {
+        let id2 = particle_id[i];
+        let pos_vector = predicted[id2].position - p1_pos;
+        let distance = length(pos_vector);
+        if (id2 == idx || distance == 0.0) { continue; }
+
+        let gradient = normalize(pos_vector) * sim.particle_mass * d1_kernel(sim.density_kernel, distance, sim.poly_kernel_radius);
+        own_gradient += gradient;
+        gradients_sum += dot(gradient, gradient);
+      }
+    }
+  }
+
+  //Particles without neighbours need no correction
+  let denominator = dot(own_gradient, own_gradient) + gradients_sum;
+  var factor = 0.0;
+  if denominator > 1e-6 { factor = density_field[idx] / denominator; }
+  solver_field[idx] = vec4f(factor, 0.0, 0.0, 0.0);
+}
+
+@compute @workgroup_size(64)
+fn dfsph_density_error(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+  let idx = global_invocation_id.x;
+  if(idx >= sim.particles_amount) { return; }
+
+  //The density was taken at the predicted positions, only the motion beyond them changes it further
+  let density = density_field[idx] + sim.time_step * density_change(idx, true);
+  let error = max(density - sim.rest_density, 0.0);
+  solver_scalar[idx] = error * solver_field[idx].x / (sim.time_step * sim.time_step);
+}
+
+@compute @workgroup_size(64)
+fn dfsph_divergence_error(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+  let idx = global_invocation_id.x;
+  if(idx >= sim.particles_amount) { return; }
+
+  let error = max(density_change(idx, false), 0.0);
+  solver_scalar[idx] = error * solver_field[idx].x / sim.time_step;
+}
+
+//Same correction for both solves, from the stiffness of the pass before
+@compute @workgroup_size(64)
+fn dfsph_correct(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+  let idx = global_invocation_id.x;
+  if(idx >= sim.particles_amount) { return; }
+
+  let p1_pos = predicted[idx].position;
+  let p1_stiffness = solver_scalar[idx] / density_field[idx];
+
+  var correction = vec3f(0.0);
+
+  let center = get_cell_coord(p1_pos);
+  //Neighbour search
+  for(var x = -1; x <= 1; x++) {
+    for(var y = -1; y <= 1; y++) {
+      let cur_pos = center + vec3i(x, y, 0);
+
+      let hash = cell_key(cur_pos);
+      if hash == MAX_U32 { continue; }
+
+      for(var i = cell_start[hash]; i < cell_end[hash]; i++) {
+        let id2 = particle_id[i];
+        let pos_vector = predicted[id2].position - p1_pos;
+        let distance = length(pos_vector);
+        if (id2 == idx || distance == 0.0) { continue; }
+
+        let p2_stiffness = solver_scalar[id2] / density_field[id2];
+        correction += sim.particle_mass * (p1_stiffness + p2_stiffness) * normalize(pos_vector) * d1_kernel(sim.density_kernel, distance, sim.poly_kernel_radius);
+      }
+    }
+  }
+
+  particles[idx].velocity -= sim.time_step * correction * sim.pixels_per_metre;
+}
+
+//Rate of change of the density in kg/m²/s, optionally only from the velocity beyond the prediction
+fn density_change(idx: u32, beyond_prediction: bool) -> f32 {
+  let p1_pos = predicted[idx].position;
+  var p1_vel = particles[idx].velocity;
+  if beyond_prediction { p1_vel -= predicted[idx].velocity; }
+
+  var change = 0.0;
+
+  let center = get_cell_coord(p1_pos);
+  //Neighbour search
+  for(var x = -1; x <= 1; x++) {
+    for(var y = -1; y <= 1; y++) {
+      let cur_pos = center + vec3i(x, y, 0);
+
+      let hash = cell_key(cur_pos);
+      if hash == MAX_U32 { continue; }
+
+      for(var i = cell_start[hash]; i < cell_end[hash]; i++) {
+        let id2 = particle_id[i];
+        let pos_vector = predicted[id2].position - p1_pos;
+        let distance = length(pos_vector);
+        if (id2 == idx || distance == 0.0) { continue; }
+
+        var p2_vel = particles[id2].velocity;
+        if beyond_prediction { p2_vel -= predicted[id2].velocity; }
+
+        let vel_vector = (p1_vel - p2_vel) / sim.pixels_per_metre;
+        change += sim.particle_mass * dot(vel_vector, normalize(pos_vector)) * d1_kernel(sim.density_kernel, distance, sim.poly_kernel_radius);
+      }
+    }
+  }
+
+  return change;
+}
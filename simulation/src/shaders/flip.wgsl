@@ -0,0 +1,383 @@
+struct Particle {
+    position: vec3<f32>,
+    //Index at creation, kept when the particles are reordered
+    id: u32,
+    velocity: vec3<f32>,
+    color: vec4<f32>
+}
+
+struct BoundingBox {
+  position1: vec3<f32>,
+  position2: vec3<f32>,
+}
+
+struct SimulationParameters {
+  bounding_box: BoundingBox,
+  gravity: vec3<f32>,
+  particle_mass: f32,
+  particle_radius: f32,
+  particles_amount: u32,
+  collision_damping: f32, 
+  poly_kernel_radius: f32,
+  pressure_kernel_radius: f32,
+  near_pressure_kernel_radius: f32,
+  viscosity_kernel_radius: f32,
+  viscosity: f32,
+  cohesion_coef: f32,
+  curvature_cef: f32, 
+  adhesion_cef: f32,
+  rest_density: f32,
+  pressure_multiplier: f32,
+  near_pressure_multiplier: f32,
+  grid_size: f32,
+  pixels_per_metre: f32,
+  vorticity_kernel_radius: f32,
+  vorticity_inensity: f32,
+  cohesion_kernel_radius: f32,
+  adhesion_kernel_radius: f32,
+  surface_normal_kernel_radius: f32,
+  time_step: f32,
+  velocity_smoothing_scale: f32,
+  pressure_solver: u32,
+  solver_iterations: u32,
+  speed_of_sound: f32,
+  tait_exponent: f32,
+  pbf_relaxation: f32,
+  pbf_tensile_coef: f32,
+  density_kernel: u32,
+  pressure_kernel: u32,
+  viscosity_kernel: u32,
+  surface_normal_kernel: u32,
+  vorticity_kernel: u32,
+  smoothing_kernel: u32,
+  viscosity_model: u32,
+  flow_index: f32,
+  infinite_shear_viscosity: f32,
+  relaxation_time: f32,
+  yield_stress: f32,
+  max_viscosity: f32,
+  spring_stiffness: f32,
+  spring_yield_ratio: f32,
+  plasticity: f32,
+  material: u32,
+  friction_angle: f32,
+  granular_cohesion: f32,
+  shear_modulus: f32,
+  simulation_method: u32,
+  flip_ratio: f32,
+  flip_cell_size: f32,
+  projection_iterations: u32,
+  neighbour_search: u32,
+  hash_table_size: u32,
+  neighbour_sort: u32
+}
+
+//Ids of settings::method::SimulationMethod
+const METHOD_SPH: u32 = 0u;
+const METHOD_FLIP: u32 = 1u;
+const METHOD_APIC: u32 = 2u;
+
+//MAC grid over the bounding box. Velocities are stored on the faces, u faces first, then v faces
+struct Grid {
+  origin: vec2<f32>,
+  size: vec2<u32>,
+  //In pixels
+  cell_size: f32
+}
+
+const CELL_AIR: u32 = 0u;
+const CELL_FLUID: u32 = 1u;
+//Outside of the grid, the walls of the bounding box
+const CELL_SOLID: u32 = 2u;
+
+//Momentum and weight are summed as fixed point integers, so the sum doesn't depend on the order of the particles
+const FIXED_POINT_SCALE: f32 = 100000.0;
+
+@group(0) @binding(0) var<storage, read_write> particles : array<Particle>;
+//Momentum and weight per face in m/s
+@group(1) @binding(0) var<storage, read_write> accumulator : array<atomic<i32>>;
+//Face velocities in pixels per second
+@group(1) @binding(1) var<storage, read_write> velocity : array<f32>;
+//Velocities before the projection, FLIP takes the change from them
+@group(1) @binding(2) var<storage, read_write> saved_velocity : array<f32>;
+@group(1) @binding(3) var<storage, read_write> cell_type : array<atomic<u32>>;
+//Kept between steps as the first guess of the next projection
+@group(1) @binding(4) var<storage, read_write> pressure : array<f32>;
+@group(1) @binding(5) var<storage, read_write> pressure_next : array<f32>;
+@group(1) @binding(6) var<storage, read_write> divergence : array<f32>;
+//Velocity gradient carried by APIC particles as du/dx, du/dy, dv/dx, dv/dy
+@group(1) @binding(7) var<storage, read_write> affine : array<vec4<f32>>;
+@group(1) @binding(8) var<uniform> grid: Grid;
+@group(2) @binding(1) var<uniform> sim: SimulationParameters;
+
+fn u_faces() -> u32 {
+  return (grid.size.x + 1u) * grid.size.y;
+}
+
+fn v_faces() -> u32 {
+  return grid.size.x * (grid.size.y + 1u);
+}
+
+fn u_index(i: i32, j: i32) -> u32 {
+  let x = u32(clamp(i, 0, i32(grid.size.x)));
+  let y = u32(clamp(j, 0, i32(grid.size.y) - 1));
+  return y * (grid.size.x + 1u) + x;
+}
+
+fn v_index(i: i32, j: i32) -> u32 {
+  let x = u32(clamp(i, 0, i32(grid.size.x) - 1));
+  let y = u32(clamp(j, 0, i32(grid.size.y)));
+  return u_faces() + y * grid.size.x + x;
+}
+
+fn cell_index(i: i32, j: i32) -> u32 {
+  return u32(j) * grid.size.x + u32(i);
+}
+
+fn cell_at(i: i32, j: i32) -> u32 {
+  if i < 0 || j < 0 || i >= i32(grid.size.x) || j >= i32(grid.size.y) { return CELL_SOLID; }
+  return atomicLoad(&cell_type[cell_index(i, j)]);
+}
+
+//Position in grid units, shifted so the faces of one axis sit on integer coordinates
+fn face_coord(position: vec3<f32>, offset: vec2<f32>) -> vec2<f32> {
+  return (position.xy - grid.origin) / grid.cell_size - offset;
+}
+
+//Bilinear weights of the four faces around a position, and their index
+struct Stencil {
+  base: vec2<i32>,
+  fraction: vec2<f32>
+}
+
+fn stencil(coord: vec2<f32>) -> Stencil {
+  let base = floor(coord);
+  return Stencil(vec2<i32>(base), coord - base);
+}
+
+fn weight(s: Stencil, corner: vec2<i32>) -> f32 {
+  let w = mix(1.0 - s.fraction, s.fraction, vec2<f32>(corner));
+  return w.x * w.y;
+}
+
+//Gradient of the weight in 1/pixels
+fn weight_gradient(s: Stencil, corner: vec2<i32>) -> vec2<f32> {
+  let w = mix(1.0 - s.fraction, s.fraction, vec2<f32>(corner));
+  let dw = mix(vec2<f32>(-1.0), vec2<f32>(1.0), vec2<f32>(corner));
+  return vec2<f32>(dw.x * w.y, w.x * dw.y) / grid.cell_size;
+}
+
+fn face_index(axis: u32, cell: vec2<i32>) -> u32 {
+  if axis == 0u { return u_index(cell.x, cell.y); }
+  return v_index(cell.x, cell.y);
+}
+
+//Faces of the u grid sit on the cell's left edge, v faces on its bottom edge
+fn face_offset(axis: u32) -> vec2<f32> {
+  if axis == 0u { return vec2<f32>(0.0, 0.5); }
+  return vec2<f32>(0.5, 0.0);
+}
+
+@compute @workgroup_size(64)
+fn flip_particles_to_grid(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+  let idx = global_invocation_id.x;
+  if(idx >= sim.particles_amount) { return; }
+
+  let particle = particles[idx];
+  let c = affine[idx];
+  var gradients = array<vec2<f32>, 2>(c.xy, c.zw);
+
+  let cell = vec2<i32>(floor((particle.position.xy - grid.origin) / grid.cell_size));
+  let clamped = clamp(cell, vec2<i32>(0), vec2<i32>(grid.size) - 1);
+  atomicStore(&cell_type[cell_index(clamped.x, clamped.y)], CELL_FLUID);
+
+  for(var axis = 0u; axis < 2u; axis++) {
+    let offset = face_offset(axis);
+    let s = stencil(face_coord(particle.position, offset));
+    for(var x = 0; x <= 1; x++) {
+      for(var y = 0; y <= 1; y++) {
+        let corner = vec2<i32>(x, y);
+        let w = weight(s, corner);
+        //APIC adds the velocity the particle's gradient gives at the face, zero for FLIP
+        let face_position = (vec2<f32>(s.base + corner) + offset) * grid.cell_size + grid.origin;
+        let face_velocity = particle.velocity[axis] + dot(gradients[axis], face_position - particle.position.xy);
+        let face = face_index(axis, s.base + corner);
+        atomicAdd(&accumulator[2u * face], i32(round(w * face_velocity / sim.pixels_per_metre * FIXED_POINT_SCALE)));
+        atomicAdd(&accumulator[2u * face + 1u], i32(round(w * FIXED_POINT_SCALE)));
+      }
+    }
+  }
+}
+
+//Faces on the walls of the bounding box
+fn is_wall(face: u32) -> bool {
+  if face < u_faces() {
+    let i = face % (grid.size.x + 1u);
+    return i == 0u || i == grid.size.x;
+  }
+  let j = (face - u_faces()) / grid.size.x;
+  return j == 0u || j == grid.size.y;
+}
+
+@compute @workgroup_size(64)
+fn flip_normalize(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+  let face = global_invocation_id.x;
+  if(face >= u_faces() + v_faces()) { return; }
+
+  let total_weight = atomicLoad(&accumulator[2u * face + 1u]);
+  var v = 0.0;
+  if total_weight > 0 {
+    v = f32(atomicLoad(&accumulator[2u * face])) / f32(total_weight) * sim.pixels_per_metre;
+  }
+  saved_velocity[face] = v;
+
+  if total_weight > 0 {
+    var axis = 0u;
+    if face >= u_faces() { axis = 1u; }
+    v += sim.time_step * sim.gravity[axis] * sim.pixels_per_metre;
+  }
+  if is_wall(face) { v = 0.0; }
+  velocity[face] = v;
+}
+
+@compute @workgroup_size(64)
+fn flip_divergence(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+  let idx = global_invocation_id.x;
+  if(idx >= grid.size.x * grid.size.y) { return; }
+
+  let i = i32(idx % grid.size.x);
+  let j = i32(idx / grid.size.x);
+  if cell_at(i, j) != CELL_FLUID {
+    divergence[idx] = 0.0;
+    return;
+  }
+
+  let du = velocity[u_index(i + 1, j)] - velocity[u_index(i, j)];
+  let dv = velocity[v_index(i, j + 1)] - velocity[v_index(i, j)];
+  divergence[idx] = (du + dv) / grid.cell_size;
+}
+
+//One Jacobi iteration of the pressure Poisson equation. Walls keep the normal velocity at zero,
+//air is at zero pressure
+fn jacobi(idx: u32, from_next: bool) -> f32 {
+  let i = i32(idx % grid.size.x);
+  let j = i32(idx / grid.size.x);
+  if cell_at(i, j) != CELL_FLUID { return 0.0; }
+
+  var sum = 0.0;
+  var count = 0.0;
+  var neighbours = array<vec2<i32>, 4>(vec2<i32>(-1, 0), vec2<i32>(1, 0), vec2<i32>(0, -1), vec2<i32>(0, 1));
+  for(var n = 0; n < 4; n++) {
+    let neighbour = vec2<i32>(i, j) + neighbours[n];
+    let kind = cell_at(neighbour.x, neighbour.y);
+    if kind == CELL_SOLID { continue; }
+    count += 1.0;
+    if kind != CELL_FLUID { continue; }
+    let n_idx = cell_index(neighbour.x, neighbour.y);
+    if from_next { sum += pressure_next[n_idx]; } else { sum += pressure[n_idx]; }
+  }
+  if count == 0.0 { return 0.0; }
+
+  return (sum - grid.cell_size * grid.cell_size * divergence[idx] / sim.time_step) / count;
+}
+
+@compute @workgroup_size(64)
+fn flip_jacobi_even(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+  let idx = global_invocation_id.x;
+  if(idx >= grid.size.x * grid.size.y) { return; }
+  pressure_next[idx] = jacobi(idx, false);
+}
+
+@compute @workgroup_size(64)
+fn flip_jacobi_odd(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+  let idx = global_invocation_id.x;
+  if(idx >= grid.size.x * grid.size.y) { return; }
+  pressure[idx] = jacobi(idx, true);
+}
+
+fn cell_pressure(i: i32, j: i32) -> f32 {
+  if cell_at(i, j) != CELL_FLUID { return 0.0; }
+  return pressure[cell_index(i, j)];
+}
+
+//Subtract the pressure gradient from the faces between fluid and fluid or air
+@compute @workgroup_size(64)
+fn flip_project(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+  let face = global_invocation_id.x;
+  if(face >= u_faces() + v_faces() || is_wall(face)) { return; }
+
+  var cell = vec2<i32>(0);
+  var previous = vec2<i32>(0);
+  if face < u_faces() {
+    cell = vec2<i32>(i32(face % (grid.size.x + 1u)), i32(face / (grid.size.x + 1u)));
+    previous = cell - vec2<i32>(1, 0);
+  } else {
+    let v_face = face - u_faces();
+    cell = vec2<i32>(i32(v_face % grid.size.x), i32(v_face / grid.size.x));
+    previous = cell - vec2<i32>(0, 1);
+  }
+
+  if cell_at(cell.x, cell.y) != CELL_FLUID && cell_at(previous.x, previous.y) != CELL_FLUID { return; }
+  let gradient = (cell_pressure(cell.x, cell.y) - cell_pressure(previous.x, previous.y)) / grid.cell_size;
+  velocity[face] -= sim.time_step * gradient;
+}
+
+@compute @workgroup_size(64)
+fn flip_grid_to_particles(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+  let idx = global_invocation_id.x;
+  if(idx >= sim.particles_amount) { return; }
+
+  var particle = particles[idx];
+  let particle_velocity = particle.velocity;
+  var pic = vec2<f32>(0.0);
+  var change = vec2<f32>(0.0);
+  var gradients = array<vec2<f32>, 2>(vec2<f32>(0.0), vec2<f32>(0.0));
+
+  for(var axis = 0u; axis < 2u; axis++) {
+    let s = stencil(face_coord(particle.position, face_offset(axis)));
+    for(var x = 0; x <= 1; x++) {
+      for(var y = 0; y <= 1; y++) {
+        let corner = vec2<i32>(x, y);
+        let face = face_index(axis, s.base + corner);
+        let w = weight(s, corner);
+        pic[axis] += w * velocity[face];
+        change[axis] += w * (velocity[face] - saved_velocity[face]);
+        gradients[axis] += weight_gradient(s, corner) * velocity[face];
+      }
+    }
+  }
+
+  var v = pic;
+  if sim.simulation_method == METHOD_APIC {
+    affine[idx] = vec4<f32>(gradients[0], gradients[1]);
+  } else {
+    v = mix(pic, particle_velocity.xy + change, sim.flip_ratio);
+    affine[idx] = vec4<f32>(0.0);
+  }
+
+  particle.velocity = vec3<f32>(v, 0.0);
+  particle.position += sim.time_step * particle.velocity;
+  compute_collisions(&particle);
+  particles[idx] = particle;
+}
+
+fn compute_collisions(particle: ptr<function, Particle>)  {
+  let p1 = sim.bounding_box.position1;
+  let p2 = sim.bounding_box.position2;
+
+  var pos = (*particle).position;
+  var vel = (*particle).velocity;
+
+  if pos.x < p1.x || pos.x > p2.x {
+    pos.x = clamp(pos.x, p1.x, p2.x);
+    vel.x *= -sim.collision_damping;
+  }
+
+  if pos.y < p1.y || pos.y > p2.y {
+    pos.y = clamp(pos.y, p1.y, p2.y);
+    vel.y *= -sim.collision_damping;
+  }
+
+  (*particle).position = pos;
+  (*particle).velocity = vel;
+}
@@ -0,0 +1,112 @@
+
+///
+/// Position based fluids (Macklin and Müller 2013), appended to simulation.wgsl.
+/// Density constraints are projected on the predicted positions after the other forces,
+/// the velocity then follows from how far the particles moved.
+/// solver_scalar holds the constraint multipliers, solver_field the position corrections in metres
+///
+
+@compute @workgroup_size(64)
+fn pbf_predict(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+  let idx = global_invocation_id.x;
+  if(idx >= sim.particles_amount) { return; }
+
+  predicted[idx].position = particles[idx].position + sim.time_step * particles[idx].velocity;
+}
+
+@compute @workgroup_size(64)
+fn pbf_lambda(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+  let idx = global_invocation_id.x;
+  if(idx >= sim.particles_amount) { return; }
+
+  let p1_pos = predicted[idx].position;
+
+  var density = 0.0;
+  //Gradients of the constraint with respect to the particle and to each neighbour
+  var own_gradient = vec3f(0.0);
+  var gradients_sum = 0.0;
+
+  let center = get_cell_coord(p1_pos);
+  //Neighbour search
+  for(var x = -1; x <= 1; x++) {
+    for(var y = -1; y <= 1; y++) {
+      let cur_pos = center + vec3i(x, y, 0);
+
+      let hash = cell_key(cur_pos);
+      if hash == MAX_U32 { continue; }
+
+      for(var i = cell_start[hash]; i < cell_end[hash]; i++) {
+        let id2 = particle_id[i];
+        let pos_vector = predicted[id2].position - p1_pos;
+        let distance = length(pos_vector);
+
+        density += sim.particle_mass * kernel(sim.density_kernel, distance, sim.poly_kernel_radius);
+        if (id2 == idx || distance == 0.0) { continue; }
+
+        let gradient = normalize(pos_vector) * sim.particle_mass * d1_kernel(sim.density_kernel, distance, sim.poly_kernel_radius) / sim.rest_density;
+        own_gradient += gradient;
+        gradients_sum += dot(gradient, gradient);
+      }
+    }
+  }
+
+  //Only compression is corrected, pulling sparse particles together makes them clump
+  let constraint = max(density / sim.rest_density - 1.0, 0.0);
+  solver_scalar[idx] = -constraint / (gradients_sum + dot(own_gradient, own_gradient) + sim.pbf_relaxation);
+}
+
+@compute @workgroup_size(64)
+fn pbf_delta(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+  let idx = global_invocation_id.x;
+  if(idx >= sim.particles_amount) { return; }
+
+  let p1_pos = predicted[idx].position;
+  let p1_lambda = solver_scalar[idx];
+  //Artificial pressure is relative to the kernel at a fifth of its radius
+  let reference_kernel = kernel(sim.density_kernel, 0.2 * sim.poly_kernel_radius * sim.pixels_per_metre, sim.poly_kernel_radius);
+
+  var delta = vec3f(0.0);
+
+  let center = get_cell_coord(p1_pos);
+  //Neighbour search
+  for(var x = -1; x <= 1; x++) {
+    for(var y = -1; y <= 1; y++) {
+      let cur_pos = center + vec3i(x, y, 0);
+
+      let hash = cell_key(cur_pos);
+      if hash == MAX_U32 { continue; }
+
+      for(var i = cell_start[hash]; i < cell_end[hash]; i++) {
+        let id2 = particle_id[i];
+        let pos_vector = predicted[id2].position - p1_pos;
+        let distance = length(pos_vector);
+        if (id2 == idx || distance == 0.0) { continue; }
+
+        let tensile = -sim.pbf_tensile_coef * pow(kernel(sim.density_kernel, distance, sim.poly_kernel_radius) / reference_kernel, 4.0);
+        delta += (p1_lambda + solver_scalar[id2] + tensile) * normalize(pos_vector) * d1_kernel(sim.density_kernel, distance, sim.poly_kernel_radius);
+      }
+    }
+  }
+
+  solver_field[idx] = vec4f(delta * sim.particle_mass / sim.rest_density, 0.0);
+}
+
+//Separate from pbf_delta, neighbours still read the positions there
+@compute @workgroup_size(64)
+fn pbf_apply(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+  let idx = global_invocation_id.x;
+  if(idx >= sim.particles_amount) { return; }
+
+  let position = predicted[idx].position + solver_field[idx].xyz * sim.pixels_per_metre;
+  let p1 = sim.bounding_box.position1;
+  let p2 = sim.bounding_box.position2;
+  predicted[idx].position = vec3f(clamp(position.xy, p1.xy, p2.xy), position.z);
+}
+
+@compute @workgroup_size(64)
+fn pbf_velocity(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+  let idx = global_invocation_id.x;
+  if(idx >= sim.particles_amount) { return; }
+
+  particles[idx].velocity = (predicted[idx].position - particles[idx].position) / sim.time_step;
+}
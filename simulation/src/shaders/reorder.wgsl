@@ -0,0 +1,81 @@
+struct Particle {
+    position: vec3<f32>,
+    //Index at creation, kept when the particles are reordered
+    id: u32,
+    velocity: vec3<f32>,
+    color: vec4<f32>
+}
+
+struct Predicted {
+    position: vec3<f32>,
+    velocity: vec3<f32>
+}
+
+//Neighbour is the index plus one, 0 marks a free slot
+struct Spring {
+  neighbour: u32,
+  rest_length: f32
+}
+//Slots per particle, MAX_SPRINGS in particle.rs
+const MAX_SPRINGS: u32 = 16u;
+
+@group(0) @binding(0) var<storage, read_write> particles : array<Particle>;
+@group(0) @binding(1) var<storage, read_write> springs : array<Spring>;
+@group(0) @binding(2) var<storage, read_write> stress : array<vec4<f32>>;
+//Copies of the particle data before the reordering, springs and stress only while they are in use
+@group(1) @binding(0) var<storage, read_write> old_particles : array<Particle>;
+@group(1) @binding(1) var<storage, read_write> predicted : array<Predicted>;
+@group(1) @binding(2) var<storage, read_write> old_predicted : array<Predicted>;
+@group(1) @binding(3) var<storage, read_write> old_springs : array<Spring>;
+@group(1) @binding(4) var<storage, read_write> old_stress : array<vec4<f32>>;
+//Index each particle moves to
+@group(1) @binding(5) var<storage, read_write> new_index : array<u32>;
+@group(2) @binding(1) var<storage, read_write> particle_id : array<u32>;
+
+//After the sort particle_id holds the old index of the particle in every sorted slot
+@compute @workgroup_size(64)
+fn reorder_index(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+    let idx = global_invocation_id.x;
+    if(idx >= arrayLength(&new_index)) { return; }
+
+    new_index[particle_id[idx]] = idx;
+}
+
+//Springs move with their particle and point at their neighbour's new index
+@compute @workgroup_size(64)
+fn reorder_springs(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+    let idx = global_invocation_id.x;
+    if(idx >= arrayLength(&new_index)) { return; }
+
+    let source = particle_id[idx];
+    for(var s = 0u; s < MAX_SPRINGS; s++) {
+        var spring = old_springs[source * MAX_SPRINGS + s];
+        if(spring.neighbour != 0u) {
+            spring.neighbour = new_index[spring.neighbour - 1u] + 1u;
+        }
+        springs[idx * MAX_SPRINGS + s] = spring;
+    }
+}
+
+@compute @workgroup_size(64)
+fn reorder_stress(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+    let idx = global_invocation_id.x;
+    if(idx >= arrayLength(&new_index)) { return; }
+
+    stress[idx] = old_stress[particle_id[idx]];
+}
+
+//Move every particle into its sorted slot, so neighbours in a cell are next to each other in memory.
+//Runs last, the passes above still need the old indices
+@compute @workgroup_size(64)
+fn reorder_gather(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+    let idx = global_invocation_id.x;
+    if(idx >= arrayLength(&new_index)) { return; }
+
+    let source = particle_id[idx];
+    particles[idx] = old_particles[source];
+    predicted[idx] = old_predicted[source];
+
+    //The neighbour search finds the particles in place now
+    particle_id[idx] = idx;
+}
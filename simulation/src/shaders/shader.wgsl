@@ -32,14 +32,46 @@ struct SimulationParameters {
   pressure_multiplier: f32,
   near_pressure_multiplier: f32,
   grid_size: f32,
-  scene_scale_factor: f32,
+  pixels_per_metre: f32,
   vorticity_kernel_radius: f32,
   vorticity_inensity: f32,
   cohesion_kernel_radius: f32,
   adhesion_kernel_radius: f32,
   surface_normal_kernel_radius: f32,
   time_step: f32,
-  velocity_smoothing_scale: f32
+  velocity_smoothing_scale: f32,
+  pressure_solver: u32,
+  solver_iterations: u32,
+  speed_of_sound: f32,
+  tait_exponent: f32,
+  pbf_relaxation: f32,
+  pbf_tensile_coef: f32,
+  density_kernel: u32,
+  pressure_kernel: u32,
+  viscosity_kernel: u32,
+  surface_normal_kernel: u32,
+  vorticity_kernel: u32,
+  smoothing_kernel: u32,
+  viscosity_model: u32,
+  flow_index: f32,
+  infinite_shear_viscosity: f32,
+  relaxation_time: f32,
+  yield_stress: f32,
+  max_viscosity: f32,
+  spring_stiffness: f32,
+  spring_yield_ratio: f32,
+  plasticity: f32,
+  material: u32,
+  friction_angle: f32,
+  granular_cohesion: f32,
+  shear_modulus: f32,
+  simulation_method: u32,
+  flip_ratio: f32,
+  flip_cell_size: f32,
+  projection_iterations: u32,
+  neighbour_search: u32,
+  hash_table_size: u32,
+  neighbour_sort: u32
 }
 
 @group(0) @binding(0) var<uniform> camera: CameraUniform;
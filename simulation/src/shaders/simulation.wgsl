@@ -1,21 +1,29 @@
 const MAX_U32: u32 = 0xFFFFFFFF;
 
-var<private> rand_seed : vec2f;
+var<private> rand_state : u32;
 
-fn init_rand(invocation_id : u32, seed : vec4f) {
-  rand_seed = seed.xz;
-  rand_seed = fract(rand_seed * cos(35.456+f32(invocation_id) * seed.yw));
-  rand_seed = fract(rand_seed * cos(41.235+f32(invocation_id) * seed.xw));
+//PCG hash, integer only so the sequence doesn't depend on the adapter's float precision
+fn pcg_hash(input: u32) -> u32 {
+  let state = input * 747796405u + 2891336453u;
+  let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+  return (word >> 22u) ^ word;
 }
 
+//Every invocation gets its own sequence, which changes every step and with the seed
+fn init_rand(invocation_id : u32) {
+  rand_state = pcg_hash(invocation_id ^ pcg_hash(sim_step.frame ^ pcg_hash(sim_step.seed)));
+}
+
+//Uniform in [0, 1)
 fn rand() -> f32 {
-  rand_seed.x = fract(cos(dot(rand_seed, vec2f(23.14077926, 232.61690225))) * 136.8168);
-  rand_seed.y = fract(cos(dot(rand_seed, vec2f(54.47856553, 345.84153136))) * 534.7645);
-  return rand_seed.y;
+  rand_state = pcg_hash(rand_state);
+  return f32(rand_state >> 8u) / 16777216.0;
 }
 
 struct Particle {
     position: vec3<f32>,
+    //Index at creation, kept when the particles are reordered
+    id: u32,
     velocity: vec3<f32>,
     color: vec4<f32>
 }
@@ -49,26 +57,107 @@ struct SimulationParameters {
   pressure_multiplier: f32,
   near_pressure_multiplier: f32,
   grid_size: f32,
-  scene_scale_factor: f32,
+  pixels_per_metre: f32,
   vorticity_kernel_radius: f32,
   vorticity_inensity: f32,
   cohesion_kernel_radius: f32,
   adhesion_kernel_radius: f32,
   surface_normal_kernel_radius: f32,
   time_step: f32,
-  velocity_smoothing_scale: f32
+  velocity_smoothing_scale: f32,
+  pressure_solver: u32,
+  solver_iterations: u32,
+  speed_of_sound: f32,
+  tait_exponent: f32,
+  pbf_relaxation: f32,
+  pbf_tensile_coef: f32,
+  density_kernel: u32,
+  pressure_kernel: u32,
+  viscosity_kernel: u32,
+  surface_normal_kernel: u32,
+  vorticity_kernel: u32,
+  smoothing_kernel: u32,
+  viscosity_model: u32,
+  flow_index: f32,
+  infinite_shear_viscosity: f32,
+  relaxation_time: f32,
+  yield_stress: f32,
+  max_viscosity: f32,
+  spring_stiffness: f32,
+  spring_yield_ratio: f32,
+  plasticity: f32,
+  material: u32,
+  friction_angle: f32,
+  granular_cohesion: f32,
+  shear_modulus: f32,
+  simulation_method: u32,
+  flip_ratio: f32,
+  flip_cell_size: f32,
+  projection_iterations: u32,
+  neighbour_search: u32,
+  hash_table_size: u32,
+  neighbour_sort: u32
+}
+
+//Ids of settings::solver::PressureSolver
+const SOLVER_LINEAR: u32 = 0u;
+const SOLVER_TAIT: u32 = 1u;
+const SOLVER_PBF: u32 = 2u;
+const SOLVER_DFSPH: u32 = 3u;
+
+//Ids of settings::material::ViscosityModel
+const VISCOSITY_NEWTONIAN: u32 = 0u;
+const VISCOSITY_POWER_LAW: u32 = 1u;
+const VISCOSITY_CARREAU: u32 = 2u;
+const VISCOSITY_BINGHAM: u32 = 3u;
+const VISCOSITY_VISCOELASTIC: u32 = 4u;
+
+//Ids of settings::material::Material
+const MATERIAL_FLUID: u32 = 0u;
+const MATERIAL_GRANULAR: u32 = 1u;
+
+//Neighbour is the id plus one, 0 marks a free slot
+struct Spring {
+  neighbour: u32,
+  rest_length: f32
+}
+//Slots per particle, MAX_SPRINGS in particle.rs
+const MAX_SPRINGS: u32 = 16u;
+
+//Cell range of the dense neighbour search, NeighbourGridUniform in particle.rs
+struct NeighbourGrid {
+  origin: vec2<i32>,
+  size: vec2<u32>
+}
+
+//Ids of settings::neighbour::NeighbourSearch
+const SEARCH_HASHED: u32 = 0u;
+const SEARCH_DENSE: u32 = 1u;
+
+struct Step {
+  frame: u32,
+  seed: u32
 }
 
 @group(0) @binding(0) var<storage, read_write> particles : array<Particle>;
+@group(0) @binding(1) var<storage, read_write> springs : array<Spring>;
+//Deviatoric stress of granular particles as xx, yy, zz and xy in N/m
+@group(0) @binding(2) var<storage, read_write> stress : array<vec4<f32>>;
 @group(1) @binding(0) var<storage, read_write> density_field : array<f32>;
 @group(1) @binding(1) var<storage, read_write> predicted : array<Predicted>;
 @group(1) @binding(2) var<storage, read_write> surface_normals : array<vec3<f32>>;
 @group(1) @binding(3) var<storage, read_write> near_density_field : array<f32>;
 @group(1) @binding(4) var<storage, read_write> vorticity_field : array<vec3<f32>>;
+//Scratch space of the iterative pressure solvers
+@group(1) @binding(5) var<storage, read_write> solver_scalar : array<f32>;
+@group(1) @binding(6) var<storage, read_write> solver_field : array<vec4<f32>>;
+@group(1) @binding(7) var<storage, read_write> viscosity_field : array<f32>;
 @group(2) @binding(1) var<uniform> sim: SimulationParameters;
-@group(3) @binding(0) var<storage, read_write> cell_hash : array<u32>;
+@group(2) @binding(2) var<uniform> sim_step: Step;
 @group(3) @binding(1) var<storage, read_write> particle_id : array<u32>;
 @group(3) @binding(2) var<storage, read_write> cell_start : array<u32>;
+@group(3) @binding(4) var<uniform> neighbour_grid: NeighbourGrid;
+@group(3) @binding(5) var<storage, read_write> cell_end : array<u32>;
 
 @compute @workgroup_size(64)
 fn predict_positions(@builtin(global_invocation_id) global_invocation_id : vec3u) {
@@ -77,7 +166,7 @@ fn predict_positions(@builtin(global_invocation_id) global_invocation_id : vec3u
 
   var particle = particles[idx];
   //Apply gravity
-  predicted[idx].velocity = particle.velocity + sim.time_step * sim.gravity / sim.scene_scale_factor;
+  predicted[idx].velocity = particle.velocity + sim.time_step * sim.gravity * sim.pixels_per_metre;
   predicted[idx].position = particle.position + sim.time_step * predicted[idx].velocity;
 }
 
@@ -95,13 +184,10 @@ fn update_positions(@builtin(global_invocation_id) global_invocation_id : vec3u)
     for(var y = -1; y <= 1; y++) {
       let cur_pos = center + vec3i(x, y, 0); 
 
-      let hash = get_key_from_hash(z_order_hash(cur_pos.x, cur_pos.y));
-
-      var i = cell_start[hash];
-      for(; i < sim.particles_amount; i++) {
-        let cell = cell_hash[i];
-        if(cell != hash) { break; }
+      let hash = cell_key(cur_pos);
+      if hash == MAX_U32 { continue; }
 
+      for(var i = cell_start[hash]; i < cell_end[hash]; i++) {
         let id2 = particle_id[i];
         if (id2 == idx) { continue; }
 
@@ -111,7 +197,7 @@ fn update_positions(@builtin(global_invocation_id) global_invocation_id : vec3u)
         let distance = distance(p2.position, p1.position);
         let vel_vector = p2.velocity - p1.velocity;
 
-        smoothed_vel += sim.particle_mass * vel_vector * poly_kernel(distance, sim.grid_size) / p2_density;
+        smoothed_vel += sim.particle_mass * vel_vector * kernel(sim.smoothing_kernel, distance, sim.grid_size) / p2_density;
       }
     }
   }
@@ -130,12 +216,13 @@ fn calculate_forces(@builtin(global_invocation_id) global_invocation_id : vec3u)
   let idx = global_invocation_id.x;
   if(idx >= sim.particles_amount) { return; }
 
-  init_rand(idx, vec4f(sim.time_step * f32(idx)));
+  init_rand(idx);
   var particle = particles[idx];
 
   //Apply forces
-  let accel = compute_accel(idx);
-  particle.velocity = predicted[idx].velocity +  sim.time_step * accel / sim.scene_scale_factor;
+  var accel = compute_accel(idx);
+  if sim.viscosity_model == VISCOSITY_VISCOELASTIC { accel += spring_accel(idx); }
+  particle.velocity = predicted[idx].velocity +  sim.time_step * accel * sim.pixels_per_metre;
   particles[idx] = particle;
 }
 
@@ -145,6 +232,7 @@ fn compute_accel(idx: u32) -> vec3<f32>{
   var surface_tension_force = vec3<f32>(0.0);
   var adhesion_force = vec3<f32>(0.0);
   var corrective_vorticity = vec3<f32>(0.0);
+  var stress_force = vec2<f32>(0.0);
 
   let p1_vel = predicted[idx].velocity;
   let p1_pos = predicted[idx].position;
@@ -152,6 +240,8 @@ fn compute_accel(idx: u32) -> vec3<f32>{
   let p1_near_density = near_density_field[idx];
   let p1_normal = surface_normals[idx];
   let p1_vorticity = vorticity_field[idx];
+  let p1_viscosity = viscosity_field[idx];
+  let p1_stress = stress_tensor(stress[idx]);
 
   surface_normals[idx] = vec3f(0.0);
 
@@ -161,14 +251,10 @@ fn compute_accel(idx: u32) -> vec3<f32>{
     for(var y = -1; y <= 1; y++) {
       let cur_pos = center + vec3i(x, y, 0); 
 
-      let hash = get_key_from_hash(z_order_hash(cur_pos.x, cur_pos.y));
-
-      var i = cell_start[hash];
-      for(; i < sim.particles_amount; i++) {
-
-        let cell = cell_hash[i];
-        if(cell != hash) { break; }
+      let hash = cell_key(cur_pos);
+      if hash == MAX_U32 { continue; }
 
+      for(var i = cell_start[hash]; i < cell_end[hash]; i++) {
         let id2 = particle_id[i];
         if (id2 == idx) { continue; }
 
@@ -186,15 +272,24 @@ fn compute_accel(idx: u32) -> vec3<f32>{
         if (distance == 0.0) { dir = normalize(vec3<f32>(rand() - 0.5, rand() - 0.5, 0.0)); }
         else { dir = normalize(pos_vector); }
 
-        //Calculate pressure
-        let average_pressure = (density_to_pressure(p1_density) + density_to_pressure(p2_density)) / 2.0;
-        let average_near_pressure = (near_density_to_pressure(p1_near_density) + near_density_to_pressure(p2_near_density)) / 2.0;
+        //Calculate pressure, the iterative solvers correct for it after the forces instead
+        if pressure_is_force() {
+          let average_pressure = (density_to_pressure(p1_density) + density_to_pressure(p2_density)) / 2.0;
+          let average_near_pressure = (near_density_to_pressure(p1_near_density) + near_density_to_pressure(p2_near_density)) / 2.0;
+
+          pressure_force += dir * sim.particle_mass * average_pressure * d1_kernel(sim.pressure_kernel, distance, sim.pressure_kernel_radius) / p2_density;
+          pressure_force += dir * sim.particle_mass * average_near_pressure * d1_kernel(KERNEL_SPIKY, distance, sim.near_pressure_kernel_radius) / p2_near_density;
+        }
 
-        pressure_force += dir * sim.particle_mass * average_pressure * d1_spiky_2_kernel(distance, sim.pressure_kernel_radius) / p2_density;
-        pressure_force += dir * sim.particle_mass * average_near_pressure * d1_spiky_3_kernel(distance, sim.near_pressure_kernel_radius) / p2_near_density;
+        //Grains resist shearing through their deviatoric stress
+        if sim.material == MATERIAL_GRANULAR {
+          let average_stress = 0.5 * (p1_stress + stress_tensor(stress[id2]));
+          stress_force += average_stress * dir.xy * sim.particle_mass * d1_kernel(sim.pressure_kernel, distance, sim.pressure_kernel_radius) / p2_density;
+        }
 
         //Calculate viscosity
-        let visc = sim.viscosity * sim.particle_mass * vel_vector * viscosity_kernel(distance, sim.viscosity_kernel_radius) / p2_density;
+        let average_viscosity = (p1_viscosity + viscosity_field[id2]) / 2.0;
+        let visc = average_viscosity * sim.particle_mass * vel_vector * kernel(sim.viscosity_kernel, distance, sim.viscosity_kernel_radius) / p2_density;
         viscosity_force += visc;
 
         //Calculate surface tension forces
@@ -204,7 +299,7 @@ fn compute_accel(idx: u32) -> vec3<f32>{
         adhesion_force += dir * sim.adhesion_cef * pow(sim.particle_mass, 2.0) * adhesion_kernel(distance, sim.adhesion_kernel_radius);
 
         //Calculate corrective vorticity
-        let vort_grad = vec3f(vec2f(d1_spiky_2_kernel(distance, sim.vorticity_kernel_radius)), 0.0);
+        let vort_grad = vec3f(vec2f(d1_kernel(sim.vorticity_kernel, distance, sim.vorticity_kernel_radius)), 0.0);
         corrective_vorticity += sim.particle_mass * length(p2_vorticity) * vort_grad / p2_density;
       }
     }
@@ -215,7 +310,7 @@ fn compute_accel(idx: u32) -> vec3<f32>{
     vorticity_force = sim.vorticity_inensity * cross(normalize(corrective_vorticity), p1_vorticity);
   }
 
-  return (vorticity_force + viscosity_force + adhesion_force + surface_tension_force - pressure_force) / p1_density;
+  return (vorticity_force + viscosity_force + adhesion_force + surface_tension_force - pressure_force + vec3f(stress_force, 0.0)) / p1_density;
 }
 
 fn compute_collisions(particle: ptr<function, Particle>)  {
@@ -258,21 +353,18 @@ fn compute_density(@builtin(global_invocation_id) global_invocation_id : vec3u)
     for(var y = -1; y <= 1; y++) {
       let cur_pos = center + vec3i(x, y, 0); 
 
-      let hash = get_key_from_hash(z_order_hash(cur_pos.x, cur_pos.y));
-
-      var i = cell_start[hash];
-      for(; i < sim.particles_amount; i++) {
-        let cell = cell_hash[i];
-        if(cell != hash) { break; }
+      let hash = cell_key(cur_pos);
+      if hash == MAX_U32 { continue; }
 
+      for(var i = cell_start[hash]; i < cell_end[hash]; i++) {
         let id2 = particle_id[i];
         let p2_pos = predicted[id2].position;
 
         //Calulate density
         let distance = length(p2_pos - p1_pos);
 
-        density += sim.particle_mass * spiky_2_kernel(distance, sim.poly_kernel_radius);
-        near_density += sim.particle_mass * spiky_3_kernel(distance, sim.poly_kernel_radius);
+        density += sim.particle_mass * kernel(sim.density_kernel, distance, sim.poly_kernel_radius);
+        near_density += sim.particle_mass * kernel(KERNEL_SPIKY, distance, sim.poly_kernel_radius);
       }
     }
   }
@@ -291,6 +383,8 @@ fn compute_intermediate_values(@builtin(global_invocation_id) global_invocation_
 
   var surface_normal = vec3<f32>(0.0);
   var vorticity = vec3<f32>(0.0);
+  //Columns are the derivatives of the velocity along x and y
+  var velocity_gradient = mat2x2<f32>(vec2f(0.0), vec2f(0.0));
 
   let center = get_cell_coord(p1_pos);
   //Neighbour search
@@ -298,13 +392,10 @@ fn compute_intermediate_values(@builtin(global_invocation_id) global_invocation_
     for(var y = -1; y <= 1; y++) {
       let cur_pos = center + vec3i(x, y, 0); 
 
-      let hash = get_key_from_hash(z_order_hash(cur_pos.x, cur_pos.y));
-
-      var i = cell_start[hash];
-      for(; i < sim.particles_amount; i++) {
-        let cell = cell_hash[i];
-        if(cell != hash) { break; }
+      let hash = cell_key(cur_pos);
+      if hash == MAX_U32 { continue; }
 
+      for(var i = cell_start[hash]; i < cell_end[hash]; i++) {
         let id2 = particle_id[i];
         if (id2 == idx) { continue; }
 
@@ -319,20 +410,49 @@ fn compute_intermediate_values(@builtin(global_invocation_id) global_invocation_
         if distance != 0.0 { dir = normalize(pos_vector); }
 
         //Calulate surface normals
-        surface_normal += dir * sim.particle_mass * d1_poly_kernel(distance, sim.surface_normal_kernel_radius) / density_field[id2];
+        surface_normal += dir * sim.particle_mass * d1_kernel(sim.surface_normal_kernel, distance, sim.surface_normal_kernel_radius) / density_field[id2];
 
         //Calculate vorticity
-        let vort_grad = vec3f(vec2f(d1_spiky_2_kernel(distance, sim.vorticity_kernel_radius)), 0.0);
+        let vort_grad = vec3f(vec2f(d1_kernel(sim.vorticity_kernel, distance, sim.vorticity_kernel_radius)), 0.0);
         vorticity += -sim.particle_mass * cross(vel_vector, vort_grad) / density_field[id2];
+
+        //Calculate the velocity gradient in 1/s
+        let volume = sim.particle_mass / density_field[id2];
+        let kernel_gradient = dir.xy * d1_kernel(sim.viscosity_kernel, distance, sim.viscosity_kernel_radius);
+        let relative_velocity = vel_vector.xy / sim.pixels_per_metre;
+        velocity_gradient += volume * mat2x2<f32>(relative_velocity * kernel_gradient.x, relative_velocity * kernel_gradient.y);
       }
     }
   }
  
   surface_normals[idx] = surface_normal;
   vorticity_field[idx] = vorticity;
+
+  //Shear rate from the strain rate tensor, the symmetric part of the gradient
+  let strain_rate = 0.5 * (velocity_gradient + transpose(velocity_gradient));
+  let shear_rate = sqrt(2.0 * (dot(strain_rate[0], strain_rate[0]) + dot(strain_rate[1], strain_rate[1])));
+  viscosity_field[idx] = apparent_viscosity(shear_rate);
+
+  //Only grains keep a stress, fluids that turn granular start without one
+  if sim.material == MATERIAL_GRANULAR {
+    //Confining pressure, the near pressure balances the negative pressure of grains at rest
+    let pressure = density_to_pressure(density_field[idx]) + near_density_to_pressure(near_density_field[idx]);
+    stress[idx] = granular_stress(stress[idx], velocity_gradient, pressure);
+  } else {
+    stress[idx] = vec4f(0.0);
+  }
+}
+
+fn pressure_is_force() -> bool {
+  return sim.pressure_solver == SOLVER_LINEAR || sim.pressure_solver == SOLVER_TAIT;
 }
 
 fn density_to_pressure(density: f32) -> f32 {
+  if sim.pressure_solver == SOLVER_TAIT {
+    //Stiffness so that sound travels at the given speed through the fluid at rest
+    let stiffness = sim.rest_density * sim.speed_of_sound * sim.speed_of_sound / sim.tait_exponent;
+    return stiffness * (pow(max(density, 0.0) / sim.rest_density, sim.tait_exponent) - 1.0);
+  }
   return (density - sim.rest_density) * sim.pressure_multiplier;
 }
 
@@ -341,88 +461,241 @@ fn near_density_to_pressure(near_density: f32) -> f32 {
 }
 
 ///
-/// Kernels
+/// Materials
 ///
-fn spiky_2_kernel(dst: f32, h: f32) -> f32 {
-  let r = dst * sim.scene_scale_factor;
-  if r > h {
-    return 0.0;
+//Shear rate the models take at rest, the power law and Bingham viscosities diverge there
+const MIN_SHEAR_RATE: f32 = 1e-3;
+//Papanastasiou regularization of the Bingham model in s, larger is closer to a sharp yield
+const BINGHAM_REGULARIZATION: f32 = 100.0;
+
+fn apparent_viscosity(shear_rate: f32) -> f32 {
+  let rate = max(shear_rate, MIN_SHEAR_RATE);
+  switch sim.viscosity_model {
+    case VISCOSITY_POWER_LAW: {
+      return min(sim.viscosity * pow(rate, sim.flow_index - 1.0), sim.max_viscosity);
+    }
+    case VISCOSITY_CARREAU: {
+      let relaxed = sim.relaxation_time * shear_rate;
+      let thinning = pow(1.0 + relaxed * relaxed, (sim.flow_index - 1.0) / 2.0);
+      return min(sim.infinite_shear_viscosity + (sim.viscosity - sim.infinite_shear_viscosity) * thinning, sim.max_viscosity);
+    }
+    case VISCOSITY_BINGHAM: {
+      let yield_viscosity = sim.yield_stress * (1.0 - exp(-BINGHAM_REGULARIZATION * rate)) / rate;
+      return min(sim.viscosity + yield_viscosity, sim.max_viscosity);
+    }
+    default: {
+      return sim.viscosity;
+    }
   }
+}
 
-  let volume = radians(180.0)*h*h/6;
+//Clavet et al. 2005, springs form between neighbours within the viscosity kernel radius
+//and their rest lengths yield to stretching or compressing beyond spring_yield_ratio
+@compute @workgroup_size(64)
+fn update_springs(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+  let idx = global_invocation_id.x;
+  if(idx >= sim.particles_amount) { return; }
 
-  return pow(1-r/h,2.0)/ volume;
-}
+  let p1_pos = predicted[idx].position;
+  let h = sim.viscosity_kernel_radius;
+  let first = idx * MAX_SPRINGS;
+
+  //Plastic flow of the existing springs, the ones longer than the radius break
+  var free_slots = 0u;
+  for(var s = first; s < first + MAX_SPRINGS; s++) {
+    let spring = springs[s];
+    if spring.neighbour == 0u { free_slots++; continue; }
+
+    let current_length = distance(predicted[spring.neighbour - 1u].position, p1_pos) / sim.pixels_per_metre;
+    let tolerance = sim.spring_yield_ratio * spring.rest_length;
+    var rest_length = spring.rest_length;
+    if current_length > rest_length + tolerance {
+      rest_length += sim.time_step * sim.plasticity * (current_length - rest_length - tolerance);
+    } else if current_length < rest_length - tolerance {
+      rest_length -= sim.time_step * sim.plasticity * (rest_length - tolerance - current_length);
+    }
 
-fn spiky_3_kernel(dst: f32, h: f32) -> f32 {
-  let r = dst * sim.scene_scale_factor;
-  if r > h {
-    return 0.0;
+    if rest_length > h {
+      springs[s] = Spring(0u, 0.0);
+      free_slots++;
+    } else {
+      springs[s].rest_length = rest_length;
+    }
   }
 
-  let volume = radians(180.0)*h*h/10.0;
+  let center = get_cell_coord(p1_pos);
+  //Neighbour search
+  for(var x = -1; x <= 1; x++) {
+    for(var y = -1; y <= 1; y++) {
+      let cur_pos = center + vec3i(x, y, 0);
 
-  return pow(1-r/h,3.0) / volume;
-}
+      let hash = cell_key(cur_pos);
+      if hash == MAX_U32 { continue; }
 
-fn d1_spiky_2_kernel(dst: f32, h: f32) -> f32 {
-  let r = dst * sim.scene_scale_factor;
-  if r > h {
-    return 0.0;
-  }
+      for(var i = cell_start[hash]; i < cell_end[hash]; i++) {
+        if free_slots == 0u { return; }
 
-  let alpha = 12.0 / (radians(180.0)*pow(h, 4.0));
+        let id2 = particle_id[i];
+        let current_length = distance(predicted[id2].position, p1_pos) / sim.pixels_per_metre;
+        if (id2 == idx || current_length >= h) { continue; }
+
+        //Connect to neighbours without a spring yet, in the first free slot
+        var connected = false;
+        var free = MAX_U32;
+        for(var s = first; s < first + MAX_SPRINGS; s++) {
+          let neighbour = springs[s].neighbour;
+          if neighbour == id2 + 1u { connected = true; break; }
+          if neighbour == 0u && free == MAX_U32 { free = s; }
+        }
+        if !connected {
+          springs[free] = Spring(id2 + 1u, current_length);
+          free_slots--;
+        }
+      }
+    }
+  }
+}
 
-  return (h-r) * alpha;
+//Drucker–Prager yield surface under plane strain: the deviatoric stress holds up to
+//cohesion + friction * pressure, with both terms matched to the Mohr–Coulomb ones
+fn drucker_prager() -> vec2f {
+  let tan_friction = tan(radians(sim.friction_angle));
+  let scale = sqrt(9.0 + 12.0 * tan_friction * tan_friction);
+  return vec2f(3.0 * sim.granular_cohesion, 3.0 * tan_friction) / scale;
 }
 
-fn d1_spiky_3_kernel(dst: f32, h: f32) -> f32 {
-  let r = dst * sim.scene_scale_factor;
-  if r > h {
-    return 0.0;
+//Elastoplastic update of the deviatoric stress (Bui et al. 2008), the pressure comes from the
+//density as for fluids. The elastic trial stress is scaled back onto the yield surface
+fn granular_stress(old_stress: vec4f, velocity_gradient: mat2x2<f32>, pressure: f32) -> vec4f {
+  let strain_rate = vec3f(velocity_gradient[0][0], velocity_gradient[1][1], 0.5 * (velocity_gradient[1][0] + velocity_gradient[0][1]));
+  let spin = 0.5 * (velocity_gradient[1][0] - velocity_gradient[0][1]);
+  let volumetric_rate = strain_rate.x + strain_rate.y;
+
+  //Jaumann rate, so rotating grains keep their stress
+  let rate = vec4f(
+    2.0 * sim.shear_modulus * (strain_rate.x - volumetric_rate / 3.0) + 2.0 * old_stress.w * spin,
+    2.0 * sim.shear_modulus * (strain_rate.y - volumetric_rate / 3.0) - 2.0 * old_stress.w * spin,
+    2.0 * sim.shear_modulus * (-volumetric_rate / 3.0),
+    2.0 * sim.shear_modulus * strain_rate.z + (old_stress.y - old_stress.x) * spin
+  );
+  let trial = old_stress + sim.time_step * rate;
+
+  let yield_surface = drucker_prager();
+  let limit = max(yield_surface.x + yield_surface.y * pressure, 0.0);
+  let j2 = 0.5 * dot(trial.xyz, trial.xyz) + trial.w * trial.w;
+  if sqrt(j2) > limit {
+    return trial * limit / sqrt(j2);
   }
+  return trial;
+}
 
-  let alpha = 30.0 / (radians(180.0)*pow(h, 5.0));
-
-  return pow(h-r,2.0) * alpha;
+//In-plane part of the stress
+fn stress_tensor(components: vec4f) -> mat2x2<f32> {
+  return mat2x2<f32>(components.x, components.w, components.w, components.y);
 }
 
+//Acceleration in m/s² the springs of a particle pull it with
+fn spring_accel(idx: u32) -> vec3<f32> {
+  let p1_pos = predicted[idx].position;
+  let h = sim.viscosity_kernel_radius;
+  let first = idx * MAX_SPRINGS;
 
-fn poly_kernel(dst: f32, h: f32) -> f32 {
-  let r = dst * sim.scene_scale_factor;
-  if r > h {
-    return 0.0;
-  }
+  var force = vec3f(0.0);
+  for(var s = first; s < first + MAX_SPRINGS; s++) {
+    let spring = springs[s];
+    if spring.neighbour == 0u { continue; }
+
+    let pos_vector = predicted[spring.neighbour - 1u].position - p1_pos;
+    let current_length = length(pos_vector) / sim.pixels_per_metre;
+    if current_length == 0.0 { continue; }
 
-  let volume = radians(180.0) * pow(h, 8.0) / 4.0;
+    //Pulls towards the neighbour when stretched and pushes away when compressed
+    force += sim.spring_stiffness * (1.0 - spring.rest_length / h) * (current_length - spring.rest_length) * normalize(pos_vector);
+  }
 
-  return pow(h*h-r*r,3.0) / volume;
+  return force / sim.particle_mass;
 }
 
-fn d1_poly_kernel(dst: f32, h: f32) -> f32 {
-  let r = dst * sim.scene_scale_factor;
+///
+/// Kernels
+///
+//Ids of settings::kernel::Kernel
+const KERNEL_QUADRATIC_SPIKY: u32 = 0u;
+const KERNEL_CUBIC_SPLINE: u32 = 1u;
+const KERNEL_WENDLAND_C2: u32 = 2u;
+const KERNEL_WENDLAND_C4: u32 = 3u;
+const KERNEL_POLY6: u32 = 4u;
+const KERNEL_SPIKY: u32 = 5u;
+
+//The simulation is 2D, kernels are normalized for it
+const DIMENSIONS: u32 = 2u;
+const PI: f32 = 3.14159265358979;
+
+//Same as settings::kernel, which tests that they integrate to 1 and that the slopes match
+
+//W(r) for the distance in pixels and the support radius in metres
+fn kernel(kind: u32, dst: f32, h: f32) -> f32 {
+  let r = dst / sim.pixels_per_metre;
   if r > h {
     return 0.0;
   }
 
-  let volume = radians(180.0) * pow(h, 8.0) / 4.0;
-
-  return 6 * r * pow(h*h-r*r,2.0) / volume;
+  return kernel_normalization(kind) / pow(h, f32(DIMENSIONS)) * kernel_shape(kind, r / h);
 }
 
-fn viscosity_kernel(dst: f32, h: f32) -> f32 {
-  let r = dst * sim.scene_scale_factor;
+//How fast the kernel falls off, -dW/dr
+fn d1_kernel(kind: u32, dst: f32, h: f32) -> f32 {
+  let r = dst / sim.pixels_per_metre;
   if r > h {
     return 0.0;
   }
 
-  let volume = radians(180.0) * pow(h, 6.0) / 30.0;
+  return -kernel_normalization(kind) / pow(h, f32(DIMENSIONS + 1u)) * kernel_shape_derivative(kind, r / h);
+}
 
-  return (h-r) / volume;
+//Factor that makes the kernel integrate to 1, times h^dimensions
+fn kernel_normalization(kind: u32) -> f32 {
+  var normalization_2d = array<f32, 6>(6.0 / PI, 40.0 / (7.0 * PI), 7.0 / PI, 9.0 / PI, 4.0 / PI, 10.0 / PI);
+  var normalization_3d = array<f32, 6>(15.0 / (2.0 * PI), 8.0 / PI, 21.0 / (2.0 * PI), 495.0 / (32.0 * PI), 315.0 / (64.0 * PI), 15.0 / PI);
+  if DIMENSIONS == 2u {
+    return normalization_2d[kind];
+  }
+  return normalization_3d[kind];
 }
 
+//Unnormalized kernel over q = r / h
+fn kernel_shape(kind: u32, q: f32) -> f32 {
+  switch kind {
+    case KERNEL_CUBIC_SPLINE: {
+      if q <= 0.5 { return 6.0 * (q * q * q - q * q) + 1.0; }
+      return 2.0 * pow(1.0 - q, 3.0);
+    }
+    case KERNEL_WENDLAND_C2: { return pow(1.0 - q, 4.0) * (1.0 + 4.0 * q); }
+    case KERNEL_WENDLAND_C4: { return pow(1.0 - q, 6.0) * (1.0 + 6.0 * q + 35.0 / 3.0 * q * q); }
+    case KERNEL_POLY6: { return pow(1.0 - q * q, 3.0); }
+    case KERNEL_SPIKY: { return pow(1.0 - q, 3.0); }
+    default: { return pow(1.0 - q, 2.0); }
+  }
+}
+
+//Derivative of the shape with respect to q
+fn kernel_shape_derivative(kind: u32, q: f32) -> f32 {
+  switch kind {
+    case KERNEL_CUBIC_SPLINE: {
+      if q <= 0.5 { return 6.0 * (3.0 * q * q - 2.0 * q); }
+      return -6.0 * pow(1.0 - q, 2.0);
+    }
+    case KERNEL_WENDLAND_C2: { return -20.0 * q * pow(1.0 - q, 3.0); }
+    case KERNEL_WENDLAND_C4: { return -56.0 / 3.0 * q * (1.0 + 5.0 * q) * pow(1.0 - q, 5.0); }
+    case KERNEL_POLY6: { return -6.0 * q * pow(1.0 - q * q, 2.0); }
+    case KERNEL_SPIKY: { return -3.0 * pow(1.0 - q, 2.0); }
+    default: { return -2.0 * (1.0 - q); }
+  }
+}
+
+//Surface tension kernels of Akinci et al. 2013, not smoothing kernels so they aren't selectable
 fn cohesion_kernel(dst: f32, h: f32) -> f32 {
-  let r = dst * sim.scene_scale_factor;
+  let r = dst / sim.pixels_per_metre;
 
   let k = 32 / (radians(180.0) * pow(h, 9.0));
   let fun = pow(h-r,3.0) * pow(r, 3.0);
@@ -438,7 +711,7 @@ fn cohesion_kernel(dst: f32, h: f32) -> f32 {
 }
 
 fn adhesion_kernel(dst: f32, h: f32) -> f32 {
-  let r = dst * sim.scene_scale_factor;
+  let r = dst / sim.pixels_per_metre;
 
   let k = 0.007 / pow(h, 3.25);
   let e = 0.001;
@@ -454,9 +727,10 @@ fn adhesion_kernel(dst: f32, h: f32) -> f32 {
 const B: array<u32, 4> = array<u32, 4>(0x55555555, 0x33333333, 0x0F0F0F0F, 0x00FF00FF);
 const S: array<u32, 4> = array<u32, 4>(1, 2, 4, 8);
 
+//Cells within 32768 of the origin on either side keep distinct codes, negative coordinates included
 fn z_order_hash(x_in: i32, y_in: i32) -> u32 {
-    var x = u32(x_in);
-    var y = u32(y_in);
+    var x = u32(x_in + 32768) & 0xFFFFu;
+    var y = u32(y_in + 32768) & 0xFFFFu;
 
     x = (x | (x << S[3])) & B[3];
     x = (x | (x << S[2])) & B[2];
@@ -471,12 +745,24 @@ fn z_order_hash(x_in: i32, y_in: i32) -> u32 {
     return x | (y << 1);
 }
 
+//Dense grids keep particles that left the box in its border cells
 fn get_cell_coord(pos: vec3f) -> vec3i {
-    return vec3i((pos * sim.scene_scale_factor) / sim.grid_size);
+    let cell = vec3i(floor((pos / sim.pixels_per_metre) / sim.grid_size));
+    if sim.neighbour_search != SEARCH_DENSE { return cell; }
+
+    let last = neighbour_grid.origin + vec2i(neighbour_grid.size) - 1;
+    return vec3i(clamp(cell.xy, neighbour_grid.origin, last), cell.z);
 }
 
-fn get_key_from_hash(hash: u32) -> u32 {
-    return hash % sim.particles_amount;
+//Bucket of a cell, MAX_U32 for cells outside of the dense grid.
+//The hash table keeps the low bits of the z-order code, so only cells far apart share a bucket
+fn cell_key(cell: vec3i) -> u32 {
+    if sim.neighbour_search == SEARCH_DENSE {
+        let local = cell.xy - neighbour_grid.origin;
+        if any(local < vec2i(0)) || any(local >= vec2i(neighbour_grid.size)) { return MAX_U32; }
+        return u32(local.y) * neighbour_grid.size.x + u32(local.x);
+    }
+    return z_order_hash(cell.x, cell.y) & (sim.hash_table_size - 1u);
 }
 
 
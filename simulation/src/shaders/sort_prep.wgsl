@@ -2,6 +2,8 @@ const MAX_U32: u32 = 0xFFFFFFFF;
 
 struct Particle {
     position: vec3<f32>,
+    //Index at creation, kept when the particles are reordered
+    id: u32,
     velocity: vec3<f32>,
     color: vec4<f32>
 }
@@ -30,16 +32,58 @@ struct SimulationParameters {
   pressure_multiplier: f32,
   near_pressure_multiplier: f32,
   grid_size: f32,
-  scene_scale_factor: f32,
+  pixels_per_metre: f32,
   vorticity_kernel_radius: f32,
   vorticity_inensity: f32,
   cohesion_kernel_radius: f32,
   adhesion_kernel_radius: f32,
   surface_normal_kernel_radius: f32,
   time_step: f32,
-  velocity_smoothing_scale: f32
+  velocity_smoothing_scale: f32,
+  pressure_solver: u32,
+  solver_iterations: u32,
+  speed_of_sound: f32,
+  tait_exponent: f32,
+  pbf_relaxation: f32,
+  pbf_tensile_coef: f32,
+  density_kernel: u32,
+  pressure_kernel: u32,
+  viscosity_kernel: u32,
+  surface_normal_kernel: u32,
+  vorticity_kernel: u32,
+  smoothing_kernel: u32,
+  viscosity_model: u32,
+  flow_index: f32,
+  infinite_shear_viscosity: f32,
+  relaxation_time: f32,
+  yield_stress: f32,
+  max_viscosity: f32,
+  spring_stiffness: f32,
+  spring_yield_ratio: f32,
+  plasticity: f32,
+  material: u32,
+  friction_angle: f32,
+  granular_cohesion: f32,
+  shear_modulus: f32,
+  simulation_method: u32,
+  flip_ratio: f32,
+  flip_cell_size: f32,
+  projection_iterations: u32,
+  neighbour_search: u32,
+  hash_table_size: u32,
+  neighbour_sort: u32
 }
 
+//Cell range of the dense neighbour search, NeighbourGridUniform in particle.rs
+struct NeighbourGrid {
+  origin: vec2<i32>,
+  size: vec2<u32>
+}
+
+//Ids of settings::neighbour::NeighbourSearch
+const SEARCH_HASHED: u32 = 0u;
+const SEARCH_DENSE: u32 = 1u;
+
 struct Predicted {
     position: vec3<f32>,
     velocity: vec3<f32>
@@ -51,6 +95,21 @@ struct Predicted {
 @group(3) @binding(0) var<storage, read_write> cell_hash : array<u32>;
 @group(3) @binding(1) var<storage, read_write> particle_id : array<u32>;
 @group(3) @binding(2) var<storage, read_write> cell_start : array<u32>;
+//Occupied buckets, colliding particles and the fullest bucket
+@group(3) @binding(3) var<storage, read_write> neighbour_stats : array<atomic<u32>>;
+@group(3) @binding(4) var<uniform> neighbour_grid: NeighbourGrid;
+//One past the last particle of every bucket
+@group(3) @binding(5) var<storage, read_write> cell_end : array<u32>;
+
+//Mark every bucket empty, the table can be larger than the particle count
+@compute @workgroup_size(64)
+fn clearCellStart(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+    let idx = global_invocation_id.x;
+    if(idx >= arrayLength(&cell_start)) { return; }
+
+    cell_start[idx] = MAX_U32;
+    cell_end[idx] = 0u;
+}
 
 @compute @workgroup_size(64)
 fn calcHash(@builtin(global_invocation_id) global_invocation_id : vec3u) {
@@ -58,10 +117,8 @@ fn calcHash(@builtin(global_invocation_id) global_invocation_id : vec3u) {
     if(idx >= sim.particles_amount) { return; }
 
     let pos = get_cell_coord(predicted[idx].position);
-    let hash = get_key_from_hash(z_order_hash(pos.x, pos.y));
-    cell_hash[idx] = hash;
+    cell_hash[idx] = cell_key(pos);
     particle_id[idx] = idx;
-    cell_start[idx] = MAX_U32; 
 }
 
 @compute @workgroup_size(64)
@@ -80,14 +137,37 @@ fn findCellStart(@builtin(global_invocation_id) global_invocation_id : vec3u) {
     if( key != key_prev ) {
         cell_start[key] = idx;
     }
+    if( idx == sim.particles_amount - 1u || cell_hash[idx + 1u] != key ) {
+        cell_end[key] = idx + 1u;
+    }
+}
+
+//Count the occupied buckets, the particles sharing a bucket with another cell and the fullest bucket
+@compute @workgroup_size(64)
+fn hashStats(@builtin(global_invocation_id) global_invocation_id : vec3u) {
+    let idx = global_invocation_id.x;
+    if(idx >= sim.particles_amount) { return; }
+
+    let start = cell_start[cell_hash[idx]];
+    if( idx == start ) {
+        atomicAdd(&neighbour_stats[0], 1u);
+    }
+    atomicMax(&neighbour_stats[2], idx - start + 1u);
+
+    let cell = get_cell_coord(predicted[particle_id[idx]].position);
+    let first_cell = get_cell_coord(predicted[particle_id[start]].position);
+    if( any(cell.xy != first_cell.xy) ) {
+        atomicAdd(&neighbour_stats[1], 1u);
+    }
 }
 
 const B: array<u32, 4> = array<u32, 4>(0x55555555, 0x33333333, 0x0F0F0F0F, 0x00FF00FF);
 const S: array<u32, 4> = array<u32, 4>(1, 2, 4, 8);
 
+//Cells within 32768 of the origin on either side keep distinct codes, negative coordinates included
 fn z_order_hash(x_in: i32, y_in: i32) -> u32 {
-    var x = u32(x_in);
-    var y = u32(y_in);
+    var x = u32(x_in + 32768) & 0xFFFFu;
+    var y = u32(y_in + 32768) & 0xFFFFu;
 
     x = (x | (x << S[3])) & B[3];
     x = (x | (x << S[2])) & B[2];
@@ -102,10 +182,22 @@ fn z_order_hash(x_in: i32, y_in: i32) -> u32 {
     return x | (y << 1);
 }
 
+//Dense grids keep particles that left the box in its border cells
 fn get_cell_coord(pos: vec3f) -> vec3i {
-    return vec3i((pos * sim.scene_scale_factor) / sim.grid_size);
+    let cell = vec3i(floor((pos / sim.pixels_per_metre) / sim.grid_size));
+    if sim.neighbour_search != SEARCH_DENSE { return cell; }
+
+    let last = neighbour_grid.origin + vec2i(neighbour_grid.size) - 1;
+    return vec3i(clamp(cell.xy, neighbour_grid.origin, last), cell.z);
 }
 
-fn get_key_from_hash(hash: u32) -> u32 {
-    return hash % sim.particles_amount;
-}
\ No newline at end of file
+//Bucket of a cell, MAX_U32 for cells outside of the dense grid.
+//The hash table keeps the low bits of the z-order code, so only cells far apart share a bucket
+fn cell_key(cell: vec3i) -> u32 {
+    if sim.neighbour_search == SEARCH_DENSE {
+        let local = cell.xy - neighbour_grid.origin;
+        if any(local < vec2i(0)) || any(local >= vec2i(neighbour_grid.size)) { return MAX_U32; }
+        return u32(local.y) * neighbour_grid.size.x + u32(local.x);
+    }
+    return z_order_hash(cell.x, cell.y) & (sim.hash_table_size - 1u);
+}
@@ -0,0 +1,187 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use serde::{Deserialize, Serialize};
+use settings::timeline::Timeline;
+
+use crate::cli::SnapshotArgs;
+use crate::flip::FlipState;
+use crate::particle::ParticlesState;
+use crate::readback::read_buffer;
+use crate::remote::TIMELINE;
+
+const MAGIC: &[u8; 8] = b"WSIMSNAP";
+//Bump whenever the layout of the snapshot or of any GPU buffer changes
+pub const VERSION: u32 = 13;
+
+//Complete simulation state. GPU buffers are stored as raw bytes
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub parameters: settings::SimulationParameters,
+    //Played back on the simulated time, so it continues where it was
+    pub timeline: Timeline,
+    pub frame: u64,
+    pub time: f64,
+    pub seed: u32,
+    pub particles: Vec<u8>,
+    pub predicted: Vec<u8>,
+    pub density: Vec<u8>,
+    pub near_density: Vec<u8>,
+    pub surface_normals: Vec<u8>,
+    pub vorticity: Vec<u8>,
+    pub springs: Vec<u8>,
+    pub stress: Vec<u8>,
+    pub affine: Vec<u8>,
+    //Pressure of the FLIP grid, empty if it wasn't used
+    pub flip_pressure: Vec<u8>
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(std::io::Error),
+    Encoding(bincode::Error),
+    NotASnapshot,
+    UnsupportedVersion(u32)
+}
+
+impl std::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotError::Io(err) => write!(f, "{err}"),
+            SnapshotError::Encoding(err) => write!(f, "{err}"),
+            SnapshotError::NotASnapshot => write!(f, "not a simulation snapshot"),
+            SnapshotError::UnsupportedVersion(version) => write!(f, "unsupported snapshot version {version}, expected {VERSION}")
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<std::io::Error> for SnapshotError {
+    fn from(err: std::io::Error) -> Self {
+        SnapshotError::Io(err)
+    }
+}
+
+impl From<bincode::Error> for SnapshotError {
+    fn from(err: bincode::Error) -> Self {
+        SnapshotError::Encoding(err)
+    }
+}
+
+impl Snapshot {
+    pub fn capture(device: &wgpu::Device, queue: &wgpu::Queue, particles_state: &ParticlesState, flip_state: &FlipState, frame: u64, time: f64, seed: u32) -> Self {
+        let read = |buffer| read_buffer::<u8>(device, queue, buffer);
+        let parameters = *crate::uniforms::parameters::SIMULATION_PARAMETERS.lock().unwrap();
+
+        Snapshot {
+            parameters,
+            timeline: TIMELINE.lock().unwrap().clone(),
+            frame,
+            time,
+            seed,
+            particles: read(&particles_state.particles_buffer),
+            predicted: read(&particles_state.predicted_buffer),
+            density: read(&particles_state.density_field_buffer),
+            near_density: read(&particles_state.near_density_field_buffer),
+            surface_normals: read(&particles_state.surface_normals_buffer),
+            vorticity: read(&particles_state.vorticity_buffer),
+            springs: read(&particles_state.springs_buffer),
+            stress: read(&particles_state.stress_buffer),
+            affine: read(&particles_state.affine_buffer),
+            flip_pressure: flip_state.pressure(device, queue)
+        }
+    }
+
+    //Buffers have to be created for the snapshot's parameters
+    pub fn restore(&self, device: &wgpu::Device, queue: &wgpu::Queue, particles_state: &ParticlesState, flip_state: &mut FlipState) {
+        queue.write_buffer(&particles_state.particles_buffer, 0, &self.particles);
+        queue.write_buffer(&particles_state.predicted_buffer, 0, &self.predicted);
+        queue.write_buffer(&particles_state.density_field_buffer, 0, &self.density);
+        queue.write_buffer(&particles_state.near_density_field_buffer, 0, &self.near_density);
+        queue.write_buffer(&particles_state.surface_normals_buffer, 0, &self.surface_normals);
+        queue.write_buffer(&particles_state.vorticity_buffer, 0, &self.vorticity);
+        queue.write_buffer(&particles_state.springs_buffer, 0, &self.springs);
+        queue.write_buffer(&particles_state.stress_buffer, 0, &self.stress);
+        queue.write_buffer(&particles_state.affine_buffer, 0, &self.affine);
+        flip_state.restore_pressure(device, queue, &self.parameters, particles_state, &self.flip_pressure);
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SnapshotError> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(MAGIC)?;
+        file.write_all(&VERSION.to_le_bytes())?;
+
+        let mut encoder = DeflateEncoder::new(file, flate2::Compression::fast());
+        bincode::serialize_into(&mut encoder, self)?;
+        encoder.finish()?.flush()?;
+
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SnapshotError> {
+        let mut file = BufReader::new(File::open(path)?);
+
+        let mut magic = [0u8; 8];
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(SnapshotError::NotASnapshot);
+        }
+
+        let mut version = [0u8; 4];
+        file.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        if version != VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        Ok(bincode::deserialize_from(DeflateDecoder::new(file))?)
+    }
+}
+
+pub struct SnapshotState {
+    dir: PathBuf,
+    every: Option<u32>,
+    requested: bool,
+    //Paused simulations stay on the same frame, which is only saved once
+    last_frame: Option<u64>
+}
+
+impl SnapshotState {
+    pub fn new(args: &SnapshotArgs) -> Self {
+        if let Err(err) = std::fs::create_dir_all(&args.snapshot_dir) {
+            log::error!("Failed to create snapshot directory {}: {err}", args.snapshot_dir.display());
+        }
+
+        SnapshotState {
+            dir: args.snapshot_dir.clone(),
+            every: args.snapshot_every.map(|every| every.max(1)),
+            requested: false,
+            last_frame: None
+        }
+    }
+
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+
+    //Saves the snapshot capture takes when one is due or requested
+    pub fn update(&mut self, frame: u64, capture: impl FnOnce() -> Snapshot) {
+        let due = self.every.is_some_and(|every| frame.is_multiple_of(every as u64)) && self.last_frame != Some(frame);
+        if !due && !self.requested {
+            return;
+        }
+        self.requested = false;
+        self.last_frame = Some(frame);
+
+        let snapshot = capture();
+        let path = self.dir.join(format!("snapshot_{frame:06}.wsim"));
+        match snapshot.save(&path) {
+            Ok(_) => log::info!("Saved snapshot to {}", path.display()),
+            Err(err) => log::error!("Failed to save snapshot to {}: {err}", path.display())
+        }
+    }
+}
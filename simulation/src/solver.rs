@@ -0,0 +1,113 @@
+use settings::solver::PressureSolver;
+use settings::SimulationParameters;
+
+//Source of the simulation module, the iterative solvers are appended to the shared passes
+pub const SHADER_SOURCE: &str = concat!(
+    include_str!("shaders/simulation.wgsl"),
+    include_str!("shaders/pbf.wgsl"),
+    include_str!("shaders/dfsph.wgsl")
+);
+
+//Encodes a pass of a pipeline over all particles with the simulation's bind groups
+pub type Dispatch<'a> = dyn Fn(&mut wgpu::CommandEncoder, &wgpu::ComputePipeline) + 'a;
+
+//Keeps the fluid from compressing. Runs on the neighbour search of the step, after the
+//forces pass set the velocities and before the positions are updated from them
+pub trait Solver {
+    fn solve(&self, encoder: &mut wgpu::CommandEncoder, dispatch: &Dispatch, iterations: u32);
+}
+
+//Linear and Tait pressure are forces, the forces pass already applied them
+struct ForceBased;
+
+impl Solver for ForceBased {
+    fn solve(&self, _encoder: &mut wgpu::CommandEncoder, _dispatch: &Dispatch, _iterations: u32) {}
+}
+
+struct PositionBased {
+    predict_pipeline: wgpu::ComputePipeline,
+    lambda_pipeline: wgpu::ComputePipeline,
+    delta_pipeline: wgpu::ComputePipeline,
+    apply_pipeline: wgpu::ComputePipeline,
+    velocity_pipeline: wgpu::ComputePipeline
+}
+
+impl Solver for PositionBased {
+    fn solve(&self, encoder: &mut wgpu::CommandEncoder, dispatch: &Dispatch, iterations: u32) {
+        dispatch(encoder, &self.predict_pipeline);
+        for _ in 0..iterations {
+            dispatch(encoder, &self.lambda_pipeline);
+            dispatch(encoder, &self.delta_pipeline);
+            dispatch(encoder, &self.apply_pipeline);
+        }
+        dispatch(encoder, &self.velocity_pipeline);
+    }
+}
+
+struct DivergenceFree {
+    factor_pipeline: wgpu::ComputePipeline,
+    density_error_pipeline: wgpu::ComputePipeline,
+    divergence_error_pipeline: wgpu::ComputePipeline,
+    correct_pipeline: wgpu::ComputePipeline
+}
+
+impl Solver for DivergenceFree {
+    fn solve(&self, encoder: &mut wgpu::CommandEncoder, dispatch: &Dispatch, iterations: u32) {
+        dispatch(encoder, &self.factor_pipeline);
+        for _ in 0..iterations {
+            dispatch(encoder, &self.density_error_pipeline);
+            dispatch(encoder, &self.correct_pipeline);
+        }
+        for _ in 0..iterations {
+            dispatch(encoder, &self.divergence_error_pipeline);
+            dispatch(encoder, &self.correct_pipeline);
+        }
+    }
+}
+
+//Every solver is built up front so scenes and the UI can switch between them while running
+pub struct SolverState {
+    force_based: ForceBased,
+    position_based: PositionBased,
+    divergence_free: DivergenceFree
+}
+
+impl SolverState {
+    pub fn new(device: &wgpu::Device, layout: &wgpu::PipelineLayout, module: &wgpu::ShaderModule) -> Self {
+        let pipeline = |entry_point: &str| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(entry_point),
+            layout: Some(layout),
+            module,
+            entry_point
+        });
+
+        SolverState {
+            force_based: ForceBased,
+            position_based: PositionBased {
+                predict_pipeline: pipeline("pbf_predict"),
+                lambda_pipeline: pipeline("pbf_lambda"),
+                delta_pipeline: pipeline("pbf_delta"),
+                apply_pipeline: pipeline("pbf_apply"),
+                velocity_pipeline: pipeline("pbf_velocity")
+            },
+            divergence_free: DivergenceFree {
+                factor_pipeline: pipeline("dfsph_factor"),
+                density_error_pipeline: pipeline("dfsph_density_error"),
+                divergence_error_pipeline: pipeline("dfsph_divergence_error"),
+                correct_pipeline: pipeline("dfsph_correct")
+            }
+        }
+    }
+
+    fn solver(&self, solver: PressureSolver) -> &dyn Solver {
+        match solver {
+            PressureSolver::Linear | PressureSolver::Tait => &self.force_based,
+            PressureSolver::Pbf => &self.position_based,
+            PressureSolver::Dfsph => &self.divergence_free
+        }
+    }
+
+    pub fn solve(&self, sim: &SimulationParameters, encoder: &mut wgpu::CommandEncoder, dispatch: &Dispatch) {
+        self.solver(sim.pressure_solver()).solve(encoder, dispatch, sim.solver_iterations.max(1));
+    }
+}
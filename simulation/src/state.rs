@@ -1,22 +1,43 @@
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use winit::window::Window;
 use winit::event:: WindowEvent;
-
-use crate::particle::NeighbourSearchSortState;
+use winit::event::{ElementState, KeyEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+use settings::material::ViscosityModel;
+use settings::neighbour::NeighbourSort;
+use settings::protocol::Command;
+use settings::SimulationParameters;
+
+use crate::capture::CaptureState;
+use crate::cli::Args;
+use crate::flip::FlipState;
+
+use crate::particle::{NeighbourSearchGridState, NeighbourSearchSortState};
+use crate::overlay::OverlayState;
+use crate::particle_export::ExportState;
+use crate::remote::{self, COMMANDS, TIMELINE};
+use crate::reorder::ReorderState;
+use crate::script::ScriptState;
+use crate::snapshot::{Snapshot, SnapshotState};
+use crate::solver::{self, SolverState};
 use crate::particle::ParticlesState;
 use crate::particle::{Particle, ParticleRaw};
 use crate::uniforms::parameters::SIMULATION_PARAMETERS;
 use crate::uniforms::UniformState;
 use crate::vertex::*;
 use crate::geometry;
+use crate::surface::SurfaceState;
+use crate::telemetry::TelemetryState;
 
 pub struct State {
-    pub surface: wgpu::Surface<'static>,
+    //Surface is missing in headless mode
+    pub surface: Option<wgpu::Surface<'static>>,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
     pub size: winit::dpi::PhysicalSize<u32>,
-    pub window: Arc<Window>,
     uniform_state: UniformState,
     render_pipeline: wgpu::RenderPipeline,
     particles_state: ParticlesState,
@@ -24,31 +45,55 @@ pub struct State {
     forces_pipeline: wgpu::ComputePipeline,
     d_pipeline: wgpu::ComputePipeline,
     sort_state: NeighbourSearchSortState,
+    //Only created when asked for
+    reorder_state: Option<ReorderState>,
+    clear_cell_start_pipeline: wgpu::ComputePipeline,
     calc_hash_pipeline: wgpu::ComputePipeline,
     cell_start_pipeline: wgpu::ComputePipeline,
+    hash_stats_pipeline: wgpu::ComputePipeline,
     pre_pos_pipeline: wgpu::ComputePipeline,
     sn_pipeline: wgpu::ComputePipeline,
-    move_pipeline: wgpu::ComputePipeline
+    move_pipeline: wgpu::ComputePipeline,
+    springs_pipeline: wgpu::ComputePipeline,
+    solver_state: SolverState,
+    flip_state: FlipState,
+    surface_state: SurfaceState,
+    capture_state: CaptureState,
+    export_state: ExportState,
+    snapshot_state: SnapshotState,
+    telemetry_state: TelemetryState,
+    //Only created for a window
+    overlay_state: Option<OverlayState>,
+    pub script_state: Option<ScriptState>,
+    //Whether the last parameters had to be clamped, so it's only logged once
+    parameters_clamped: bool,
+    //Whether the springs buffer holds the springs of the last step
+    springs_active: bool,
+    //Whether the last step was FLIP or APIC, so the velocity gradients belong to the particles
+    grid_active: bool,
+    paused: bool,
+    step_requested: bool,
+    frame: u64,
+    //Simulated time in seconds
+    time: f64
 }
 
 impl State {
-    pub async fn new(window: Arc<Window>) -> Self {
+    pub async fn new(window: Option<Arc<Window>>, size: winit::dpi::PhysicalSize<u32>, args: &Args) -> Self {
         //
         // Start of window surface configuration
         //
-        let size = window.inner_size();
-
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
             backends: wgpu::Backends::all(),
             ..Default::default()
         });
         
-        let surface = instance.create_surface(window.clone()).unwrap();
+        let surface = window.clone().map(|window| instance.create_surface(window).unwrap());
 
         let adapter = instance.request_adapter(
             &wgpu::RequestAdapterOptions {
                 power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
+                compatible_surface: surface.as_ref(),
                 force_fallback_adapter: false,
             },
         ).await.unwrap();
@@ -62,25 +107,30 @@ impl State {
             None, // Trace path
         ).await.unwrap();
 
-        let surface_caps = surface.get_capabilities(&adapter);
-
-        let surface_format = surface_caps.formats.iter()
-            .copied()
-            .find(|f| f.is_srgb())
-            .unwrap_or(surface_caps.formats[0]);
-
-        let config = wgpu::SurfaceConfiguration {
+        //Headless mode renders into an offscreen texture of this format
+        let mut config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
             width: size.width,
             height: size.height,
-            present_mode: surface_caps.present_modes[0],
+            present_mode: wgpu::PresentMode::Fifo,
             desired_maximum_frame_latency: 2,
-            alpha_mode: surface_caps.alpha_modes[0],
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
             view_formats: vec![],
         };
 
-        surface.configure(&device, &config);
+        if let Some(surface) = &surface {
+            let surface_caps = surface.get_capabilities(&adapter);
+
+            config.format = surface_caps.formats.iter()
+                .copied()
+                .find(|f| f.is_srgb())
+                .unwrap_or(surface_caps.formats[0]);
+            config.present_mode = surface_caps.present_modes[0];
+            config.alpha_mode = surface_caps.alpha_modes[0];
+
+            surface.configure(&device, &config);
+        }
 
         //
         // End of window surface configuration
@@ -88,10 +138,10 @@ impl State {
 
         let particles_state = ParticlesState::new(&device);
         let circle_mesh_buffer = Particle::circle_mesh().into_buffer(&device);
-        let uniform_state = UniformState::new(&device, &size);
+        let uniform_state = UniformState::new(&device, &size, args.seed.unwrap_or_default());
 
-        let subgroup_size = wgpu_sort::utils::guess_workgroup_size(&device, &queue).await.unwrap();
-        let sort_state = NeighbourSearchSortState::new(&device, subgroup_size);
+        let sort_state = NeighbourSearchSortState::new(&device, args.sort_subgroup_size);
+        let reorder_state = args.reorder_particles.then(|| ReorderState::new(&device, &particles_state, &sort_state.grid_state));
 
         //
         // Render pipeline
@@ -154,7 +204,7 @@ impl State {
         //
         let simulate_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Simulation Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/simulation.wgsl").into()),
+            source: wgpu::ShaderSource::Wgsl(solver::SHADER_SOURCE.into()),
         });
 
         let compute_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor 
@@ -205,6 +255,16 @@ impl State {
             entry_point: "compute_intermediate_values"
         });
 
+        let springs_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor{
+            label: Some("Viscoelastic springs"),
+            layout: Some(&compute_pipeline_layout),
+            module: &simulate_shader,
+            entry_point: "update_springs"
+        });
+
+        let solver_state = SolverState::new(&device, &compute_pipeline_layout, &simulate_shader);
+        let flip_state = FlipState::new(&device, &particles_state, &uniform_state.bind_group_layout);
+
         //
         // Pipeline to prepare resources for the sort
         //
@@ -224,6 +284,13 @@ impl State {
             push_constant_ranges: &[]
         });
 
+        let clear_cell_start_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor{
+            label: Some("Clear cell start pipeline"),
+            layout: Some(&sort_prep_pipeline_layout),
+            module: &sort_prep_shader,
+            entry_point: "clearCellStart"
+        });
+
         let calc_hash_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor{
             label: Some("Sort preperation pipeline"),
             layout: Some(&sort_prep_pipeline_layout),
@@ -238,8 +305,20 @@ impl State {
             entry_point: "findCellStart"
         });
 
+        let hash_stats_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor{
+            label: Some("Neighbour search statistics pipeline"),
+            layout: Some(&sort_prep_pipeline_layout),
+            module: &sort_prep_shader,
+            entry_point: "hashStats"
+        });
+
+        let surface_state = SurfaceState::new(&device, config.format, &uniform_state.bind_group_layout, &args.surface);
+        let capture_state = CaptureState::new(&device, config.format, size.width, size.height, &args.capture);
+        let export_state = ExportState::new(&args.export);
+        let snapshot_state = SnapshotState::new(&args.snapshot);
+        let overlay_state = window.map(|window| OverlayState::new(&device, config.format, window, args.overlay));
+
         Self {
-            window,
             surface,
             device,
             queue,
@@ -252,16 +331,50 @@ impl State {
             forces_pipeline,
             d_pipeline,
             sort_state,
+            reorder_state,
+            clear_cell_start_pipeline,
             calc_hash_pipeline,
             cell_start_pipeline,
+            hash_stats_pipeline,
             pre_pos_pipeline,
             sn_pipeline,
-            move_pipeline
+            move_pipeline,
+            springs_pipeline,
+            solver_state,
+            flip_state,
+            surface_state,
+            capture_state,
+            export_state,
+            snapshot_state,
+            telemetry_state: TelemetryState::new(),
+            overlay_state,
+            script_state: None,
+            parameters_clamped: false,
+            springs_active: false,
+            grid_active: false,
+            paused: false,
+            step_requested: false,
+            frame: 0,
+            time: 0.0
         }
     }
 
-    pub fn window(&self) -> &Window {
-        &self.window
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    //Continue from a snapshot. Buffers must have been created with the snapshot's parameters
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        if snapshot.particles.len() as u64 != self.particles_state.particles_buffer.size() {
+            log::error!("Snapshot doesn't match the particle buffers, ignoring it");
+            return;
+        }
+
+        snapshot.restore(&self.device, &self.queue, &self.particles_state, &mut self.flip_state);
+        self.springs_active = snapshot.parameters.viscosity_model() == ViscosityModel::Viscoelastic;
+        self.grid_active = snapshot.parameters.simulation_method().is_grid_based();
+        self.frame = snapshot.frame;
+        self.time = snapshot.time;
     }
 
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
@@ -269,87 +382,307 @@ impl State {
             self.size = new_size;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
+            if let Some(surface) = &self.surface {
+                surface.configure(&self.device, &self.config);
+            }
+            self.capture_state.resize(&self.device, new_size.width, new_size.height);
         }
     }
 
-    pub fn input(&mut self, _event: &WindowEvent) -> bool {
-        false
+    pub fn input(&mut self, event: &WindowEvent) -> bool {
+        if self.overlay_state.as_mut().is_some_and(|overlay| overlay.input(event)) {
+            return true;
+        }
+
+        match event {
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state: ElementState::Pressed,
+                        physical_key: PhysicalKey::Code(KeyCode::KeyM),
+                        ..
+                    },
+                ..
+            } => {
+                self.surface_state.render = !self.surface_state.render;
+                true
+            },
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state: ElementState::Pressed,
+                        physical_key: PhysicalKey::Code(KeyCode::F12),
+                        ..
+                    },
+                ..
+            } => {
+                self.capture_state.request_screenshot();
+                true
+            },
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state: ElementState::Pressed,
+                        physical_key: PhysicalKey::Code(KeyCode::F5),
+                        ..
+                    },
+                ..
+            } => {
+                self.snapshot_state.request();
+                true
+            },
+            _ => false
+        }
     }
 
     pub fn update(&mut self) {
-        self.uniform_state.update(&self.queue);
-        self.queue.write_buffer(&self.uniform_state.simulation_parameters.buffer, 0, bytemuck::cast_slice(&[*SIMULATION_PARAMETERS.lock().unwrap()]));
+        //The timeline and scripts only run for steps that are about to happen, commands scripts issue apply right away.
+        //Scripts go last so they can override keyframed values
+        if !self.paused || self.step_requested {
+            let mut sim = SIMULATION_PARAMETERS.lock().unwrap();
+            TIMELINE.lock().unwrap().apply(&mut sim, self.time);
+            drop(sim);
+
+            if let Some(script) = &mut self.script_state {
+                script.run(self.frame, self.time);
+            }
+        }
+
+        let commands: Vec<Command> = COMMANDS.lock().unwrap().drain(..).collect();
+        for command in commands {
+            self.apply_command(command);
+        }
+
+        //Remote changes are refused when invalid, keyframes and scripts are clamped instead
+        let mut sim = SIMULATION_PARAMETERS.lock().unwrap();
+        let validation = sim.validate();
+        if !validation.is_valid() {
+            if !self.parameters_clamped {
+                log::error!("Invalid parameters, clamping: {}", validation.error_message());
+            }
+            *sim = sim.clamped();
+        }
+        self.parameters_clamped = !validation.is_valid();
+        remote::publish_parameters(&sim);
+
+        self.uniform_state.update(&self.queue, self.frame);
+        self.queue.write_buffer(&self.uniform_state.simulation_parameters.buffer, 0, bytemuck::cast_slice(&[*sim]));
+    }
+
+    fn apply_command(&mut self, command: Command) {
+        match command {
+            Command::Pause => self.paused = true,
+            Command::Resume => self.paused = false,
+            Command::Step => self.step_requested = true,
+            Command::Reset => {
+                self.particles_state.reset(&self.device, &self.queue);
+                self.flip_state.reset();
+                self.frame = 0;
+                self.time = 0.0;
+            },
+            Command::Snapshot => self.snapshot_state.request(),
+            Command::Screenshot => self.capture_state.request_screenshot()
+        }
     }
 
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
-        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let output = match &self.surface {
+            Some(surface) => Some(surface.get_current_texture()?),
+            None => None
+        };
+
+        let sample = self.telemetry_state.due(self.overlay_state.as_ref().is_some_and(|overlay| overlay.visible));
+        let mut step_time = None;
+
+        //A paused simulation keeps drawing the last step
+        if !self.paused || std::mem::take(&mut self.step_requested) {
+            step_time = Some(self.step(sample));
+        }
+        let seed = self.uniform_state.step.step_uniform.seed;
+        self.snapshot_state.update(self.frame, || Snapshot::capture(&self.device, &self.queue, &self.particles_state, &self.flip_state, self.frame, self.time, seed));
 
         let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Render Encoder"),
         });
 
-        let particles_amount = SIMULATION_PARAMETERS.lock().unwrap().particles_amount;
-        let workgroups = cgmath::vec3(particles_amount.div_ceil(64), 1, 1);
+        if let Some(output) = &output {
+            let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+            self.draw(&mut encoder, &view);
+
+            //Not part of captured frames
+            if let Some(overlay) = &mut self.overlay_state {
+                overlay.draw(&self.device, &self.queue, &mut encoder, &view, [self.config.width, self.config.height], self.paused);
+            }
+        }
+
+        //Render the frame once more into the offscreen target to read it back
+        let capture = self.capture_state.wants_frame(self.frame);
+        if capture {
+            self.draw(&mut encoder, self.capture_state.view());
+            self.capture_state.capture(&self.device, &mut encoder, self.frame);
+        }
+
+        self.queue.submit(std::iter::once(encoder.finish()));
+        if let Some(output) = output {
+            output.present();
+        }
+
+        self.capture_state.poll(&self.device, false);
+
+        self.telemetry_state.frame_rendered();
+        if sample {
+            if let Some(step_time) = step_time {
+                self.telemetry_state.step_measured(step_time);
+            }
+            self.telemetry_state.set_paused(self.paused);
+            let telemetry = self.telemetry_state.publish(&self.device, &self.queue, &self.particles_state, &self.sort_state.grid_state, self.frame, self.time);
+            if let Some(overlay) = &mut self.overlay_state {
+                overlay.telemetry(telemetry);
+            }
+        }
+
+        Ok(())
+    }
+
+    //Returns the wall time until the GPU finished the step when asked to wait for it, which is when the telemetry is sampled
+    fn step(&mut self, wait: bool) -> Duration {
+        let start = Instant::now();
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Simulation Encoder"),
+        });
+
+        let sim = *SIMULATION_PARAMETERS.lock().unwrap();
+        let grid_based = sim.simulation_method().is_grid_based();
+        if grid_based {
+            //Gradients left from an earlier APIC run are stale, and reordering SPH steps don't move them along
+            if !self.grid_active {
+                encoder.clear_buffer(&self.particles_state.affine_buffer, 0, None);
+            }
+            self.flip_state.step(&self.device, &self.queue, &mut encoder, &sim, &self.particles_state, &self.uniform_state.bind_group);
+        } else {
+            self.sph_step(&mut encoder, &sim, wait);
+        }
+        self.grid_active = grid_based;
+
+        let submission = self.queue.submit(std::iter::once(encoder.finish()));
+        let step_time = if wait {
+            self.device.poll(wgpu::Maintain::WaitForSubmissionIndex(submission));
+            start.elapsed()
+        } else {
+            Duration::ZERO
+        };
+        self.frame += 1;
+        self.time += sim.time_step as f64;
+
+        //Extract the free surface from the updated particles
+        self.surface_state.update(&self.device, &self.queue, &self.particles_state, self.frame);
+
+        //Dump particle data for post-processing
+        self.export_state.update(&self.device, &self.queue, &self.particles_state, self.frame);
+
+        step_time
+    }
+
+    //Neighbour search, forces and pressure of smoothed particle hydrodynamics
+    fn sph_step(&mut self, encoder: &mut wgpu::CommandEncoder, sim: &SimulationParameters, stats: bool) {
+        let workgroups = cgmath::vec3(sim.particles_amount.div_ceil(64), 1, 1);
 
         //Predict particle's positions
-        self.setup_compute_pass(&mut encoder, &self.pre_pos_pipeline, &workgroups);
+        self.setup_compute_pass(encoder, &self.pre_pos_pipeline, &workgroups);
+
+        //Size the table for the parameters, the counting sort fills in every bucket itself
+        let counting = sim.neighbour_sort() == NeighbourSort::Counting;
+        self.sort_state.grid_state.prepare(&self.device, &self.queue, sim);
+        if !counting {
+            let table_workgroups = cgmath::vec3(NeighbourSearchGridState::table_size(sim).max(1).div_ceil(64), 1, 1);
+            self.setup_compute_pass(encoder, &self.clear_cell_start_pipeline, &table_workgroups);
+        }
 
         //Prepare data for the sort
-        self.setup_compute_pass(&mut encoder, &self.calc_hash_pipeline, &workgroups);
+        self.setup_compute_pass(encoder, &self.calc_hash_pipeline, &workgroups);
 
         //Sort for neighbour search
-        self.sort_state.sort(&mut encoder, &self.queue);
+        self.sort_state.sort(&self.device, &self.queue, encoder, sim);
 
-        //Find start for each cell in the grid
-        self.setup_compute_pass(&mut encoder, &self.cell_start_pipeline, &workgroups);
+        //Move the particles into the sorted order
+        if let Some(reorder_state) = &self.reorder_state {
+            reorder_state.reorder(encoder, sim, &self.particles_state, &self.sort_state.grid_state);
+        }
+
+        //Find start and end of each bucket after the radix sort
+        if !counting {
+            self.setup_compute_pass(encoder, &self.cell_start_pipeline, &workgroups);
+        }
+
+        //Count buckets and collisions, only for steps the telemetry is sampled after
+        if stats {
+            encoder.clear_buffer(&self.sort_state.grid_state.stats_buffer, 0, None);
+            self.setup_compute_pass(encoder, &self.hash_stats_pipeline, &workgroups);
+        }
 
         //Precompute densities for each particle
-        self.setup_compute_pass(&mut encoder, &self.d_pipeline, &workgroups);
+        self.setup_compute_pass(encoder, &self.d_pipeline, &workgroups);
+
+        //Find surface normals, vorticity and viscosity
+        self.setup_compute_pass(encoder, &self.sn_pipeline, &workgroups);
 
-        //Find surface normals and vorticity
-        self.setup_compute_pass(&mut encoder, &self.sn_pipeline, &workgroups);
+        //Connect, stretch and break springs, starting without any whenever the viscoelastic model is picked
+        let viscoelastic = sim.viscosity_model() == ViscosityModel::Viscoelastic;
+        if viscoelastic {
+            if !self.springs_active {
+                encoder.clear_buffer(&self.particles_state.springs_buffer, 0, None);
+            }
+            self.setup_compute_pass(encoder, &self.springs_pipeline, &workgroups);
+        }
+        self.springs_active = viscoelastic;
 
         //Calculate forces
-        self.setup_compute_pass(&mut encoder, &self.forces_pipeline, &workgroups);
+        self.setup_compute_pass(encoder, &self.forces_pipeline, &workgroups);
+
+        //Correct for pressure, unless the forces already did
+        let dispatch = |encoder: &mut wgpu::CommandEncoder, pipeline: &wgpu::ComputePipeline| self.setup_compute_pass(encoder, pipeline, &workgroups);
+        self.solver_state.solve(sim, encoder, &dispatch);
 
         //Smooth velocities and update positions
-        self.setup_compute_pass(&mut encoder, &self.move_pipeline, &workgroups);
+        self.setup_compute_pass(encoder, &self.move_pipeline, &workgroups);
+    }
 
-        
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                //location(0)
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color { r: 0.0, g: 0.0, b: 0.0, a: 1.0 }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
+    //Wait for the captured frames to be written out and the last step to finish
+    pub fn finish(&mut self) {
+        self.capture_state.finish(&self.device);
+        self.device.poll(wgpu::Maintain::Wait);
+    }
 
+    fn draw(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            //location(0)
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color { r: 0.0, g: 0.0, b: 0.0, a: 1.0 }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        if self.surface_state.render {
+            self.surface_state.draw(&mut render_pass, &self.uniform_state.bind_group);
+        } else {
             render_pass.set_pipeline(&self.render_pipeline);
             render_pass.set_bind_group(0, &self.uniform_state.bind_group, &[]);
             render_pass.set_bind_group(1, &self.particles_state.fields_bind_group, &[]);
 
             render_pass.set_vertex_buffer(0, self.circle_mesh_buffer.vertices.slice(..));
             render_pass.set_vertex_buffer(1, self.particles_state.particles_buffer.slice(..));
-            render_pass.set_index_buffer(self.circle_mesh_buffer.indices.slice(..), wgpu::IndexFormat::Uint16);
+            render_pass.set_index_buffer(self.circle_mesh_buffer.indices.slice(..), wgpu::IndexFormat::Uint32);
 
             render_pass.draw_indexed(0..self.circle_mesh_buffer.num_indices, 0, 0..(self.particles_state.particles.len() as u32));
         }
-
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
-
-        Ok(())
     }
 
     fn setup_compute_pass(&self, encoder: &mut wgpu::CommandEncoder, pipeline: &wgpu::ComputePipeline, workgroups: &cgmath::Vector3<u32>) {
@@ -0,0 +1,67 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use super::marching_squares::Contours;
+
+//Contours as filled paths. SVG's y axis points down, so the scene is flipped vertically
+pub fn write_svg(path: &Path, contours: &Contours, sim: &settings::SimulationParameters) -> std::io::Result<()> {
+    let p1 = sim.bounding_box.position1;
+    let p2 = sim.bounding_box.position2;
+    let width = p2[0] - p1[0];
+    let height = p2[1] - p1[1];
+
+    let mut file = BufWriter::new(File::create(path)?);
+    writeln!(file, r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {width} {height}" width="{width}" height="{height}">"#)?;
+    writeln!(file, r#"<rect width="{width}" height="{height}" fill="black"/>"#)?;
+
+    write!(file, r#"<path fill="rgb(0,181,237)" fill-rule="evenodd" stroke="white" stroke-width="1" d=""#)?;
+    for polyline in &contours.polylines {
+        for (i, point) in polyline.points.iter().enumerate() {
+            let command = if i == 0 { 'M' } else { 'L' };
+            write!(file, "{command}{:.2},{:.2} ", point[0] - p1[0], p2[1] - point[1])?;
+        }
+        if polyline.closed {
+            write!(file, "Z ")?;
+        }
+    }
+    writeln!(file, r#""/>"#)?;
+    writeln!(file, "</svg>")?;
+
+    file.flush()
+}
+
+//Filled mesh as faces and the contours as lines
+pub fn write_obj(path: &Path, contours: &Contours) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    writeln!(file, "o surface")?;
+
+    for vertex in &contours.mesh.vertices {
+        writeln!(file, "v {} {} {}", vertex.position.x, vertex.position.y, vertex.position.z)?;
+    }
+    for triangle in contours.mesh.indices.chunks_exact(3) {
+        writeln!(file, "f {} {} {}", triangle[0] + 1, triangle[1] + 1, triangle[2] + 1)?;
+    }
+
+    //Contour points are written after the mesh vertices
+    let mut offset = contours.mesh.vertices.len() + 1;
+    writeln!(file, "o contours")?;
+    for polyline in &contours.polylines {
+        for point in &polyline.points {
+            writeln!(file, "v {} {} 0", point[0], point[1])?;
+        }
+
+        write!(file, "l")?;
+        for i in 0..polyline.points.len() {
+            write!(file, " {}", offset + i)?;
+        }
+        if polyline.closed {
+            write!(file, " {offset}")?;
+        }
+        writeln!(file)?;
+
+        offset += polyline.points.len();
+    }
+
+    file.flush()
+}
@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+
+use cgmath::{Vector3, Vector4};
+use settings::kernel::{Kernel, DIMENSIONS};
+
+use crate::geometry::Mesh;
+use crate::particle::ParticleRaw;
+use crate::vertex::Vertex;
+
+use super::SurfaceField;
+
+pub const SURFACE_COLOR: Vector4<f32> = Vector4::new(0.0, 0.71, 0.93, 1.0);
+
+//Scalar field sampled at the nodes of a regular grid
+pub struct ScalarGrid {
+    pub origin: [f32; 2],
+    pub cell_size: f32,
+    pub width: usize,
+    pub height: usize,
+    pub values: Vec<f32>
+}
+
+impl ScalarGrid {
+    //Grid covers the bounding box plus one cell on each side, so contours are always closed
+    pub fn new(sim: &settings::SimulationParameters, cell_size: f32) -> Self {
+        let p1 = sim.bounding_box.position1;
+        let p2 = sim.bounding_box.position2;
+
+        let width = ((p2[0] - p1[0]) / cell_size).ceil() as usize + 3;
+        let height = ((p2[1] - p1[1]) / cell_size).ceil() as usize + 3;
+
+        ScalarGrid {
+            origin: [p1[0] - cell_size, p1[1] - cell_size],
+            cell_size,
+            width,
+            height,
+            values: vec![0.0; width * height]
+        }
+    }
+
+    pub fn value(&self, x: usize, y: usize) -> f32 {
+        self.values[y * self.width + x]
+    }
+
+    pub fn node_position(&self, x: usize, y: usize) -> [f32; 2] {
+        [
+            self.origin[0] + x as f32 * self.cell_size,
+            self.origin[1] + y as f32 * self.cell_size
+        ]
+    }
+
+    //Splat the particles onto the grid with the same poly kernel the simulation uses
+    pub fn rasterize(&mut self, sim: &settings::SimulationParameters, particles: &[ParticleRaw], densities: &[f32], field: SurfaceField) {
+        self.values.iter_mut().for_each(|v| *v = 0.0);
+
+        let h = sim.poly_kernel_radius;
+        let radius = h * sim.pixels_per_metre;
+        let reach = (radius / self.cell_size).ceil() as i64;
+
+        for (particle, &density) in particles.iter().zip(densities) {
+            let weight = match field {
+                SurfaceField::Density => sim.particle_mass,
+                SurfaceField::Colour => {
+                    if density <= 0.0 { continue; }
+                    sim.particle_mass / density
+                }
+            };
+
+            let gx = ((particle.position[0] - self.origin[0]) / self.cell_size).round() as i64;
+            let gy = ((particle.position[1] - self.origin[1]) / self.cell_size).round() as i64;
+
+            for y in (gy - reach).max(1)..=(gy + reach).min(self.height as i64 - 2) {
+                for x in (gx - reach).max(1)..=(gx + reach).min(self.width as i64 - 2) {
+                    let node = self.node_position(x as usize, y as usize);
+                    let dx = node[0] - particle.position[0];
+                    let dy = node[1] - particle.position[1];
+                    let r = (dx * dx + dy * dy).sqrt() / sim.pixels_per_metre;
+                    if r > h { continue; }
+
+                    self.values[y as usize * self.width + x as usize] += weight * Kernel::Poly6.value(r, h, DIMENSIONS);
+                }
+            }
+        }
+    }
+}
+
+pub struct Contours {
+    pub polylines: Vec<Polyline>,
+    pub mesh: Mesh
+}
+
+pub struct Polyline {
+    pub points: Vec<[f32; 2]>,
+    pub closed: bool
+}
+
+//Identifies a point of the filled polygon of a cell: either a grid node or a crossing on a grid edge
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum PointKey {
+    Node(usize),
+    Edge(usize)
+}
+
+pub fn marching_squares(grid: &ScalarGrid, iso: f32) -> Contours {
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    let mut points: HashMap<PointKey, u32> = HashMap::new();
+    let mut segments: Vec<(usize, usize)> = vec![];
+
+    let mut add_point = |key: PointKey, position: [f32; 2], vertices: &mut Vec<Vertex>| -> u32 {
+        *points.entry(key).or_insert_with(|| {
+            vertices.push(Vertex::new(Vector3::new(position[0], position[1], 0.0), SURFACE_COLOR));
+            vertices.len() as u32 - 1
+        })
+    };
+
+    for y in 0..grid.height - 1 {
+        for x in 0..grid.width - 1 {
+            //Corners in counter-clockwise order: bottom left, bottom right, top right, top left
+            let corners = [(x, y), (x + 1, y), (x + 1, y + 1), (x, y + 1)];
+            //Edges between the corners with the ids shared with the neighbouring cells
+            let edges = [
+                2 * (y * grid.width + x),
+                2 * (y * grid.width + x + 1) + 1,
+                2 * ((y + 1) * grid.width + x),
+                2 * (y * grid.width + x) + 1
+            ];
+
+            let values = corners.map(|(cx, cy)| grid.value(cx, cy));
+            let inside = values.map(|v| v >= iso);
+            if inside.iter().all(|&i| !i) { continue; }
+
+            //Walk the cell boundary collecting the polygon of the inside region
+            let mut polygon: Vec<(PointKey, u32)> = vec![];
+            for i in 0..4 {
+                let (cx, cy) = corners[i];
+                if inside[i] {
+                    let key = PointKey::Node(cy * grid.width + cx);
+                    polygon.push((key, add_point(key, grid.node_position(cx, cy), &mut vertices)));
+                }
+
+                let next = (i + 1) % 4;
+                if inside[i] != inside[next] {
+                    let t = (iso - values[i]) / (values[next] - values[i]);
+                    let a = grid.node_position(cx, cy);
+                    let b = grid.node_position(corners[next].0, corners[next].1);
+                    let position = [a[0] + t * (b[0] - a[0]), a[1] + t * (b[1] - a[1])];
+                    let key = PointKey::Edge(edges[i]);
+                    polygon.push((key, add_point(key, position, &mut vertices)));
+                }
+            }
+
+            for i in 1..polygon.len() - 1 {
+                indices.extend_from_slice(&[polygon[0].1, polygon[i].1, polygon[i + 1].1]);
+            }
+
+            //Polygon sides connecting two edge crossings lie on the contour
+            for i in 0..polygon.len() {
+                let (a, b) = (polygon[i].0, polygon[(i + 1) % polygon.len()].0);
+                if let (PointKey::Edge(a), PointKey::Edge(b)) = (a, b) {
+                    segments.push((a, b));
+                }
+            }
+        }
+    }
+
+    let position_of = |edge: usize| {
+        let v = vertices[points[&PointKey::Edge(edge)] as usize].position;
+        [v.x, v.y]
+    };
+    let polylines = join_segments(&segments).into_iter()
+        .map(|(edges, closed)| Polyline { points: edges.into_iter().map(position_of).collect(), closed })
+        .collect();
+
+    Contours {
+        polylines,
+        mesh: Mesh {
+            indices,
+            vertices
+        }
+    }
+}
+
+//Chain segments sharing an edge crossing into polylines
+fn join_segments(segments: &[(usize, usize)]) -> Vec<(Vec<usize>, bool)> {
+    let mut adjacency: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, &(a, b)) in segments.iter().enumerate() {
+        adjacency.entry(a).or_default().push(i);
+        adjacency.entry(b).or_default().push(i);
+    }
+
+    let mut used = vec![false; segments.len()];
+    let mut polylines = vec![];
+
+    //Open chains start at an end point, then whatever is left forms loops
+    let mut starts: Vec<usize> = adjacency.iter().filter(|(_, s)| s.len() == 1).map(|(&e, _)| e).collect();
+    starts.sort_unstable();
+    starts.extend(segments.iter().map(|&(a, _)| a));
+
+    for start in starts {
+        let mut current = start;
+        let mut chain = vec![start];
+
+        while let Some(&segment) = adjacency[&current].iter().find(|&&s| !used[s]) {
+            used[segment] = true;
+            let (a, b) = segments[segment];
+            current = if a == current { b } else { a };
+            chain.push(current);
+        }
+
+        if chain.len() < 2 { continue; }
+
+        let closed = chain.len() > 2 && chain.first() == chain.last();
+        if closed { chain.pop(); }
+        polylines.push((chain, closed));
+    }
+
+    polylines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Nodes one pixel apart from the origin, values given row by row from the bottom
+    fn grid(width: usize, values: &[f32]) -> ScalarGrid {
+        ScalarGrid {
+            origin: [0.0, 0.0],
+            cell_size: 1.0,
+            width,
+            height: values.len() / width,
+            values: values.to_vec()
+        }
+    }
+
+    fn sorted(points: &[[f32; 2]]) -> Vec<[f32; 2]> {
+        let mut points = points.to_vec();
+        points.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        points
+    }
+
+    #[test]
+    fn single_inside_node_gives_a_closed_diamond() {
+        let contours = marching_squares(&grid(3, &[
+            0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0
+        ]), 0.5);
+
+        assert_eq!(contours.polylines.len(), 1);
+        assert!(contours.polylines[0].closed);
+        assert_eq!(sorted(&contours.polylines[0].points), [[0.5, 1.0], [1.0, 0.5], [1.0, 1.5], [1.5, 1.0]]);
+        //One triangle per cell around the node
+        assert_eq!(contours.mesh.indices.len(), 4 * 3);
+    }
+
+    #[test]
+    fn saddle_connects_the_inside_corners() {
+        let contours = marching_squares(&grid(2, &[
+            1.0, 0.0,
+            0.0, 1.0
+        ]), 0.5);
+
+        //Two open contours, each cutting off an outside corner
+        assert_eq!(contours.polylines.len(), 2);
+        assert!(contours.polylines.iter().all(|polyline| !polyline.closed && polyline.points.len() == 2));
+        let points: Vec<[f32; 2]> = contours.polylines.iter().flat_map(|polyline| polyline.points.clone()).collect();
+        assert_eq!(sorted(&points), [[0.0, 0.5], [0.5, 0.0], [0.5, 1.0], [1.0, 0.5]]);
+        //A hexagon of both inside corners and the four crossings
+        assert_eq!(contours.mesh.indices.len(), 4 * 3);
+        assert_eq!(contours.mesh.vertices.len(), 6);
+    }
+
+    #[test]
+    fn fully_inside_grid_is_filled_without_contours() {
+        let contours = marching_squares(&grid(3, &[1.0; 9]), 0.5);
+
+        assert!(contours.polylines.is_empty());
+        //Two triangles per cell, sharing the nodes
+        assert_eq!(contours.mesh.indices.len(), 4 * 2 * 3);
+        assert_eq!(contours.mesh.vertices.len(), 9);
+    }
+
+    #[test]
+    fn blob_touching_the_border_stays_open() {
+        let contours = marching_squares(&grid(3, &[
+            1.0, 0.0, 0.0,
+            1.0, 0.0, 0.0,
+            1.0, 0.0, 0.0
+        ]), 0.5);
+
+        assert_eq!(contours.polylines.len(), 1);
+        assert!(!contours.polylines[0].closed);
+        assert_eq!(contours.polylines[0].points, [[0.5, 0.0], [0.5, 1.0], [0.5, 2.0]]);
+        assert_eq!(contours.mesh.indices.len(), 2 * 2 * 3);
+    }
+
+    #[test]
+    fn empty_grid_gives_nothing() {
+        let contours = marching_squares(&grid(3, &[0.0; 9]), 0.5);
+
+        assert!(contours.polylines.is_empty());
+        assert!(contours.mesh.indices.is_empty());
+    }
+}
@@ -0,0 +1,147 @@
+use std::path::PathBuf;
+
+use crate::cli::SurfaceArgs;
+use crate::geometry;
+use crate::particle::{ParticleRaw, ParticlesState};
+use crate::readback::read_buffer;
+use crate::uniforms::parameters::SIMULATION_PARAMETERS;
+use crate::vertex::VertexRaw;
+
+use self::marching_squares::{marching_squares, ScalarGrid};
+
+pub mod export;
+pub mod marching_squares;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SurfaceField {
+    //SPH interpolated density, iso value is relative to the rest density
+    Density,
+    //Colour field, 1 inside of the fluid and 0 outside
+    Colour
+}
+
+pub struct SurfaceState {
+    pub render: bool,
+    field: SurfaceField,
+    cell_size: f32,
+    iso: Option<f32>,
+    export_dir: Option<PathBuf>,
+    export_every: u32,
+    pipeline: wgpu::RenderPipeline,
+    mesh_buffer: Option<geometry::MeshBuffer>
+}
+
+impl SurfaceState {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, uniform_bind_group_layout: &wgpu::BindGroupLayout, args: &SurfaceArgs) -> Self {
+        if let Some(dir) = &args.surface_export {
+            if let Err(err) = std::fs::create_dir_all(dir) {
+                log::error!("Failed to create surface export directory {}: {err}", dir.display());
+            }
+        }
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Surface shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/surface.wgsl").into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Surface pipeline layout"),
+            bind_group_layouts: &[uniform_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Surface pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[VertexRaw::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        SurfaceState {
+            render: args.render_surface,
+            field: args.surface_field,
+            cell_size: args.surface_cell_size,
+            iso: args.surface_iso,
+            export_dir: args.surface_export.clone(),
+            export_every: args.surface_every.max(1),
+            pipeline,
+            mesh_buffer: None
+        }
+    }
+
+    //Extract the surface of the current particle state if it's rendered or due for export
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, particles_state: &ParticlesState, frame: u64) {
+        let export = self.export_dir.is_some() && frame.is_multiple_of(self.export_every as u64);
+        if !export && !self.render {
+            return;
+        }
+
+        let sim = *SIMULATION_PARAMETERS.lock().unwrap();
+        let particles: Vec<ParticleRaw> = read_buffer(device, queue, &particles_state.particles_buffer);
+        let densities: Vec<f32> = read_buffer(device, queue, &particles_state.density_field_buffer);
+
+        let mut grid = ScalarGrid::new(&sim, self.cell_size);
+        grid.rasterize(&sim, &particles, &densities, self.field);
+
+        let iso = self.iso.unwrap_or(match self.field {
+            SurfaceField::Density => 0.5 * sim.rest_density,
+            SurfaceField::Colour => 0.5
+        });
+        let contours = marching_squares(&grid, iso);
+
+        if let (true, Some(dir)) = (export, &self.export_dir) {
+            let svg = dir.join(format!("surface_{frame:06}.svg"));
+            if let Err(err) = export::write_svg(&svg, &contours, &sim) {
+                log::error!("Failed to write {}: {err}", svg.display());
+            }
+            let obj = dir.join(format!("surface_{frame:06}.obj"));
+            if let Err(err) = export::write_obj(&obj, &contours) {
+                log::error!("Failed to write {}: {err}", obj.display());
+            }
+        }
+
+        self.mesh_buffer = if self.render && !contours.mesh.indices.is_empty() {
+            Some(contours.mesh.into_buffer(device))
+        } else {
+            None
+        };
+    }
+
+    pub fn draw<'rp>(&'rp self, render_pass: &mut wgpu::RenderPass<'rp>, uniform_bind_group: &'rp wgpu::BindGroup) {
+        let Some(mesh_buffer) = &self.mesh_buffer else { return; };
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, uniform_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, mesh_buffer.vertices.slice(..));
+        render_pass.set_index_buffer(mesh_buffer.indices.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..mesh_buffer.num_indices, 0, 0..1);
+    }
+}
@@ -0,0 +1,92 @@
+use std::time::{Duration, Instant};
+
+use settings::protocol::{Message, Telemetry};
+
+use crate::api;
+use crate::particle::{NeighbourSearchGridState, NeighbourStats, ParticleRaw, ParticlesState};
+use crate::readback::read_buffer;
+use crate::remote;
+use crate::uniforms::parameters::SIMULATION_PARAMETERS;
+
+const INTERVAL: Duration = Duration::from_millis(250);
+
+//Publishes statistics to the connected settings clients and the control API a few times per second
+pub struct TelemetryState {
+    last_sample: Instant,
+    frames: u32,
+    //Wall time of the last step that was waited for
+    step_time: Duration,
+    paused: bool
+}
+
+impl TelemetryState {
+    pub fn new() -> Self {
+        TelemetryState {
+            last_sample: Instant::now(),
+            frames: 0,
+            step_time: Duration::ZERO,
+            paused: false
+        }
+    }
+
+    pub fn frame_rendered(&mut self) {
+        self.frames += 1;
+    }
+
+    pub fn step_measured(&mut self, step_time: Duration) {
+        self.step_time = step_time;
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    //Sampling stalls the GPU, so it's only done when someone is listening or the overlay shows it
+    pub fn due(&self, overlay: bool) -> bool {
+        self.last_sample.elapsed() >= INTERVAL && (overlay || remote::has_clients() || api::is_running())
+    }
+
+    pub fn publish(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, particles_state: &ParticlesState, grid_state: &NeighbourSearchGridState, frame: u64, time: f64) -> Telemetry {
+        let particles: Vec<ParticleRaw> = read_buffer(device, queue, &particles_state.particles_buffer);
+        let densities: Vec<f32> = read_buffer(device, queue, &particles_state.density_field_buffer);
+
+        //NaN values of an exploding simulation are skipped, the maximum shows the blow up anyway
+        let finite: Vec<f32> = densities.into_iter().filter(|d| d.is_finite()).collect();
+        let average_density = finite.iter().sum::<f32>() / finite.len().max(1) as f32;
+        let max_density = finite.iter().copied().fold(0.0, f32::max);
+        //Velocities are kept in pixels per second
+        let sim = *SIMULATION_PARAMETERS.lock().unwrap();
+        let max_speed = particles.iter()
+            .map(|p| p.velocity.iter().map(|v| v * v).sum::<f32>().sqrt())
+            .fold(0.0, f32::max) / sim.pixels_per_metre;
+
+        //The grid based methods don't search neighbours, the buffer still holds the last SPH step's
+        let neighbour_stats = if sim.simulation_method().is_grid_based() {
+            NeighbourStats::default()
+        } else {
+            grid_state.stats(device, queue)
+        };
+
+        let elapsed = self.last_sample.elapsed().as_secs_f32();
+        let telemetry = Telemetry {
+            frame,
+            time,
+            paused: self.paused,
+            fps: self.frames as f32 / elapsed,
+            step_time: self.step_time.as_secs_f32() * 1000.0,
+            particles: particles.len() as u32,
+            average_density,
+            max_density,
+            max_speed,
+            occupied_cells: neighbour_stats.occupied_cells,
+            hash_collisions: neighbour_stats.hash_collisions,
+            max_cell_particles: neighbour_stats.max_cell_particles
+        };
+        remote::publish(&Message::Telemetry(telemetry));
+        api::publish(&telemetry);
+
+        self.last_sample = Instant::now();
+        self.frames = 0;
+        telemetry
+    }
+}
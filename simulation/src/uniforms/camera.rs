@@ -64,8 +64,6 @@ impl CameraUniform {
 }
 
 pub struct CameraState {
-    pub camera: Camera,
-    pub camera_uniform: CameraUniform,
     pub buffer: wgpu::Buffer
 }
 
@@ -83,8 +81,6 @@ impl CameraState {
         );
 
         CameraState {
-            camera,
-            camera_uniform,
             buffer,
         }
     }
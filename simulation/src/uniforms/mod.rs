@@ -1,22 +1,24 @@
 use winit::dpi::PhysicalSize;
 
-use self::{camera::{Camera, CameraState}, parameters::SimulationParametersState};
+use self::{camera::{Camera, CameraState}, parameters::SimulationParametersState, step::StepState};
 
 
 pub mod camera;
 pub mod parameters;
+pub mod step;
 
 pub struct UniformState {
-    pub camera: CameraState,
     pub simulation_parameters: SimulationParametersState,
+    pub step: StepState,
     pub bind_group: wgpu::BindGroup,
     pub bind_group_layout: wgpu::BindGroupLayout
 }
 
 impl UniformState {
-    pub fn new(device: &wgpu::Device, window_size: &PhysicalSize<u32>) -> Self {
+    pub fn new(device: &wgpu::Device, window_size: &PhysicalSize<u32>, seed: u32) -> Self {
         let camera = CameraState::new(Camera::new(window_size), device);
         let simulation_parameters = SimulationParametersState::new(device);
+        let step = StepState::new(device, seed);
 
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries: &[
@@ -42,6 +44,17 @@ impl UniformState {
                     },
                     count: None,
                 },
+                //Frame counter and random seed
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
             label: Some("Uniform bind group layout"),
         });
@@ -58,18 +71,22 @@ impl UniformState {
                         binding: 1,
                         resource: simulation_parameters.buffer.as_entire_binding()
                     },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: step.buffer.as_entire_binding()
+                    },
                 ]
         });
 
         UniformState {
-            camera,
             simulation_parameters,
+            step,
             bind_group,
             bind_group_layout
         }
     }
 
-    pub fn update(&mut self, _queue: &wgpu::Queue) {
-        //TODO
+    pub fn update(&mut self, queue: &wgpu::Queue, frame: u64) {
+        self.step.update(queue, frame);
     }
 }
\ No newline at end of file
@@ -0,0 +1,42 @@
+use wgpu::util::DeviceExt;
+
+//Per step values the shaders need besides the simulation parameters
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct StepUniform {
+    pub frame: u32,
+    pub seed: u32,
+    _padding: [u32; 2]
+}
+
+pub struct StepState {
+    pub step_uniform: StepUniform,
+    pub buffer: wgpu::Buffer
+}
+
+impl StepState {
+    pub fn new(device: &wgpu::Device, seed: u32) -> Self {
+        let step_uniform = StepUniform {
+            frame: 0,
+            seed,
+            _padding: [0; 2]
+        };
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Step Buffer"),
+            contents: bytemuck::cast_slice(&[step_uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        StepState {
+            step_uniform,
+            buffer
+        }
+    }
+
+    //The frame counter wraps around, which only repeats the random sequence after 2^32 steps
+    pub fn update(&mut self, queue: &wgpu::Queue, frame: u64) {
+        self.step_uniform.frame = frame as u32;
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.step_uniform]));
+    }
+}
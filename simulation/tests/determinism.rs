@@ -0,0 +1,125 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const STEPS: u32 = 10;
+
+//Few particles in a box too small for them, so some are pushed onto the same spot
+//and the random directions separating them depend on the seed
+const SCENE: &str = r#"
+[parameters]
+particles_amount = 1024
+[parameters.bounding_box]
+position1 = [0.0, 0.0, 0.0]
+position2 = [60.0, 60.0, 1.0]
+"#;
+
+//Headless run of the scene in its own directory, listening on a socket there so runs don't share a port
+fn simulation(dir: &Path, seed: u32, steps: u32) -> Command {
+    std::fs::create_dir_all(dir).unwrap();
+    std::fs::write(dir.join("scene.toml"), SCENE).unwrap();
+
+    let mut command = Command::new(env!("CARGO_BIN_EXE_simulation"));
+    command
+        .args(["--headless", "--steps", &steps.to_string(), "--seed", &seed.to_string(), "--scene"])
+        .arg(dir.join("scene.toml"))
+        .arg("--address")
+        .arg(format!("unix:{}", dir.join("simulation.sock").display()));
+    command
+}
+
+fn check(command: &mut Command) {
+    let status = command.status().unwrap();
+    assert!(status.success(), "simulation exited with {status}");
+}
+
+//Snapshot of the last step
+fn run(dir: &Path, seed: u32) -> Vec<u8> {
+    check(simulation(dir, seed, STEPS).args(["--snapshot-every", &STEPS.to_string(), "--snapshot-dir"]).arg(dir));
+
+    std::fs::read(dir.join(format!("snapshot_{STEPS:06}.wsim"))).unwrap()
+}
+
+//Snapshot of the last step, resumed from a snapshot taken halfway
+fn run_resumed(dir: &Path, seed: u32) -> Vec<u8> {
+    let half = STEPS / 2;
+    check(simulation(dir, seed, half)
+        .args(["--snapshot-every", &half.to_string(), "--snapshot-dir"])
+        .arg(dir));
+
+    check(simulation(dir, seed, STEPS)
+        .arg("--resume")
+        .arg(dir.join(format!("snapshot_{half:06}.wsim")))
+        .args(["--snapshot-every", &STEPS.to_string(), "--snapshot-dir"])
+        .arg(dir));
+
+    std::fs::read(dir.join(format!("snapshot_{STEPS:06}.wsim"))).unwrap()
+}
+
+//Particles of the last step, snapshots differ by the seed and parameters they store anyway
+fn particles(dir: &Path, seed: u32, sort: &str) -> String {
+    check(simulation(dir, seed, STEPS)
+        .args(["--set", &format!("neighbour_sort={sort}")])
+        .args(["--export-format", "csv", "--export-every", &STEPS.to_string(), "--export"])
+        .arg(dir));
+
+    std::fs::read_to_string(dir.join(format!("particles_{STEPS:06}.csv"))).unwrap()
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("water-simulation-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn same_seed_gives_identical_particles() {
+    let (a, b) = (temp_dir("a"), temp_dir("b"));
+
+    let first = run(&a, 42);
+    let second = run(&b, 42);
+
+    let _ = std::fs::remove_dir_all(&a);
+    let _ = std::fs::remove_dir_all(&b);
+
+    assert!(first == second, "runs with the same seed diverged");
+}
+
+#[test]
+fn different_seed_changes_particles() {
+    let (a, b) = (temp_dir("seed-a"), temp_dir("seed-b"));
+
+    let first = particles(&a, 1, "Radix");
+    let second = particles(&b, 2, "Radix");
+
+    let _ = std::fs::remove_dir_all(&a);
+    let _ = std::fs::remove_dir_all(&b);
+
+    assert!(first != second, "the seed had no effect on the particles");
+}
+
+//Software adapters running on one thread count particles in index order anyway, only GPUs shuffle them
+#[test]
+fn both_sorts_give_identical_particles() {
+    let (a, b) = (temp_dir("radix"), temp_dir("counting"));
+
+    let radix = particles(&a, 42, "Radix");
+    let counting = particles(&b, 42, "Counting");
+
+    let _ = std::fs::remove_dir_all(&a);
+    let _ = std::fs::remove_dir_all(&b);
+
+    assert!(radix == counting, "the counting sort diverged from the radix sort");
+}
+
+#[test]
+fn resuming_from_a_snapshot_continues_identically() {
+    let (a, b) = (temp_dir("straight"), temp_dir("resumed"));
+
+    let straight = run(&a, 42);
+    let resumed = run_resumed(&b, 42);
+
+    let _ = std::fs::remove_dir_all(&a);
+    let _ = std::fs::remove_dir_all(&b);
+
+    assert!(straight == resumed, "the resumed run diverged from the straight one");
+}
@@ -0,0 +1,86 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use settings::descriptor::ParameterOverride;
+use settings::transport::{self, Endpoint};
+
+#[derive(Parser, Debug, Clone)]
+#[command(about = "Starts the water simulation together with its settings UI")]
+pub struct Args {
+    /// Directory containing the simulation and settings_ui binaries [default: the directory of this executable]
+    #[arg(long, value_name = "DIR")]
+    pub bin_dir: Option<PathBuf>,
+
+    /// Host the simulation listens on for the settings UI
+    #[arg(long, env = "WSIM_HOST", default_value = transport::DEFAULT_HOST)]
+    pub host: String,
+
+    /// Port the simulation and the settings UI talk over [default: a free port]
+    #[arg(long, env = "WSIM_PORT")]
+    pub port: Option<u16>,
+
+    /// Talk over a unix socket at this path instead of TCP
+    #[cfg(unix)]
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["host", "port"])]
+    pub socket: Option<PathBuf>,
+
+    /// Scene to start from, a preset file with the simulation parameters and optionally a script (.toml or .json)
+    #[arg(long, value_name = "FILE")]
+    pub scene: Option<PathBuf>,
+
+    /// Rhai script run before every step, replaces the script of the scene
+    #[arg(long, value_name = "FILE")]
+    pub script: Option<PathBuf>,
+
+    /// Override a parameter of the scene, vectors take comma separated components and choices their name, e.g. `--set gravity=0,-9.8` or `--set pressure_solver=Pbf` (repeatable)
+    #[arg(long = "set", value_name = "NAME=VALUE")]
+    pub overrides: Vec<ParameterOverride>,
+
+    /// Serve the simulation's HTTP/WebSocket control API on this port of localhost
+    #[arg(long, value_name = "PORT")]
+    pub api: Option<u16>,
+
+    /// Run the simulation without a window, implies --no-ui
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Don't start the settings UI
+    #[arg(long)]
+    pub no_ui: bool,
+
+    /// Show the settings inside the simulation's window instead of starting the settings UI
+    #[arg(long)]
+    pub overlay: bool,
+
+    /// Log filter passed on to both processes, in RUST_LOG syntax
+    #[arg(long, value_name = "FILTER", env = "RUST_LOG", default_value = "error")]
+    pub log: String,
+
+    /// How many times a crashed settings UI is started again
+    #[arg(long, value_name = "N", default_value_t = 5)]
+    pub ui_restarts: u32,
+
+    /// Further arguments for the simulation, after --
+    #[arg(last = true)]
+    pub simulation_args: Vec<String>,
+}
+
+impl Args {
+    //Picking a free port lets several simulations run side by side
+    pub fn endpoint(&self) -> std::io::Result<Endpoint> {
+        #[cfg(unix)]
+        if let Some(socket) = &self.socket {
+            return Ok(Endpoint::Unix(socket.clone()));
+        }
+
+        let port = match self.port {
+            Some(port) => port,
+            None => transport::free_port(&self.host)?
+        };
+        Ok(Endpoint::tcp(&self.host, port))
+    }
+
+    pub fn wants_ui(&self) -> bool {
+        !(self.headless || self.no_ui || self.overlay)
+    }
+}
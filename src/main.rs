@@ -1,20 +1,155 @@
-use std::process::Command;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitCode, ExitStatus, Stdio};
+use std::thread::JoinHandle;
+use std::time::Duration;
 
-fn main() -> std::result::Result<(), Box<dyn std::error::Error>>{
-    let mut simulation_process = Command::new("target/debug/simulation")
-        .env("RUST_LOG", "error")
-        .spawn()
-        .expect("Failed to spawn simulation");
+use clap::Parser;
+
+mod cli;
+
+//How often the child processes are checked on
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+fn main() -> ExitCode {
+    let args = cli::Args::parse();
+    env_logger::Builder::new().parse_filters(&args.log).init();
+
+    match run(&args) {
+        Ok(status) => exit_code(status),
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+//Runs until the simulation exits and returns its status
+fn run(args: &cli::Args) -> Result<ExitStatus, Box<dyn std::error::Error>> {
+    let bin_dir = match &args.bin_dir {
+        Some(dir) => dir.clone(),
+        None => std::env::current_exe()?.parent().ok_or("launcher has no parent directory")?.to_path_buf()
+    };
+    let simulation_path = find_binary(&bin_dir, "simulation")?;
+    let ui_path = args.wants_ui().then(|| find_binary(&bin_dir, "settings_ui")).transpose()?;
+
+    let endpoint = args.endpoint()?.to_string();
+    log::info!("Simulation and settings UI talk over {endpoint}");
+
+    let mut simulation = Command::new(&simulation_path);
+    simulation.arg("--address").arg(&endpoint);
+    if let Some(scene) = &args.scene {
+        simulation.arg("--scene").arg(scene);
+    }
+    if let Some(script) = &args.script {
+        simulation.arg("--script").arg(script);
+    }
+    for parameter in &args.overrides {
+        simulation.arg("--set").arg(parameter.to_string());
+    }
+    if let Some(api) = args.api {
+        simulation.arg("--api").arg(api.to_string());
+    }
+    if args.headless {
+        simulation.arg("--headless");
+    }
+    if args.overlay {
+        simulation.arg("--overlay");
+    }
+    simulation.args(&args.simulation_args);
+    let (mut simulation_process, simulation_output) = spawn(&mut simulation, "simulation", &args.log)?;
 
-    let mut gui_process = Command::new("target/debug/settings_ui")
-        .env("RUST_LOG", "error")
+    let mut ui = ui_path.map(|path| {
+        let mut ui = Command::new(path);
+        ui.arg("--address").arg(&endpoint);
+        ui
+    });
+    let mut ui_process = match &mut ui {
+        Some(ui) => Some(spawn(ui, "settings_ui", &args.log)?.0),
+        None => None
+    };
+    let mut restarts = 0;
+
+    let status = loop {
+        if let Some(status) = simulation_process.try_wait()? {
+            break status;
+        }
+
+        //A UI closed by the user stays closed, a crashed one is brought back
+        if let Some(status) = ui_process.as_mut().map(Child::try_wait).transpose()?.flatten() {
+            ui_process = None;
+            if !status.success() {
+                if restarts < args.ui_restarts {
+                    restarts += 1;
+                    log::error!("Settings UI exited with {status}, restarting it ({restarts}/{})", args.ui_restarts);
+                    ui_process = Some(spawn(ui.as_mut().unwrap(), "settings_ui", &args.log)?.0);
+                } else {
+                    log::error!("Settings UI exited with {status}, giving up after {restarts} restarts");
+                }
+            }
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+    };
+
+    if let Some(mut ui_process) = ui_process {
+        let _ = ui_process.kill();
+        let _ = ui_process.wait();
+    }
+    //Everything the simulation printed before exiting is passed on
+    for output in simulation_output {
+        let _ = output.join();
+    }
+    if !status.success() {
+        log::error!("Simulation exited with {status}");
+    }
+    Ok(status)
+}
+
+//Binaries are built next to the launcher, whatever the profile
+fn find_binary(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let path = dir.join(format!("{name}{}", std::env::consts::EXE_SUFFIX));
+    if path.is_file() {
+        Ok(path)
+    } else {
+        Err(format!("{} not found, build the whole workspace with `cargo build --workspace`", path.display()))
+    }
+}
+
+//Output of the child is prefixed with its name, so the logs of both processes can be told apart.
+//Returns the threads forwarding the output, they finish once the child exits
+fn spawn(command: &mut Command, name: &'static str, log: &str) -> std::io::Result<(Child, Vec<JoinHandle<()>>)> {
+    let mut child = command
+        .env("RUST_LOG", log)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
         .spawn()
-        .expect("Failed to spawn GUI application");
+        .map_err(|err| std::io::Error::new(err.kind(), format!("Failed to start {name}: {err}")))?;
+
+    let mut forwarders = vec![];
+    if let Some(stdout) = child.stdout.take() {
+        forwarders.push(std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                println!("[{name}] {line}");
+            }
+        }));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forwarders.push(std::thread::spawn(move || {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                eprintln!("[{name}] {line}");
+            }
+        }));
+    }
+
+    Ok((child, forwarders))
+}
 
-    match simulation_process.wait() {
-        Ok(_) => {}
-        Err(err) => println!("Simulation process exited with error: {err}")
+//Signals and codes that don't fit are reported as a plain failure
+fn exit_code(status: ExitStatus) -> ExitCode {
+    match status.code() {
+        Some(0) => ExitCode::SUCCESS,
+        Some(code) => ExitCode::from(u8::try_from(code).unwrap_or(1)),
+        None => ExitCode::FAILURE
     }
-    let _ = gui_process.kill();
-    Ok(())
-}
\ No newline at end of file
+}